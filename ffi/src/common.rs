@@ -45,3 +45,39 @@ pub unsafe extern "C" fn esp_string_array_free(array: *mut *mut c_char, size: si
 
     drop(strings);
 }
+
+/// Free the memory allocated for the given resolved `FormID` arrays.
+///
+/// # Safety
+///
+/// The given `form_ids` and `is_overridden` arrays must have been allocated by
+/// esplugin as the outputs of the same call to
+/// [`super::esp_plugin_resolved_form_ids`], and `count` must be the count that
+/// was output by that call.
+///
+/// This function must not be called twice for the same arrays.
+///
+/// This function is thread-safe.
+#[no_mangle]
+pub unsafe extern "C" fn esp_resolved_form_ids_free(
+    form_ids: *mut u32,
+    is_overridden: *mut bool,
+    count: size_t,
+) {
+    if count == 0 {
+        return;
+    }
+
+    if !form_ids.is_null() {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+            form_ids, count,
+        )));
+    }
+
+    if !is_overridden.is_null() {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+            is_overridden,
+            count,
+        )));
+    }
+}
@@ -43,6 +43,12 @@ pub static ESP_ERROR_UNRESOLVED_RECORD_IDS: u32 = 13;
 #[no_mangle]
 pub static ESP_ERROR_PLUGIN_METADATA_NOT_FOUND: u32 = 14;
 
+#[no_mangle]
+pub static ESP_ERROR_RECORDS_NOT_RETAINED: u32 = 15;
+
+#[no_mangle]
+pub static ESP_ERROR_PLUGIN_FILENAME_MISMATCH: u32 = 16;
+
 #[no_mangle]
 pub static ESP_GAME_OBLIVION: u32 = 0;
 
@@ -66,3 +72,6 @@ pub static ESP_GAME_SKYRIMSE: u32 = 6;
 
 #[no_mangle]
 pub static ESP_GAME_STARFIELD: u32 = 7;
+
+#[no_mangle]
+pub static ESP_GAME_FALLOUT76: u32 = 8;
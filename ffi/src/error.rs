@@ -7,8 +7,9 @@ use esplugin::Error;
 use crate::constants::{
     ESP_ERROR_FILE_NOT_FOUND, ESP_ERROR_IO_ERROR, ESP_ERROR_IO_PERMISSION_DENIED,
     ESP_ERROR_NO_FILENAME, ESP_ERROR_NULL_POINTER, ESP_ERROR_PANICKED, ESP_ERROR_PARSE_ERROR,
-    ESP_ERROR_PLUGIN_METADATA_NOT_FOUND, ESP_ERROR_TEXT_DECODE_ERROR,
-    ESP_ERROR_UNRESOLVED_RECORD_IDS, ESP_OK,
+    ESP_ERROR_PLUGIN_FILENAME_MISMATCH, ESP_ERROR_PLUGIN_METADATA_NOT_FOUND,
+    ESP_ERROR_RECORDS_NOT_RETAINED, ESP_ERROR_TEXT_DECODE_ERROR, ESP_ERROR_UNRESOLVED_RECORD_IDS,
+    ESP_OK,
 };
 
 thread_local!(static ERROR_MESSAGE: RefCell<CString> = RefCell::new(CString::default()));
@@ -70,5 +71,7 @@ fn map_error(err: &Error) -> c_uint {
         Error::DecodeError(_) => ESP_ERROR_TEXT_DECODE_ERROR,
         Error::UnresolvedRecordIds(_) => ESP_ERROR_UNRESOLVED_RECORD_IDS,
         Error::PluginMetadataNotFound(_) => ESP_ERROR_PLUGIN_METADATA_NOT_FOUND,
+        Error::RecordsNotRetained(_) => ESP_ERROR_RECORDS_NOT_RETAINED,
+        Error::PluginFilenameMismatch(_, _) => ESP_ERROR_PLUGIN_FILENAME_MISMATCH,
     }
 }
@@ -7,9 +7,9 @@ use esplugin::{GameId, Plugin};
 use crate::{
     constants::{
         ESP_ERROR_INVALID_GAME_ID, ESP_ERROR_NOT_UTF8, ESP_ERROR_NULL_POINTER,
-        ESP_ERROR_TEXT_ENCODE_ERROR, ESP_GAME_FALLOUT3, ESP_GAME_FALLOUT4, ESP_GAME_FALLOUTNV,
-        ESP_GAME_MORROWIND, ESP_GAME_OBLIVION, ESP_GAME_SKYRIM, ESP_GAME_SKYRIMSE,
-        ESP_GAME_STARFIELD,
+        ESP_ERROR_TEXT_ENCODE_ERROR, ESP_GAME_FALLOUT3, ESP_GAME_FALLOUT4, ESP_GAME_FALLOUT76,
+        ESP_GAME_FALLOUTNV, ESP_GAME_MORROWIND, ESP_GAME_OBLIVION, ESP_GAME_SKYRIM,
+        ESP_GAME_SKYRIMSE, ESP_GAME_STARFIELD,
     },
     error::error,
 };
@@ -71,6 +71,7 @@ pub(crate) fn map_game_id(game_id: u32) -> Result<GameId, u32> {
         x if x == ESP_GAME_FALLOUT4 => Ok(GameId::Fallout4),
         x if x == ESP_GAME_SKYRIMSE => Ok(GameId::SkyrimSE),
         x if x == ESP_GAME_STARFIELD => Ok(GameId::Starfield),
+        x if x == ESP_GAME_FALLOUT76 => Ok(GameId::Fallout76),
         _ => Err(error(
             ESP_ERROR_INVALID_GAME_ID,
             &format!("Invalid game ID: {game_id}"),
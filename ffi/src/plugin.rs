@@ -86,6 +86,7 @@ pub unsafe extern "C" fn esp_plugin_free(plugin_ptr: *mut Plugin) {
 /// * [`esp_plugin_records_overlap_size`]
 /// * [`esp_plugin_is_valid_as_light_plugin`]
 /// * [`esp_plugin_is_valid_as_update_plugin`]
+/// * [`esp_plugin_resolved_form_ids`]
 ///
 /// Returns [`ESP_OK`] if successful, otherwise an `ESP_ERROR_*` code is returned.
 ///
@@ -144,6 +145,72 @@ pub unsafe extern "C" fn esp_plugin_resolve_record_ids(
     .unwrap_or(ESP_ERROR_PANICKED)
 }
 
+/// Outputs the `FormIDs` of the plugin's records, sorted in the same order as
+/// the plugin's resolved record IDs, along with a parallel array that gives
+/// whether each record is an override of a record from one of the plugin's
+/// masters.
+///
+/// The `FormIDs` are as they appear in the plugin. Records that don't have
+/// `FormIDs` (i.e. Morrowind records) are not included. The plugin's record IDs
+/// must have been resolved using [`esp_plugin_resolve_record_ids`] for
+/// Starfield plugins.
+///
+/// Returns [`ESP_OK`] if successful, otherwise an `ESP_ERROR_*` code is returned.
+///
+/// # Safety
+///
+/// `plugin_ptr` must be null or the output of a call to [`esp_plugin_new`],
+/// though a null pointer will cause an error to be returned.
+///
+/// `form_ids`, `is_overridden` and `count` must be valid pointers.
+///
+/// Both arrays are written in a single allocation each, and their lifetimes
+/// are independent of the lifetime of the plugin handle: ownership of them is
+/// passed to the caller. They must only be deallocated using
+/// [`super::esp_resolved_form_ids_free`]. If there are no `FormIDs`, both array
+/// pointers are null.
+///
+/// It is safe to call this function in parallel with any function so long as
+/// that function does not mutate the same data.
+#[no_mangle]
+pub unsafe extern "C" fn esp_plugin_resolved_form_ids(
+    plugin_ptr: *const Plugin,
+    form_ids: *mut *mut u32,
+    is_overridden: *mut *mut bool,
+    count: *mut size_t,
+) -> u32 {
+    panic::catch_unwind(|| {
+        if plugin_ptr.is_null() || form_ids.is_null() || is_overridden.is_null() || count.is_null()
+        {
+            error(ESP_ERROR_NULL_POINTER, "Null pointer passed")
+        } else {
+            let plugin = &*plugin_ptr;
+
+            let record_ids = match plugin.resolved_record_ids() {
+                Ok(x) => x,
+                Err(e) => return handle_error(&e),
+            };
+
+            let (ids, overridden): (Vec<u32>, Vec<bool>) = record_ids
+                .iter()
+                .filter_map(|r| r.raw_form_id().map(|f| (f, r.is_overridden_record())))
+                .unzip();
+
+            *count = ids.len();
+            if ids.is_empty() {
+                *form_ids = ptr::null_mut();
+                *is_overridden = ptr::null_mut();
+            } else {
+                *form_ids = Box::into_raw(ids.into_boxed_slice()).cast();
+                *is_overridden = Box::into_raw(overridden.into_boxed_slice()).cast();
+            }
+
+            ESP_OK
+        }
+    })
+    .unwrap_or(ESP_ERROR_PANICKED)
+}
+
 /// Outputs the filename associated with the given plugin handle.
 ///
 /// The filename string will be encoded in UTF-8.
@@ -14,6 +14,7 @@ void test_game_id_values() {
   assert(ESP_GAME_FALLOUT4 == 5);
   assert(ESP_GAME_SKYRIMSE == 6);
   assert(ESP_GAME_STARFIELD == 7);
+  assert(ESP_GAME_FALLOUT76 == 8);
 }
 
 void test_esp_get_error_message() {
@@ -0,0 +1,169 @@
+/*
+ * This file is part of esplugin
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * esplugin is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * esplugin is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with esplugin. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::collections::{BTreeSet, HashMap};
+
+use crate::error::Error;
+use crate::plugin::Plugin;
+use crate::record_id::ResolvedRecordId;
+
+/// An index of which plugins contain which records, for answering many
+/// overlap queries about a set of plugins without having to compare their
+/// records each time. Plugins are identified by their index in the slice that
+/// the index was built from.
+#[derive(Clone, Debug, Default)]
+pub struct ConflictIndex {
+    plugins_by_record: HashMap<ResolvedRecordId, Vec<usize>>,
+    overlapping_plugins: Vec<BTreeSet<usize>>,
+}
+
+impl ConflictIndex {
+    /// Build an index of the given plugins' records. This needs the plugins'
+    /// records to be resolved first if any are Morrowind or Starfield plugins.
+    pub fn new(plugins: &[&Plugin]) -> Result<Self, Error> {
+        let mut plugins_by_record: HashMap<ResolvedRecordId, Vec<usize>> = HashMap::new();
+
+        for (index, plugin) in plugins.iter().enumerate() {
+            for record_id in plugin.resolved_record_ids()? {
+                let indices = plugins_by_record.entry(*record_id).or_default();
+                // A plugin may contain more than one record with the same ID.
+                if indices.last() != Some(&index) {
+                    indices.push(index);
+                }
+            }
+        }
+
+        let mut overlapping_plugins = vec![BTreeSet::new(); plugins.len()];
+        for indices in plugins_by_record.values().filter(|i| i.len() > 1) {
+            for index in indices {
+                if let Some(overlapping) = overlapping_plugins.get_mut(*index) {
+                    overlapping.extend(indices.iter().filter(|i| *i != index));
+                }
+            }
+        }
+
+        Ok(Self {
+            plugins_by_record,
+            overlapping_plugins,
+        })
+    }
+
+    /// Get the indices of the plugins that contain the given record, in
+    /// ascending order.
+    pub fn plugins_with_record(&self, record_id: &ResolvedRecordId) -> &[usize] {
+        self.plugins_by_record
+            .get(record_id)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Get the indices of the other plugins that have at least one record in
+    /// common with the plugin at the given index, in ascending order. This is
+    /// empty if the index is out of range.
+    pub fn overlapping_plugins(&self, plugin_index: usize) -> Vec<usize> {
+        self.overlapping_plugins
+            .get(plugin_index)
+            .map(|o| o.iter().copied().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::path::Path;
+
+    use crate::game_id::GameId;
+    use crate::plugin::ParseOptions;
+    use crate::test_utils::{group_bytes, plugin_bytes, record_bytes};
+
+    use super::*;
+
+    fn skyrim_plugin(filename: &str, form_ids: &[u32]) -> Plugin {
+        let records: Vec<u8> = form_ids
+            .iter()
+            .flat_map(|form_id| record_bytes(*b"GLOB", 0, *form_id, &[]))
+            .collect();
+        let bytes = plugin_bytes(&["Skyrim.esm"], &group_bytes(*b"GLOB", &records));
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new(filename));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::whole_plugin())
+            .unwrap();
+        plugin
+    }
+
+    #[test]
+    fn plugins_with_record_should_list_every_plugin_containing_the_record() {
+        let a = skyrim_plugin("A.esp", &[0x1, 0x2]);
+        let b = skyrim_plugin("B.esp", &[0x2, 0x0100_0001]);
+        let c = skyrim_plugin("C.esp", &[0x2]);
+
+        let index = ConflictIndex::new(&[&a, &b, &c]).unwrap();
+
+        let record_ids = a.resolved_record_ids().unwrap();
+        assert_eq!(&[0], index.plugins_with_record(&record_ids[0]));
+        assert_eq!(&[0, 1, 2], index.plugins_with_record(&record_ids[1]));
+
+        let new_record = b
+            .resolved_record_ids()
+            .unwrap()
+            .iter()
+            .find(|r| !r.is_overridden_record())
+            .unwrap();
+        assert_eq!(&[1], index.plugins_with_record(new_record));
+    }
+
+    #[test]
+    fn plugins_with_record_should_list_a_plugin_once_if_it_has_duplicate_records() {
+        let a = skyrim_plugin("A.esp", &[0x1, 0x1]);
+        let b = skyrim_plugin("B.esp", &[0x1]);
+
+        let index = ConflictIndex::new(&[&a, &b]).unwrap();
+
+        let record_ids = a.resolved_record_ids().unwrap();
+        assert_eq!(&[0, 1], index.plugins_with_record(&record_ids[0]));
+    }
+
+    #[test]
+    fn overlapping_plugins_should_list_other_plugins_that_share_a_record() {
+        let a = skyrim_plugin("A.esp", &[0x1, 0x2]);
+        let b = skyrim_plugin("B.esp", &[0x2]);
+        let c = skyrim_plugin("C.esp", &[0x3]);
+        let d = skyrim_plugin("D.esp", &[0x1]);
+
+        let index = ConflictIndex::new(&[&a, &b, &c, &d]).unwrap();
+
+        assert_eq!(vec![1, 3], index.overlapping_plugins(0));
+        assert_eq!(vec![0], index.overlapping_plugins(1));
+        assert!(index.overlapping_plugins(2).is_empty());
+        assert!(index.overlapping_plugins(4).is_empty());
+    }
+
+    #[test]
+    fn new_should_error_if_a_plugin_has_unresolved_record_ids() {
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new("A.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes(&[], &[])),
+                ParseOptions::whole_plugin(),
+            )
+            .unwrap();
+
+        assert!(ConflictIndex::new(&[&plugin]).is_err());
+    }
+}
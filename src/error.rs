@@ -37,6 +37,8 @@ pub enum Error {
     DecodeError(Box<[u8]>),
     UnresolvedRecordIds(PathBuf),
     PluginMetadataNotFound(String),
+    RecordsNotRetained(PathBuf),
+    PluginFilenameMismatch(PathBuf, PathBuf),
 }
 
 impl From<Err<nom::error::Error<&[u8]>>> for Error {
@@ -101,6 +103,21 @@ impl fmt::Display for Error {
             Error::PluginMetadataNotFound(plugin) => {
                 write!(f, "Plugin metadata for \"{plugin}\" not found")
             }
+            Error::RecordsNotRetained(path) => {
+                write!(
+                    f,
+                    "Records were not retained when parsing the plugin at \"{}\"",
+                    escape_ascii(path)
+                )
+            }
+            Error::PluginFilenameMismatch(path1, path2) => {
+                write!(
+                    f,
+                    "The plugins at \"{}\" and \"{}\" have different filenames",
+                    escape_ascii(path1),
+                    escape_ascii(path2)
+                )
+            }
         }
     }
 }
@@ -126,6 +143,28 @@ pub enum ParsingErrorKind {
     SubrecordDataTooShort(usize),
     /// The String field is the name of the parser that errored.
     GenericParserError(String),
+    /// The usize field is the declared size of the data that was too large to
+    /// allocate a buffer for.
+    OversizedField(usize),
+    /// A plugin's header record has no data, though it should at least have a
+    /// HEDR subrecord.
+    EmptyHeader,
+    /// The u32 field is the declared size of a record's data, which its
+    /// subrecords don't exactly fill.
+    RecordDataSizeMismatch(u32),
+    /// The usize field is the maximum number of records that may be read,
+    /// which the plugin has more than.
+    BudgetExceeded(usize),
+    /// The u32 field is the declared size of a group, which is either smaller
+    /// than a group header or larger than the data that contains the group.
+    InvalidGroupSize(u32),
+    /// The data ended part-way through a read: `expected` is the number of
+    /// bytes that were being read, and `offset` is the number of them that
+    /// were read before the data ended.
+    UnexpectedEof { expected: usize, offset: usize },
+    /// A record's data is compressed and needs to be read, but decompression
+    /// support isn't enabled.
+    UnsupportedCompression,
 }
 
 impl fmt::Display for ParsingErrorKind {
@@ -139,6 +178,30 @@ impl fmt::Display for ParsingErrorKind {
                 "Subrecord data field too short, expected at least {s} bytes",
             ),
             ParsingErrorKind::GenericParserError(e) => write!(f, "Error in parser: {e}"),
+            ParsingErrorKind::OversizedField(s) => write!(
+                f,
+                "Declared data size of {s} bytes exceeds the allocation limit",
+            ),
+            ParsingErrorKind::EmptyHeader => write!(f, "The plugin header record is empty"),
+            ParsingErrorKind::RecordDataSizeMismatch(s) => write!(
+                f,
+                "Record subrecords do not fit the declared record data size of {s} bytes",
+            ),
+            ParsingErrorKind::BudgetExceeded(n) => {
+                write!(f, "The plugin has more than the limit of {n} records")
+            }
+            ParsingErrorKind::InvalidGroupSize(s) => write!(
+                f,
+                "Declared group size of {s} bytes does not fit the data containing the group",
+            ),
+            ParsingErrorKind::UnexpectedEof { expected, offset } => write!(
+                f,
+                "The data ended after {offset} of the {expected} bytes that were being read",
+            ),
+            ParsingErrorKind::UnsupportedCompression => write!(
+                f,
+                "The record data is compressed, but decompression support is not enabled",
+            ),
         }
     }
 }
@@ -16,6 +16,7 @@
  * You should have received a copy of the GNU General Public License
  * along with esplugin. If not, see <http://www.gnu.org/licenses/>.
  */
+use crate::plugin::{Plugin, PluginScale};
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
 pub enum GameId {
@@ -27,6 +28,20 @@ pub enum GameId {
     Fallout4,
     SkyrimSE,
     Starfield,
+    Fallout76,
+}
+
+/// The maximum numbers of plugins of each scale that a game can load at once.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct PluginLimits {
+    /// The maximum number of full plugins.
+    pub full: u32,
+    /// The maximum number of light plugins, or `None` if the game doesn't
+    /// support light plugins.
+    pub light: Option<u32>,
+    /// The maximum number of medium plugins, or `None` if the game doesn't
+    /// support medium plugins.
+    pub medium: Option<u32>,
 }
 
 impl GameId {
@@ -40,6 +55,84 @@ impl GameId {
     pub fn supports_medium_plugins(self) -> bool {
         self == GameId::Starfield
     }
+
+    pub fn supports_update_plugins(self) -> bool {
+        self == GameId::Starfield
+    }
+
+    pub fn supports_blueprint_plugins(self) -> bool {
+        self == GameId::Starfield
+    }
+
+    /// Get the maximum number of full plugins that the game can load at once.
+    /// Each full plugin has its own mod index, and the last one or two indexes
+    /// are reserved for light and medium plugins in games that support them.
+    pub fn max_full_plugins(self) -> u32 {
+        match self {
+            GameId::Starfield => 253,
+            GameId::SkyrimSE | GameId::Fallout4 => 254,
+            _ => 255,
+        }
+    }
+
+    /// Get the maximum number of medium plugins that the game can load at
+    /// once, or `None` if the game doesn't support medium plugins.
+    pub fn max_medium_plugins(self) -> Option<u32> {
+        self.supports_medium_plugins().then_some(256)
+    }
+
+    /// Get the maximum number of light plugins that the game can load at once,
+    /// or `None` if the game doesn't support light plugins.
+    pub fn max_light_plugins(self) -> Option<u32> {
+        self.supports_light_plugins().then_some(4096)
+    }
+
+    /// Get the maximum numbers of full, light and medium plugins that the game
+    /// can load at once.
+    pub fn plugin_limits(self) -> PluginLimits {
+        PluginLimits {
+            full: self.max_full_plugins(),
+            light: self.max_light_plugins(),
+            medium: self.max_medium_plugins(),
+        }
+    }
+
+    /// Get the maximum length in bytes of a plugin description that the game
+    /// will display in full, not including any null terminator. Morrowind
+    /// descriptions are stored in a fixed-length field, while other games'
+    /// editors limit descriptions to 512 bytes.
+    pub fn max_description_length(self) -> usize {
+        match self {
+            GameId::Morrowind => 256,
+            _ => 512,
+        }
+    }
+
+    /// Get the smallest plugin scale that the game supports, i.e. the scale
+    /// with the fewest available record slots.
+    pub fn max_plugin_scale(self) -> PluginScale {
+        if self.supports_light_plugins() {
+            PluginScale::Small
+        } else if self.supports_medium_plugins() {
+            PluginScale::Medium
+        } else {
+            PluginScale::Full
+        }
+    }
+
+    /// Get the scale of load order slot that the given plugin would take up
+    /// if it was active, i.e. which of the game's plugin limits it counts
+    /// towards. If the plugin's scale isn't supported by this game, it takes
+    /// up a full slot. A plugin whose header hasn't been parsed has no flags
+    /// set, so it takes up a full slot unless its file extension makes it
+    /// light.
+    pub fn plugin_slot_cost(self, plugin: &Plugin) -> PluginScale {
+        match plugin.scale() {
+            PluginScale::Small if self.supports_light_plugins() => PluginScale::Small,
+            PluginScale::Medium if self.supports_medium_plugins() => PluginScale::Medium,
+            _ => PluginScale::Full,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -71,6 +164,11 @@ mod tests {
         assert!(!GameId::FalloutNV.supports_light_plugins());
     }
 
+    #[test]
+    fn supports_light_plugins_should_be_false_for_fallout76() {
+        assert!(!GameId::Fallout76.supports_light_plugins());
+    }
+
     #[test]
     fn supports_light_plugins_should_be_true_for_skyrimse() {
         assert!(GameId::SkyrimSE.supports_light_plugins());
@@ -95,6 +193,87 @@ mod tests {
         assert!(!GameId::Fallout3.supports_medium_plugins());
         assert!(!GameId::FalloutNV.supports_medium_plugins());
         assert!(!GameId::Fallout4.supports_medium_plugins());
+        assert!(!GameId::Fallout76.supports_medium_plugins());
         assert!(GameId::Starfield.supports_medium_plugins());
     }
+
+    #[test]
+    fn supports_update_and_blueprint_plugins_should_be_true_for_only_starfield() {
+        assert!(!GameId::Morrowind.supports_update_plugins());
+        assert!(!GameId::SkyrimSE.supports_update_plugins());
+        assert!(!GameId::Fallout4.supports_update_plugins());
+        assert!(GameId::Starfield.supports_update_plugins());
+
+        assert!(!GameId::Morrowind.supports_blueprint_plugins());
+        assert!(!GameId::SkyrimSE.supports_blueprint_plugins());
+        assert!(!GameId::Fallout4.supports_blueprint_plugins());
+        assert!(GameId::Starfield.supports_blueprint_plugins());
+    }
+
+    #[test]
+    fn max_plugin_scale_should_be_the_smallest_scale_supported_by_the_game() {
+        assert_eq!(PluginScale::Full, GameId::Morrowind.max_plugin_scale());
+        assert_eq!(PluginScale::Full, GameId::Oblivion.max_plugin_scale());
+        assert_eq!(PluginScale::Full, GameId::Skyrim.max_plugin_scale());
+        assert_eq!(PluginScale::Small, GameId::SkyrimSE.max_plugin_scale());
+        assert_eq!(PluginScale::Small, GameId::Fallout4.max_plugin_scale());
+        assert_eq!(PluginScale::Full, GameId::Fallout76.max_plugin_scale());
+        assert_eq!(PluginScale::Small, GameId::Starfield.max_plugin_scale());
+    }
+
+    #[test]
+    fn max_full_plugins_should_exclude_mod_indexes_reserved_for_smaller_plugins() {
+        assert_eq!(255, GameId::Morrowind.max_full_plugins());
+        assert_eq!(255, GameId::Oblivion.max_full_plugins());
+        assert_eq!(255, GameId::FalloutNV.max_full_plugins());
+        assert_eq!(254, GameId::SkyrimSE.max_full_plugins());
+        assert_eq!(254, GameId::Fallout4.max_full_plugins());
+        assert_eq!(253, GameId::Starfield.max_full_plugins());
+    }
+
+    #[test]
+    fn max_description_length_should_be_256_for_morrowind_and_512_otherwise() {
+        assert_eq!(256, GameId::Morrowind.max_description_length());
+        assert_eq!(512, GameId::Oblivion.max_description_length());
+        assert_eq!(512, GameId::SkyrimSE.max_description_length());
+        assert_eq!(512, GameId::Starfield.max_description_length());
+    }
+
+    #[test]
+    fn max_light_and_medium_plugins_should_be_none_if_the_scale_is_unsupported() {
+        assert_eq!(None, GameId::Skyrim.max_light_plugins());
+        assert_eq!(Some(4096), GameId::SkyrimSE.max_light_plugins());
+        assert_eq!(Some(4096), GameId::Starfield.max_light_plugins());
+
+        assert_eq!(None, GameId::SkyrimSE.max_medium_plugins());
+        assert_eq!(Some(256), GameId::Starfield.max_medium_plugins());
+    }
+
+    #[test]
+    fn plugin_limits_should_combine_the_limits_for_each_plugin_scale() {
+        assert_eq!(
+            PluginLimits {
+                full: 255,
+                light: None,
+                medium: None
+            },
+            GameId::Skyrim.plugin_limits()
+        );
+        assert_eq!(
+            PluginLimits {
+                full: 254,
+                light: Some(4096),
+                medium: None
+            },
+            GameId::Fallout4.plugin_limits()
+        );
+        assert_eq!(
+            PluginLimits {
+                full: 253,
+                light: Some(4096),
+                medium: Some(256)
+            },
+            GameId::Starfield.plugin_limits()
+        );
+    }
 }
@@ -16,18 +16,18 @@
  * You should have received a copy of the GNU General Public License
  * along with esplugin. If not, see <http://www.gnu.org/licenses/>.
  */
-use std::io::{BufRead, Seek};
+use std::io::{BufRead, Seek, SeekFrom};
 
 use nom::bytes::complete::{tag, take};
-use nom::combinator::{all_consuming, map};
+use nom::combinator::all_consuming;
 use nom::number::complete::le_u32;
 use nom::sequence::delimited;
 use nom::{IResult, Parser};
 
 use crate::error::Error;
 use crate::game_id::GameId;
-use crate::record::Record;
-use crate::record_id::RecordId;
+use crate::record::{read_exact, Record, RecordCounter};
+use crate::record_id::{RecordId, TypedFormId};
 use crate::ParsingErrorKind;
 
 const GROUP_TYPE: &[u8] = b"GRUP";
@@ -36,31 +36,260 @@ const GROUP_TYPE: &[u8] = b"GRUP";
 pub(crate) struct Group;
 
 impl Group {
+    /// Reads the FormIDs of the records in the group and its subgroups. If
+    /// `strict` is true, each record's data is also read to check that its
+    /// subrecords exactly fill it. Each record is counted using `counter`.
     pub(crate) fn read_form_ids<R: BufRead + Seek>(
         reader: &mut R,
         game_id: GameId,
-        form_ids: &mut Vec<u32>,
+        form_ids: &mut Vec<TypedFormId>,
         header_buffer: &mut [u8],
+        max_alloc: usize,
+        strict: bool,
+        counter: &mut RecordCounter,
     ) -> Result<(), Error> {
-        let group_header_length = group_or_record_header_length(game_id);
-        let skip_length = get_header_length_to_skip(game_id);
+        read_group(
+            reader,
+            game_id,
+            header_buffer,
+            &mut |reader, header_bytes| {
+                counter.count(header_bytes)?;
+                read_form_id(reader, game_id, header_bytes, form_ids, max_alloc, strict)
+            },
+        )?;
 
-        // Get a slice of the right size from the header buffer.
-        let Some(header_bytes) = header_buffer.get_mut(..usize::from(group_header_length)) else {
-            return Err(Error::ParsingError(
-                header_buffer.to_vec().into_boxed_slice(),
-                ParsingErrorKind::GenericParserError("Group::read_form_ids".into()),
-            ));
-        };
-        reader.read_exact(header_bytes)?;
+        Ok(())
+    }
+
+    /// Reads the FormIDs of the records in the group and its subgroups if the
+    /// group has the given label, and otherwise skips over the group's
+    /// contents. Returns true if the group was read.
+    pub(crate) fn read_form_ids_if_labelled<R: BufRead + Seek>(
+        reader: &mut R,
+        game_id: GameId,
+        label: [u8; 4],
+        form_ids: &mut Vec<TypedFormId>,
+        header_buffer: &mut [u8],
+        max_alloc: usize,
+    ) -> Result<bool, Error> {
+        let (group_label, size_of_records) = read_group_header(reader, game_id, header_buffer)?;
+
+        if group_label != label {
+            reader.seek_relative(i64::from(size_of_records))?;
+            return Ok(false);
+        }
+
+        read_records(
+            reader,
+            game_id,
+            header_buffer,
+            size_of_records,
+            &mut |reader, header_bytes| {
+                read_form_id(reader, game_id, header_bytes, form_ids, max_alloc, false)
+            },
+        )?;
+
+        Ok(true)
+    }
+
+    pub(crate) fn read_records<R: BufRead + Seek>(
+        reader: &mut R,
+        game_id: GameId,
+        form_ids: &mut Vec<TypedFormId>,
+        records: &mut Vec<Record>,
+        header_buffer: &mut [u8],
+        max_alloc: usize,
+        counter: &mut RecordCounter,
+    ) -> Result<(), Error> {
+        read_group(
+            reader,
+            game_id,
+            header_buffer,
+            &mut |reader, header_bytes| {
+                counter.count(header_bytes)?;
+                let (bytes_read, record, record_id) =
+                    Record::read_with_header(reader, game_id, header_bytes, max_alloc)?;
+
+                if let Some(RecordId::FormId(form_id, record_type)) = record_id {
+                    form_ids.push(TypedFormId {
+                        form_id: form_id.get(),
+                        record_type,
+                    });
+                }
+                records.push(record);
+
+                Ok(bytes_read)
+            },
+        )?;
+
+        Ok(())
+    }
+
+    /// Adds the compressed and decompressed data sizes of compressed records
+    /// in the group to the given totals.
+    pub(crate) fn read_compressed_sizes<R: BufRead + Seek>(
+        reader: &mut R,
+        game_id: GameId,
+        totals: &mut (u64, u64),
+        header_buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        read_group(
+            reader,
+            game_id,
+            header_buffer,
+            &mut |reader, header_bytes| {
+                let (bytes_read, sizes) =
+                    Record::read_compressed_size(reader, game_id, header_bytes)?;
 
-        let (_, size_of_records) =
-            all_consuming(parse_header(group_header_length, skip_length)).parse(header_bytes)?;
+                if let Some((compressed_size, decompressed_size)) = sizes {
+                    totals.0 += u64::from(compressed_size);
+                    totals.1 += u64::from(decompressed_size);
+                }
 
-        read_records(reader, game_id, form_ids, header_buffer, size_of_records)
+                Ok(bytes_read)
+            },
+        )?;
+
+        Ok(())
+    }
+
+    /// Counts the records in the group and its subgroups, returning the
+    /// group's label and the count.
+    pub(crate) fn count_records<R: BufRead + Seek>(
+        reader: &mut R,
+        game_id: GameId,
+        header_buffer: &mut [u8],
+    ) -> Result<([u8; 4], usize), Error> {
+        let mut count = 0;
+
+        let label = read_group(
+            reader,
+            game_id,
+            header_buffer,
+            &mut |reader, header_bytes| {
+                count += 1;
+                Record::skip(reader, game_id, header_bytes)
+            },
+        )?;
+
+        Ok((label, count))
     }
 }
 
+/// Reads a group's header and then its contents, calling `read_record` for
+/// each record found in the group or its subgroups. The callback is given the
+/// record's header bytes and must read the rest of the record, returning the
+/// number of bytes that it read. Returns the group's label.
+fn read_group<R, F>(
+    reader: &mut R,
+    game_id: GameId,
+    header_buffer: &mut [u8],
+    read_record: &mut F,
+) -> Result<[u8; 4], Error>
+where
+    R: BufRead + Seek,
+    F: FnMut(&mut R, &mut [u8]) -> Result<u32, Error>,
+{
+    let (label, size_of_records) = read_group_header(reader, game_id, header_buffer)?;
+
+    read_records(reader, game_id, header_buffer, size_of_records, read_record)?;
+
+    Ok(label)
+}
+
+/// Reads a group's header, returning the group's label and the size of its
+/// contents, which must fit in the rest of the reader's data.
+fn read_group_header<R: BufRead + Seek>(
+    reader: &mut R,
+    game_id: GameId,
+    header_buffer: &mut [u8],
+) -> Result<([u8; 4], u32), Error> {
+    let group_header_length = group_or_record_header_length(game_id);
+    let skip_length = get_header_length_to_skip(game_id);
+
+    // Get a slice of the right size from the header buffer.
+    let Some(header_bytes) = header_buffer.get_mut(..usize::from(group_header_length)) else {
+        return Err(Error::ParsingError(
+            header_buffer.to_vec().into_boxed_slice(),
+            ParsingErrorKind::GenericParserError("read_group".into()),
+        ));
+    };
+    read_exact(reader, header_bytes, None)?;
+
+    let (_, group_size) = all_consuming(parse_header(skip_length)).parse(header_bytes)?;
+
+    let position = reader.stream_position()?;
+    let end = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(position))?;
+
+    let size_of_records = group_contents_size(
+        header_bytes,
+        group_size,
+        group_header_length,
+        end.saturating_sub(position),
+    )?;
+
+    Ok((group_label(header_bytes), size_of_records))
+}
+
+/// Gets the size of a group's contents from its declared size, checking that
+/// the group is at least as large as its header and that its contents are no
+/// larger than `max_size`.
+fn group_contents_size(
+    header_bytes: &[u8],
+    group_size: u32,
+    group_header_length: u8,
+    max_size: u64,
+) -> Result<u32, Error> {
+    group_size
+        .checked_sub(u32::from(group_header_length))
+        .filter(|size| u64::from(*size) <= max_size)
+        .ok_or_else(|| {
+            Error::ParsingError(
+                header_bytes.into(),
+                ParsingErrorKind::InvalidGroupSize(group_size),
+            )
+        })
+}
+
+/// Reads a record's ID, adding it to `form_ids` if it's a FormID, and returns
+/// the number of bytes read. If `strict` is true, the record's data is also
+/// read to check that its subrecords exactly fill it.
+fn read_form_id<R: BufRead + Seek>(
+    reader: &mut R,
+    game_id: GameId,
+    header_bytes: &mut [u8],
+    form_ids: &mut Vec<TypedFormId>,
+    max_alloc: usize,
+    strict: bool,
+) -> Result<u32, Error> {
+    let (bytes_read, record_id) = if strict {
+        let (bytes_read, _, record_id) =
+            Record::read_with_header(reader, game_id, header_bytes, max_alloc)?;
+        (bytes_read, record_id)
+    } else {
+        Record::read_record_id(reader, game_id, header_bytes, true, max_alloc)?
+    };
+
+    if let Some(RecordId::FormId(form_id, record_type)) = record_id {
+        form_ids.push(TypedFormId {
+            form_id: form_id.get(),
+            record_type,
+        });
+    }
+
+    Ok(bytes_read)
+}
+
+// The label comes straight after the group type and size.
+fn group_label(header_bytes: &[u8]) -> [u8; 4] {
+    let mut label = [0; 4];
+    if let Some(bytes) = header_bytes.get(8..12) {
+        label.copy_from_slice(bytes);
+    }
+    label
+}
+
 // Groups and records have the same header length in any game that has both.
 fn group_or_record_header_length(game_id: GameId) -> u8 {
     match game_id {
@@ -76,26 +305,24 @@ fn get_header_length_to_skip(game_id: GameId) -> u8 {
     }
 }
 
-fn parse_header(group_header_length: u8, skip_length: u8) -> impl Fn(&[u8]) -> IResult<&[u8], u32> {
-    move |input| {
-        map(
-            delimited(tag(GROUP_TYPE), le_u32, take(skip_length)),
-            move |group_size| group_size - u32::from(group_header_length),
-        )
-        .parse(input)
-    }
+fn parse_header(skip_length: u8) -> impl Fn(&[u8]) -> IResult<&[u8], u32> {
+    move |input| delimited(tag(GROUP_TYPE), le_u32, take(skip_length)).parse(input)
 }
 
-fn read_records<R: BufRead + Seek>(
+fn read_records<R, F>(
     reader: &mut R,
     game_id: GameId,
-    form_ids: &mut Vec<u32>,
     header_buffer: &mut [u8],
     size_of_records: u32,
-) -> Result<(), Error> {
+    read_record: &mut F,
+) -> Result<(), Error>
+where
+    R: BufRead + Seek,
+    F: FnMut(&mut R, &mut [u8]) -> Result<u32, Error>,
+{
     let header_length = group_or_record_header_length(game_id);
     let skip_length = get_header_length_to_skip(game_id);
-    let parse_header = parse_header(header_length, skip_length);
+    let parse_header = parse_header(skip_length);
 
     let mut bytes_read = 0;
 
@@ -110,22 +337,22 @@ fn read_records<R: BufRead + Seek>(
             ));
         };
 
-        reader.read_exact(header_bytes)?;
+        read_exact(reader, header_bytes, None)?;
         bytes_read += u32::from(header_length);
 
         if header_bytes.starts_with(GROUP_TYPE) {
-            let (_, size_of_records) = all_consuming(&parse_header).parse(header_bytes)?;
+            let (_, group_size) = all_consuming(&parse_header).parse(header_bytes)?;
+            let size_of_records = group_contents_size(
+                header_bytes,
+                group_size,
+                header_length,
+                u64::from(size_of_records.saturating_sub(bytes_read)),
+            )?;
 
-            read_records(reader, game_id, form_ids, header_buffer, size_of_records)?;
+            read_records(reader, game_id, header_buffer, size_of_records, read_record)?;
             bytes_read += size_of_records;
         } else {
-            let (record_bytes_read, record_id) =
-                Record::read_record_id(reader, game_id, header_bytes, true)?;
-            bytes_read += record_bytes_read;
-
-            if let Some(RecordId::FormId(form_id)) = record_id {
-                form_ids.push(form_id.get());
-            }
+            bytes_read += read_record(reader, header_bytes)?;
         }
     }
 
@@ -145,38 +372,110 @@ mod tests {
         let data =
             &include_bytes!("../testing-plugins/Skyrim/Data/Blank - Master Dependent.esm")[0x56..];
 
-        let mut form_ids: Vec<u32> = Vec::new();
+        let mut form_ids: Vec<TypedFormId> = Vec::new();
         let mut header_buf = [0; MAX_RECORD_HEADER_LENGTH];
         Group::read_form_ids(
             &mut Cursor::new(&data),
             GameId::Skyrim,
             &mut form_ids,
             &mut header_buf,
+            usize::MAX,
+            false,
+            &mut RecordCounter::new(None, false),
         )
         .unwrap();
 
         assert_eq!(8, form_ids.len());
         // Also check three FormIDs from near the beginning, middle and end of the group.
-        assert!(form_ids.contains(&0xCF0));
-        assert!(form_ids.contains(&0x0100_0CEB));
-        assert!(form_ids.contains(&0x0100_0CED));
+        assert!(form_ids.iter().any(|f| f.form_id == 0xCF0));
+        assert!(form_ids.iter().any(|f| f.form_id == 0x0100_0CEB));
+        assert!(form_ids.iter().any(|f| f.form_id == 0x0100_0CED));
     }
 
     #[test]
     fn new_should_store_formids_for_all_records_in_subgroups() {
         let data = &include_bytes!("../testing-plugins/Skyrim/Data/Blank.esm")[0x1004C..0x10114];
 
-        let mut form_ids: Vec<u32> = Vec::new();
+        let mut form_ids: Vec<TypedFormId> = Vec::new();
         let mut header_buf = [0; MAX_RECORD_HEADER_LENGTH];
         Group::read_form_ids(
             &mut Cursor::new(&data),
             GameId::Skyrim,
             &mut form_ids,
             &mut header_buf,
+            usize::MAX,
+            false,
+            &mut RecordCounter::new(None, false),
         )
         .unwrap();
 
         assert_eq!(1, form_ids.len());
-        assert!(form_ids.contains(&0xCF9));
+        assert!(form_ids.iter().any(|f| f.form_id == 0xCF9));
+    }
+
+    fn group_header_bytes(group_size: u32) -> Vec<u8> {
+        let mut bytes = GROUP_TYPE.to_vec();
+        bytes.extend(group_size.to_le_bytes());
+        bytes.extend(b"GLOB");
+        bytes.extend([0; 12]);
+        bytes
+    }
+
+    fn read_form_ids(data: &[u8]) -> Result<(), Error> {
+        let mut header_buf = [0; MAX_RECORD_HEADER_LENGTH];
+        Group::read_form_ids(
+            &mut Cursor::new(data),
+            GameId::SkyrimSE,
+            &mut Vec::new(),
+            &mut header_buf,
+            usize::MAX,
+            false,
+            &mut RecordCounter::new(None, false),
+        )
+    }
+
+    #[test]
+    fn read_form_ids_should_error_if_a_group_is_smaller_than_its_header() {
+        assert!(matches!(
+            read_form_ids(&group_header_bytes(10)),
+            Err(Error::ParsingError(
+                _,
+                ParsingErrorKind::InvalidGroupSize(10)
+            ))
+        ));
+    }
+
+    #[test]
+    fn read_form_ids_should_error_if_a_group_is_larger_than_the_remaining_data() {
+        let mut data = group_header_bytes(100);
+        data.extend([0; 24]);
+
+        assert!(matches!(
+            read_form_ids(&data),
+            Err(Error::ParsingError(
+                _,
+                ParsingErrorKind::InvalidGroupSize(100)
+            ))
+        ));
+    }
+
+    #[test]
+    fn read_form_ids_should_error_if_a_subgroup_is_larger_than_its_parent_group() {
+        let mut data = group_header_bytes(48);
+        data.extend(group_header_bytes(48));
+        data.extend([0; 24]);
+
+        assert!(matches!(
+            read_form_ids(&data),
+            Err(Error::ParsingError(
+                _,
+                ParsingErrorKind::InvalidGroupSize(48)
+            ))
+        ));
+
+        let mut data = group_header_bytes(48);
+        data.extend(group_header_bytes(24));
+
+        assert!(read_form_ids(&data).is_ok());
     }
 }
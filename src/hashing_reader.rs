@@ -0,0 +1,221 @@
+/*
+ * This file is part of esplugin
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * esplugin is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * esplugin is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with esplugin. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+
+use xxhash_rust::xxh3::Xxh3;
+
+/// Wraps a reader to hash the bytes that are read from it as they are read,
+/// so that a plugin's content can be hashed while it is parsed. Bytes are
+/// hashed in order and only once, so bytes that are skipped over by seeking
+/// forwards are read and hashed, while bytes that are read again after seeking
+/// backwards aren't hashed again. Seeking relative to the end of the reader
+/// doesn't hash anything, so that the reader's length can be checked cheaply.
+pub(crate) struct HashingReader<R> {
+    reader: R,
+    hasher: Xxh3,
+    position: u64,
+    hashed_to: u64,
+}
+
+impl<R: BufRead + Seek> HashingReader<R> {
+    pub(crate) fn new(mut reader: R) -> io::Result<Self> {
+        let position = reader.stream_position()?;
+
+        Ok(Self {
+            reader,
+            hasher: Xxh3::new(),
+            position,
+            hashed_to: position,
+        })
+    }
+
+    /// Hash any bytes that haven't been read yet and return the hash of all
+    /// the bytes from where the reader started.
+    pub(crate) fn finish(mut self) -> io::Result<u64> {
+        self.hash_to(u64::MAX)?;
+
+        Ok(self.hasher.digest())
+    }
+
+    /// Read and hash bytes from the end of the hashed bytes up to the given
+    /// position, or until the end of the reader if that comes first.
+    fn hash_to(&mut self, target: u64) -> io::Result<()> {
+        if self.position != self.hashed_to {
+            self.position = self.reader.seek(SeekFrom::Start(self.hashed_to))?;
+        }
+
+        while self.position < target {
+            let buffer = self.reader.fill_buf()?;
+            if buffer.is_empty() {
+                break;
+            }
+
+            let remaining = usize::try_from(target - self.position).unwrap_or(usize::MAX);
+            let length = buffer.len().min(remaining);
+            if let Some(bytes) = buffer.get(..length) {
+                self.hasher.update(bytes);
+            }
+            self.reader.consume(length);
+
+            self.position += u64::try_from(length).unwrap_or(u64::MAX);
+            self.hashed_to = self.position;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: BufRead + Seek> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let length = {
+            let available = self.fill_buf()?;
+            let length = available.len().min(buf.len());
+            if let (Some(destination), Some(source)) =
+                (buf.get_mut(..length), available.get(..length))
+            {
+                destination.copy_from_slice(source);
+            }
+            length
+        };
+
+        self.consume(length);
+
+        Ok(length)
+    }
+}
+
+impl<R: BufRead + Seek> BufRead for HashingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        // The reader may have been moved past the hashed bytes by seeking
+        // relative to its end.
+        if self.position > self.hashed_to {
+            let position = self.position;
+            self.hash_to(position)?;
+        }
+
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        let end = self
+            .position
+            .saturating_add(u64::try_from(amount).unwrap_or(u64::MAX));
+
+        if end > self.hashed_to {
+            // fill_buf() has just been called, so this doesn't read any more
+            // data and can't fail.
+            if let Ok(buffer) = self.reader.fill_buf() {
+                let start = usize::try_from(self.hashed_to - self.position).unwrap_or(usize::MAX);
+                if let Some(bytes) = buffer.get(start..amount) {
+                    self.hasher.update(bytes);
+                }
+            }
+            self.hashed_to = end;
+        }
+
+        self.reader.consume(amount);
+        self.position = end;
+    }
+}
+
+impl<R: BufRead + Seek> Seek for HashingReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(_) => None,
+        };
+
+        match target {
+            Some(target) if target > self.hashed_to => {
+                self.hash_to(target)?;
+                if self.position < target {
+                    // The target is past the end of the reader.
+                    self.position = self.reader.seek(SeekFrom::Start(target))?;
+                }
+            }
+            _ => self.position = self.reader.seek(pos)?,
+        }
+
+        Ok(self.position)
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufReader, Cursor};
+
+    use xxhash_rust::xxh3::xxh3_64;
+
+    use super::*;
+
+    const DATA: &[u8] = b"The quick brown fox jumps over the lazy dog";
+
+    #[test]
+    fn finish_should_hash_all_data_that_was_not_read() {
+        let mut reader =
+            HashingReader::new(BufReader::with_capacity(4, Cursor::new(DATA))).unwrap();
+
+        let mut buffer = [0; 10];
+        reader.read_exact(&mut buffer).unwrap();
+
+        assert_eq!(b"The quick ", &buffer);
+        assert_eq!(xxh3_64(DATA), reader.finish().unwrap());
+    }
+
+    #[test]
+    fn seeking_should_hash_each_byte_once_and_in_order() {
+        let mut reader =
+            HashingReader::new(BufReader::with_capacity(4, Cursor::new(DATA))).unwrap();
+
+        let mut buffer = [0; 5];
+        reader.seek(SeekFrom::Current(4)).unwrap();
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(b"quick", &buffer);
+
+        assert_eq!(43, reader.seek(SeekFrom::End(0)).unwrap());
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(b"The q", &buffer);
+
+        reader.seek(SeekFrom::Start(20)).unwrap();
+        assert_eq!(20, reader.stream_position().unwrap());
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(b"jumps", &buffer);
+
+        assert_eq!(xxh3_64(DATA), reader.finish().unwrap());
+    }
+
+    #[test]
+    fn reading_after_seeking_from_the_end_should_hash_the_skipped_bytes() {
+        let mut reader =
+            HashingReader::new(BufReader::with_capacity(4, Cursor::new(DATA))).unwrap();
+
+        reader.seek(SeekFrom::End(-3)).unwrap();
+        let mut buffer = [0; 3];
+        reader.read_exact(&mut buffer).unwrap();
+        assert_eq!(b"dog", &buffer);
+
+        assert_eq!(xxh3_64(DATA), reader.finish().unwrap());
+    }
+}
@@ -124,17 +124,41 @@
     )
 )]
 
+pub use crate::conflict_index::ConflictIndex;
 pub use crate::error::{Error, MoreDataNeeded, ParsingErrorKind};
-pub use crate::game_id::GameId;
-pub use crate::plugin::{plugins_metadata, ParseOptions, Plugin, PluginMetadata};
+pub use crate::game_id::{GameId, PluginLimits};
+pub use crate::plugin::{
+    can_merge, header_layout, is_valid_plugin_file, master_diff, merged_record_ids,
+    orphaned_masters_if_removed, plugins_metadata, read_all_records, record_set_diff, resolve_all,
+    winning_records, BlueprintInfo, ContentKey, DisplayName, EslBlocker, ExtensionRules,
+    GameVersionRequirement, HeaderCounts, HeaderLayout, HeaderWarning, MasterDiff, MergeReport,
+    ParseOptions, ParseOptionsBuilder, Plugin, PluginMetadata, PluginParser, PluginScale,
+    RecordDiff, RecordStats, ResolutionStatus, SizeReport, StarfieldRole, StringEncoding,
+};
+pub use crate::record::Record;
+pub use crate::record_id::ResolvedRecordId;
+pub use crate::scan::{scan_directory, ScanResult};
+pub use crate::subrecord::Subrecord;
+#[cfg(feature = "testing")]
+pub use crate::writer::PluginWriter;
+#[cfg(feature = "fixedbitset")]
+pub use fixedbitset::FixedBitSet;
 
+mod conflict_index;
 mod error;
 mod game_id;
 mod group;
+#[cfg(feature = "xxhash-rust")]
+mod hashing_reader;
 mod plugin;
 mod record;
 mod record_id;
+mod scan;
 mod subrecord;
+#[cfg(test)]
+mod test_utils;
+#[cfg(feature = "testing")]
+mod writer;
 
 // No point recording any details of the error because it's not reported.
 struct SliceTooSmallError;
@@ -145,6 +169,12 @@ fn le_slice_to_u32(input: &[u8]) -> Result<u32, SliceTooSmallError> {
     subarray::<ARRAY_SIZE>(input, 0).map(u32::from_le_bytes)
 }
 
+fn le_slice_to_i32(input: &[u8]) -> Result<i32, SliceTooSmallError> {
+    const ARRAY_SIZE: usize = std::mem::size_of::<i32>();
+
+    subarray::<ARRAY_SIZE>(input, 0).map(i32::from_le_bytes)
+}
+
 fn le_slice_to_f32(input: &[u8]) -> Result<f32, SliceTooSmallError> {
     const ARRAY_SIZE: usize = std::mem::size_of::<f32>();
 
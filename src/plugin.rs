@@ -17,23 +17,50 @@
  * along with esplugin. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use std::collections::HashSet;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
 use std::ffi::OsStr;
 use std::fs::File;
+use std::hash::{Hash, Hasher};
+#[cfg(feature = "testing")]
+use std::io::Cursor;
 use std::io::{BufRead, BufReader, Seek};
+use std::num::NonZeroU32;
 use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
 
-use encoding_rs::WINDOWS_1252;
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+#[cfg(feature = "fixedbitset")]
+use fixedbitset::FixedBitSet;
+#[cfg(feature = "xxhash-rust")]
+use xxhash_rust::xxh3::xxh3_64;
 
 use crate::error::{Error, ParsingErrorKind};
 use crate::game_id::GameId;
 use crate::group::Group;
-use crate::record::{Record, MAX_RECORD_HEADER_LENGTH};
-use crate::record_id::{NamespacedId, ObjectIndexMask, RecordId, ResolvedRecordId, SourcePlugin};
+#[cfg(feature = "xxhash-rust")]
+use crate::hashing_reader::HashingReader;
+use crate::record::{header_length, read_exact, Record, RecordCounter, MAX_RECORD_HEADER_LENGTH};
+use crate::record_id::{
+    calculate_filename_hash, NamespacedId, ObjectIndexMask, RecordId, ResolvedRecordId,
+    SourcePlugin, TypedFormId,
+};
+use crate::subrecord::Subrecord;
+
+const MAX_HEADER_SCAN_LENGTH: usize = 1024;
+
+/// Mod indices are one byte, and a plugin's own records use the index after
+/// its masters' indices.
+const MAX_MASTERS: usize = 255;
+/// Starfield full plugins can't use the 0xFD and 0xFE mod indices, which are
+/// used by medium and small plugins.
+const MAX_STARFIELD_FULL_MASTERS: usize = 0xFD;
+const MAX_STARFIELD_MEDIUM_MASTERS: usize = 0x100;
+const MAX_STARFIELD_SMALL_MASTERS: usize = 0x1000;
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 enum FileExtension {
+    Esp,
     Esm,
     Esl,
     Ghost,
@@ -42,7 +69,9 @@ enum FileExtension {
 
 impl From<&OsStr> for FileExtension {
     fn from(value: &OsStr) -> Self {
-        if value.eq_ignore_ascii_case("esm") {
+        if value.eq_ignore_ascii_case("esp") {
+            FileExtension::Esp
+        } else if value.eq_ignore_ascii_case("esm") {
             FileExtension::Esm
         } else if value.eq_ignore_ascii_case("esl") {
             FileExtension::Esl
@@ -54,11 +83,51 @@ impl From<&OsStr> for FileExtension {
     }
 }
 
+/// Split a filename into its stem and extension in the same way as
+/// `Path::extension()`, so that a leading dot doesn't start an extension.
+fn split_extension(filename: &str) -> Option<(&str, &str)> {
+    filename
+        .rsplit_once('.')
+        .filter(|(stem, _)| !stem.is_empty())
+}
+
+/// Get the file extension that the game would see for the plugin at the given
+/// path, looking through a `.ghost` extension. Both forward and backward
+/// slashes are treated as separators and trailing separators are ignored, as
+/// paths inside archives or virtual filesystems may use either, whatever the
+/// current platform. A doubly-ghosted filename has no plugin file extension.
+fn plugin_file_extension(path: &Path) -> FileExtension {
+    let path = path.to_string_lossy();
+    let Some(filename) = path.split(['/', '\\']).rfind(|c| !c.is_empty()) else {
+        return FileExtension::Unrecognised;
+    };
+
+    match split_extension(filename) {
+        Some((stem, extension)) => match FileExtension::from(OsStr::new(extension)) {
+            FileExtension::Ghost => split_extension(stem)
+                .map(|(_, e)| FileExtension::from(OsStr::new(e)))
+                .filter(|e| *e != FileExtension::Ghost)
+                .unwrap_or(FileExtension::Unrecognised),
+            e => e,
+        },
+        None => FileExtension::Unrecognised,
+    }
+}
+
+/// Check if the path has a plugin file extension, optionally followed by a
+/// `.ghost` extension.
+pub(crate) fn has_plugin_file_extension(path: &Path) -> bool {
+    matches!(
+        plugin_file_extension(path),
+        FileExtension::Esp | FileExtension::Esm | FileExtension::Esl
+    )
+}
+
 #[derive(Clone, Default, PartialEq, Eq, Debug, Hash)]
 enum RecordIds {
     #[default]
     None,
-    FormIds(Vec<u32>),
+    FormIds(Vec<TypedFormId>),
     NamespacedIds(Vec<NamespacedId>),
     Resolved(Vec<ResolvedRecordId>),
 }
@@ -69,8 +138,8 @@ impl From<Vec<NamespacedId>> for RecordIds {
     }
 }
 
-impl From<Vec<u32>> for RecordIds {
-    fn from(form_ids: Vec<u32>) -> RecordIds {
+impl From<Vec<TypedFormId>> for RecordIds {
+    fn from(form_ids: Vec<TypedFormId>) -> RecordIds {
         RecordIds::FormIds(form_ids)
     }
 }
@@ -79,91 +148,891 @@ impl From<Vec<u32>> for RecordIds {
 struct PluginData {
     header_record: Record,
     record_ids: RecordIds,
+    records: Option<Vec<Record>>,
+    form_id_resolver: Option<FormIdResolver>,
+    /// The type and human-readable ID of each Morrowind record, in the order
+    /// they appear in the plugin, if they were collected.
+    morrowind_ids: Option<Vec<([u8; 4], String)>>,
+    /// The type of each record, in the order they appear in the plugin, if
+    /// they were collected.
+    record_types: Option<Vec<[u8; 4]>>,
+    /// The plugin's raw FormIDs in the order they appear in the plugin, if
+    /// they were preserved.
+    file_order_form_ids: Option<Vec<u32>>,
+    #[cfg(feature = "xxhash-rust")]
+    content_hash: Option<u64>,
+    encoding: StringEncoding,
+}
+
+/// Holds what's needed to resolve a plugin's raw FormIDs, so that FormIDs can
+/// still be resolved after the plugin's own records have been.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+struct FormIdResolver {
+    parent: SourcePlugin,
+    masters: Vec<SourcePlugin>,
+}
+
+impl FormIdResolver {
+    fn new(
+        game_id: GameId,
+        plugin_metadata: &PluginMetadata,
+        masters: &[String],
+        other_plugins_metadata: &[PluginMetadata],
+    ) -> Result<Self, Error> {
+        let parent = hashed_parent(game_id, plugin_metadata);
+        let masters = match game_id {
+            GameId::Starfield => hashed_masters_for_starfield(masters, other_plugins_metadata)?,
+            _ => hashed_masters(masters),
+        };
+
+        Ok(Self { parent, masters })
+    }
+
+    fn resolve(&self, raw_form_id: u32) -> ResolvedRecordId {
+        ResolvedRecordId::from_form_id(self.parent, &self.masters, raw_form_id)
+    }
+
+    /// Get the mod index bits that the parent plugin's new records should
+    /// have, given its scale and the number of masters with the same scale.
+    fn parent_mod_index(&self) -> u32 {
+        let object_index_mask = self.parent.object_index_mask;
+        let base = if object_index_mask == u32::from(ObjectIndexMask::Medium) {
+            0xFD00_0000
+        } else if object_index_mask == u32::from(ObjectIndexMask::Small) {
+            0xFE00_0000
+        } else {
+            0
+        };
+
+        let same_scale_masters = self
+            .masters
+            .iter()
+            .filter(|m| m.object_index_mask == object_index_mask)
+            .count();
+
+        u32::try_from(same_scale_masters)
+            .unwrap_or(u32::MAX)
+            .saturating_mul(object_index_mask + 1)
+            .saturating_add(base)
+    }
+
+    /// Check if there are more masters of any scale than there are mod indices
+    /// for masters of that scale.
+    fn has_too_many_masters(&self) -> bool {
+        let count = |mask: ObjectIndexMask| {
+            self.masters
+                .iter()
+                .filter(|m| m.object_index_mask == u32::from(mask))
+                .count()
+        };
+
+        count(ObjectIndexMask::Full) > MAX_STARFIELD_FULL_MASTERS
+            || count(ObjectIndexMask::Medium) > MAX_STARFIELD_MEDIUM_MASTERS
+            || count(ObjectIndexMask::Small) > MAX_STARFIELD_SMALL_MASTERS
+    }
+
+    fn resolve_all(&self, form_ids: &[TypedFormId]) -> Vec<ResolvedRecordId> {
+        let mut form_ids: Vec<_> = form_ids
+            .iter()
+            .map(|f| ResolvedRecordId::from_typed_form_id(self.parent, &self.masters, *f))
+            .collect();
+
+        form_ids.sort();
+
+        form_ids
+    }
 }
 
+/// The scale of a plugin, which determines how many new records it can have
+/// and how its FormIDs are mapped to mod indexes at runtime.
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
-enum PluginScale {
+pub enum PluginScale {
     Full,
     Medium,
     Small,
 }
 
+/// Counts of a plugin's records.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default)]
+pub struct RecordStats {
+    /// The total number of records.
+    pub total: usize,
+    /// The number of records that the plugin adds.
+    pub new: usize,
+    /// The number of records that the plugin overrides from its masters.
+    pub overrides: usize,
+}
+
+/// A summary of how a plugin's file size breaks down.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default)]
+pub struct SizeReport {
+    /// The size of the plugin file in bytes.
+    pub file_size: u64,
+    /// The size of the plugin's header record in bytes.
+    pub header_size: u64,
+    /// The total size in bytes of compressed record data as stored in the file.
+    pub compressed_data_size: u64,
+    /// The total size in bytes that compressed record data has once inflated.
+    pub decompressed_data_size: u64,
+}
+
+/// A version of a game that a plugin needs, e.g. because its header version
+/// changes which FormIDs light plugins can use.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct GameVersionRequirement {
+    pub game_id: GameId,
+    /// The major, minor and patch components of the game's version.
+    pub game_version: (u32, u32, u32),
+}
+
+/// The outcome of trying to resolve a plugin's record IDs.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub enum ResolutionStatus {
+    /// The plugin's record IDs are resolved.
+    Complete,
+    /// The plugin's record IDs are still unresolved because metadata for
+    /// these masters wasn't given.
+    MissingMasters(Vec<String>),
+}
+
+/// A reason why a plugin can't be converted to a light plugin.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum EslBlocker {
+    /// The game doesn't support light plugins.
+    UnsupportedGame,
+    /// The plugin's records have namespaced IDs instead of FormIDs.
+    NamespacedRecordIds,
+    /// The plugin has a new record with this FormID, which has an object index
+    /// outside the range that light plugins can use.
+    OutOfRangeFormId(u32),
+}
+
+/// An inconsistency in a plugin's header record that doesn't stop the plugin
+/// from being parsed.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub enum HeaderWarning {
+    /// The header has no HEDR subrecord.
+    MissingHeaderData,
+    /// The header's HEDR subrecord only has this many bytes of data, which is
+    /// fewer than the game expects.
+    HeaderDataTooShort(usize),
+    /// The header has no SNAM subrecord. Morrowind plugins store their
+    /// description in their HEDR subrecord, so never have this warning.
+    MissingDescription,
+    /// The header has more than one subrecord of this type, but only one is
+    /// used.
+    DuplicateSubrecord([u8; 4]),
+    /// The MAST subrecord for this master isn't immediately followed by a DATA
+    /// subrecord.
+    MasterWithoutData(String),
+    /// The header has a DATA subrecord that doesn't immediately follow a MAST
+    /// subrecord.
+    DataWithoutMaster,
+}
+
+/// Where a game's plugins store data in their header record, as used by this
+/// library to read that data.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct HeaderLayout {
+    /// The type of the header record.
+    pub record_type: [u8; 4],
+    /// The type of the subrecord that holds the plugin's description.
+    pub description_subrecord_type: [u8; 4],
+    /// The offset of the description in its subrecord's data.
+    pub description_offset: usize,
+    /// The offset of the record and group count in the HEDR subrecord's data.
+    pub record_count_offset: usize,
+    /// The offset of the plugin's flags in the HEDR subrecord's data, or
+    /// `None` if the flags are stored in the header record's header.
+    pub flags_offset: Option<usize>,
+    /// The length of the HEDR subrecord's data.
+    pub hedr_length: usize,
+}
+
+/// Get the layout of the header record of plugins for the given game.
+pub fn header_layout(game_id: GameId) -> HeaderLayout {
+    match game_id {
+        GameId::Morrowind => HeaderLayout {
+            record_type: *b"TES3",
+            description_subrecord_type: *b"HEDR",
+            description_offset: 40,
+            record_count_offset: 296,
+            flags_offset: Some(4),
+            hedr_length: 300,
+        },
+        _ => HeaderLayout {
+            record_type: *b"TES4",
+            description_subrecord_type: *b"SNAM",
+            description_offset: 0,
+            record_count_offset: 4,
+            flags_offset: None,
+            hedr_length: 12,
+        },
+    }
+}
+
+/// Counts that are stored in a plugin's header record. Counts that the
+/// plugin's header doesn't have are `None`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default)]
+pub struct HeaderCounts {
+    /// The number of strings that can be tagified, from the INTV subrecord.
+    pub tagifiable_string_count: Option<u32>,
+    /// The number of interior cells, from the INCC subrecord.
+    pub interior_cell_count: Option<u32>,
+}
+
+/// The role that a Starfield plugin plays once the game has applied its rules
+/// for how the light, medium, update and blueprint flags interact.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum StarfieldRole {
+    Full { blueprint: bool },
+    Medium { blueprint: bool },
+    Small { blueprint: bool },
+    Update { blueprint: bool },
+}
+
+/// How a Starfield blueprint plugin's other flags affect how it's loaded.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct BlueprintInfo {
+    /// The effective scale of the plugin once its light and medium flags
+    /// have been applied. Update plugins are full plugins.
+    pub scale: PluginScale,
+    /// Whether the plugin's update flag is in effect.
+    pub is_update: bool,
+    /// Whether the plugin is a master file. The game loads blueprint master
+    /// files after all other plugins, regardless of their scale.
+    pub loads_after_other_plugins: bool,
+}
+
+/// The display name of a record, from its FULL subrecord.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub enum DisplayName {
+    /// The name is stored in the plugin.
+    Inline(String),
+    /// The name is stored in the plugin's string tables, at the given ID.
+    Localized(u32),
+}
+
+/// Rules for how a plugin's file extension affects how it's treated, for
+/// setups that repurpose extensions. The rules can only turn off behaviour
+/// that the plugin's game has: e.g. allowing the .esl extension to make a
+/// plugin light has no effect for games that don't support light plugins.
+/// The default rules match the games' behaviour.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct ExtensionRules {
+    /// Whether the .esm extension makes a plugin a master file.
+    pub esm_implies_master: bool,
+    /// Whether the .esl extension makes a plugin a light master file.
+    pub esl_implies_light: bool,
+}
+
+impl Default for ExtensionRules {
+    fn default() -> Self {
+        Self {
+            esm_implies_master: true,
+            esl_implies_light: true,
+        }
+    }
+}
+
+/// A plugin, along with the game it's for, the path it was given, and the data
+/// that has been parsed from it. Plugins are compared and hashed using their
+/// paths as well as their games, extension rules and parsed data: use
+/// `content_key()` to compare plugins while ignoring their paths.
 #[derive(Clone, PartialEq, Eq, Debug, Hash)]
 pub struct Plugin {
     game_id: GameId,
     path: PathBuf,
+    rules: ExtensionRules,
     data: PluginData,
 }
 
+/// A reference to a plugin that is compared and hashed using only the plugin's
+/// game and parsed data, ignoring its path. Resolved record IDs depend on the
+/// filenames of the plugins that records come from, so two plugins with
+/// identical content but different filenames may still be unequal once their
+/// record IDs are resolved.
+#[derive(Clone, Copy, Debug)]
+pub struct ContentKey<'a>(&'a Plugin);
+
+impl ContentKey<'_> {
+    pub fn plugin(&self) -> &Plugin {
+        self.0
+    }
+}
+
+impl PartialEq for ContentKey<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.game_id == other.0.game_id && self.0.data == other.0.data
+    }
+}
+
+impl Eq for ContentKey<'_> {}
+
+impl Hash for ContentKey<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.game_id.hash(state);
+        self.0.data.hash(state);
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "The options are independent of each other"
+)]
 pub struct ParseOptions {
     header_only: bool,
+    retain_records: bool,
+    scan_for_header: bool,
+    buffer_capacity: Option<usize>,
+    max_alloc: Option<usize>,
+    #[cfg(feature = "xxhash-rust")]
+    compute_hash: bool,
+    encoding: StringEncoding,
+    strict: bool,
+    masters_only: bool,
+    max_records_scanned: Option<usize>,
+    collect_record_types: bool,
+    preserve_form_id_order: bool,
+    collect_morrowind_record_ids: bool,
 }
 
 impl ParseOptions {
     pub fn header_only() -> Self {
-        Self { header_only: true }
+        Self {
+            header_only: true,
+            ..Self::whole_plugin()
+        }
     }
 
     pub fn whole_plugin() -> Self {
-        Self { header_only: false }
+        Self {
+            header_only: false,
+            retain_records: false,
+            scan_for_header: false,
+            buffer_capacity: None,
+            max_alloc: None,
+            #[cfg(feature = "xxhash-rust")]
+            compute_hash: false,
+            encoding: StringEncoding::Windows1252,
+            strict: false,
+            masters_only: false,
+            max_records_scanned: None,
+            collect_record_types: false,
+            preserve_form_id_order: false,
+            collect_morrowind_record_ids: false,
+        }
+    }
+
+    /// Parse the whole plugin and keep its records in memory, which is needed
+    /// by functions that read record content.
+    pub fn whole_plugin_with_records() -> Self {
+        Self {
+            retain_records: true,
+            ..Self::whole_plugin()
+        }
+    }
+
+    /// Parse the whole plugin, checking that each record's subrecords exactly
+    /// fill the record's declared data size. This is slower than
+    /// `whole_plugin()` because all record data must be read.
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            ..Self::whole_plugin()
+        }
+    }
+
+    /// Parse only as much of the plugin's header as is needed to read its
+    /// masters. Only `masters()` can be relied upon after parsing with this
+    /// option: other header data may not have been read.
+    pub fn masters_only() -> Self {
+        Self {
+            masters_only: true,
+            ..Self::header_only()
+        }
+    }
+
+    pub fn builder() -> ParseOptionsBuilder {
+        ParseOptionsBuilder::default()
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self::whole_plugin()
+    }
+}
+
+/// Builds a `ParseOptions` value, starting from the defaults (parsing the whole
+/// plugin without retaining its records).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default)]
+pub struct ParseOptionsBuilder {
+    options: ParseOptions,
+}
+
+impl ParseOptionsBuilder {
+    #[must_use]
+    pub fn header_only(mut self, header_only: bool) -> Self {
+        self.options.header_only = header_only;
+        self
+    }
+
+    #[must_use]
+    pub fn retain_records(mut self, retain_records: bool) -> Self {
+        self.options.retain_records = retain_records;
+        self
+    }
+
+    /// If enabled, skip up to 1 KiB of junk data at the start of the plugin
+    /// while looking for the header record.
+    #[must_use]
+    pub fn scan_for_header(mut self, scan_for_header: bool) -> Self {
+        self.options.scan_for_header = scan_for_header;
+        self
+    }
+
+    /// Set the capacity in bytes of the buffer used when reading the plugin.
+    /// By default, the standard library's default capacity is used. Scanning
+    /// for the header only searches as much data as fits in the buffer.
+    #[must_use]
+    pub fn buffer_capacity(mut self, capacity: usize) -> Self {
+        self.options.buffer_capacity = Some(capacity);
+        self
+    }
+
+    /// Limit the size of the buffers that are allocated to hold record data,
+    /// so that parsing errors if a record declares or decompresses to a larger
+    /// size. By default, there's no limit. This can be used to guard against
+    /// maliciously crafted plugins.
+    #[must_use]
+    pub fn max_alloc(mut self, max_alloc: usize) -> Self {
+        self.options.max_alloc = Some(max_alloc);
+        self
+    }
+
+    /// If enabled, calculate a hash of the plugin's content while parsing it,
+    /// which can then be retrieved using `Plugin::content_hash()`. The whole
+    /// plugin is read into memory to do so, even if only its header is parsed.
+    /// This needs the `xxhash-rust` feature to be enabled.
+    #[cfg(feature = "xxhash-rust")]
+    #[must_use]
+    pub fn compute_hash(mut self, compute_hash: bool) -> Self {
+        self.options.compute_hash = compute_hash;
+        self
+    }
+
+    /// Set the encoding that is used to decode the plugin's strings, e.g. its
+    /// masters and description. By default, strings are decoded as
+    /// Windows-1252, which is what the games use.
+    #[must_use]
+    pub fn encoding(mut self, encoding: StringEncoding) -> Self {
+        self.options.encoding = encoding;
+        self
+    }
+
+    /// If enabled, check that each record's subrecords exactly fill the
+    /// record's declared data size, erroring if they don't. Records are always
+    /// checked when they are retained. The subrecords of compressed records
+    /// are only checked if the `compressed-fields` feature is enabled.
+    #[must_use]
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.options.strict = strict;
+        self
+    }
+
+    /// If enabled, stop reading the plugin once its masters have been read, so
+    /// that the rest of the header (e.g. a large ONAM subrecord) is skipped.
+    /// This implies `header_only`. A plugin's masters are assumed to be listed
+    /// together, as they are in plugins written by the games' tools, so any
+    /// masters that are listed after other subrecords are not read.
+    #[must_use]
+    pub fn masters_only(mut self, masters_only: bool) -> Self {
+        self.options.masters_only = masters_only;
+        self
+    }
+
+    /// Limit the number of records that are read while parsing, so that
+    /// parsing errors if the plugin has more records than that. All records
+    /// that are read count towards the limit, whether or not they are kept.
+    /// By default, there's no limit. Like `max_alloc`, this can be used to
+    /// guard against maliciously crafted plugins.
+    #[must_use]
+    pub fn max_records_scanned(mut self, max_records_scanned: usize) -> Self {
+        self.options.max_records_scanned = Some(max_records_scanned);
+        self
+    }
+
+    /// If enabled, record the type of each record while parsing, so that
+    /// `Plugin::record_type_sequence()` can be used without retaining the
+    /// plugin's records.
+    #[must_use]
+    pub fn collect_record_types(mut self, collect_record_types: bool) -> Self {
+        self.options.collect_record_types = collect_record_types;
+        self
+    }
+
+    /// If enabled, keep a copy of the plugin's FormIDs in the order they
+    /// appear in the plugin, so that `Plugin::form_ids_in_file_order()` can
+    /// be used after the plugin's record IDs have been resolved without
+    /// retaining the plugin's records.
+    #[must_use]
+    pub fn preserve_form_id_order(mut self, preserve_form_id_order: bool) -> Self {
+        self.options.preserve_form_id_order = preserve_form_id_order;
+        self
+    }
+
+    /// If enabled, record the human-readable ID of each Morrowind record while
+    /// parsing, so that `Plugin::morrowind_record_ids()` can be used. This has
+    /// no effect for other games.
+    #[must_use]
+    pub fn collect_morrowind_record_ids(mut self, collect_morrowind_record_ids: bool) -> Self {
+        self.options.collect_morrowind_record_ids = collect_morrowind_record_ids;
+        self
+    }
+
+    pub fn build(self) -> ParseOptions {
+        self.options
+    }
+}
+
+/// The encoding of the strings in a plugin.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Default)]
+pub enum StringEncoding {
+    /// The encoding that the games use.
+    #[default]
+    Windows1252,
+    /// Used by some third-party tools when writing plugins.
+    Utf8,
+}
+
+impl StringEncoding {
+    fn encoding(self) -> &'static Encoding {
+        match self {
+            StringEncoding::Windows1252 => WINDOWS_1252,
+            StringEncoding::Utf8 => UTF_8,
+        }
+    }
+}
+
+/// Scratch buffers that are used while reading a plugin's records.
+#[derive(Clone, Debug, Default)]
+struct ParseBuffers {
+    form_ids: Vec<TypedFormId>,
+    header: [u8; MAX_RECORD_HEADER_LENGTH],
+}
+
+/// Parses plugins, reusing scratch buffers between calls to avoid repeatedly
+/// allocating them when parsing many plugins.
+#[derive(Clone, Debug, Default)]
+pub struct PluginParser {
+    buffers: ParseBuffers,
+}
+
+impl PluginParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse the plugin data read from `reader` into `plugin`, as
+    /// `Plugin::parse_reader` does.
+    pub fn parse_into<R: std::io::Read + std::io::Seek>(
+        &mut self,
+        plugin: &mut Plugin,
+        reader: R,
+        options: ParseOptions,
+    ) -> Result<(), Error> {
+        plugin.data = read_plugin_data(
+            buffered_reader(reader, options),
+            plugin.game_id,
+            options,
+            plugin.header_type(),
+            &mut self.buffers,
+            &mut |_| {},
+        )?;
+
+        if let Some(mut form_ids) = plugin.resolve_parsed_record_ids()? {
+            form_ids.clear();
+            self.buffers.form_ids = form_ids;
+        }
+
+        Ok(())
     }
 }
 
 impl Plugin {
     pub fn new(game_id: GameId, filepath: &Path) -> Plugin {
+        Plugin::new_with_rules(game_id, filepath, ExtensionRules::default())
+    }
+
+    /// Create a plugin that uses the given rules for how its file extension
+    /// affects how it's treated, instead of the game's own rules.
+    pub fn new_with_rules(game_id: GameId, filepath: &Path, rules: ExtensionRules) -> Plugin {
         Plugin {
             game_id,
             path: filepath.to_path_buf(),
+            rules,
             data: PluginData::default(),
         }
     }
 
+    /// Parse the header of the plugin at the given path, detecting which game
+    /// the plugin is for from the header's type, length and version. Some
+    /// header versions are shared by more than one game, so this is a best
+    /// guess: plugins for Fallout 3 and Skyrim that have a 0.94 header version
+    /// are taken to be for Fallout 3, and Skyrim plugins are taken to be for
+    /// Skyrim Special Edition.
+    pub fn open(path: &Path) -> Result<Plugin, Error> {
+        let mut first_error = None;
+
+        for game_id in [GameId::Morrowind, GameId::Oblivion, GameId::SkyrimSE] {
+            let mut plugin = Plugin::new(game_id, path);
+            match plugin.parse_file(ParseOptions::header_only()) {
+                Ok(()) => {
+                    if game_id == GameId::SkyrimSE {
+                        plugin.game_id = game_id_from_header_version(plugin.header_version());
+                    }
+                    return Ok(plugin);
+                }
+                Err(Error::IoError(e)) => return Err(Error::IoError(e)),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        Err(first_error.unwrap_or_else(|| Error::NoFilename(path.to_path_buf())))
+    }
+
+    /// Create a plugin that has the given header record, as if it had been
+    /// parsed from a plugin file that contains only that record. This is only
+    /// available with the `testing` feature, to help test code that uses
+    /// plugins against synthetic headers.
+    #[cfg(feature = "testing")]
+    pub fn with_header_record(
+        game_id: GameId,
+        path: &Path,
+        header_record: &[u8],
+    ) -> Result<Plugin, Error> {
+        let mut plugin = Plugin::new(game_id, path);
+        plugin.parse_reader(Cursor::new(header_record), ParseOptions::header_only())?;
+        Ok(plugin)
+    }
+
     pub fn parse_reader<R: std::io::Read + std::io::Seek>(
         &mut self,
         reader: R,
         options: ParseOptions,
     ) -> Result<(), Error> {
-        let mut reader = BufReader::new(reader);
+        self.parse_buffered_reader(buffered_reader(reader, options), options)
+    }
+
+    /// Parse the plugin data read from `reader`, as `parse_reader` does, but
+    /// without wrapping the reader in another buffer. The `buffer_capacity`
+    /// option is ignored.
+    pub fn parse_buffered_reader<R: BufRead + Seek>(
+        &mut self,
+        reader: R,
+        options: ParseOptions,
+    ) -> Result<(), Error> {
+        self.parse_buffered(reader, options, &mut |_| {})
+    }
 
-        self.data = read_plugin(&mut reader, self.game_id, options, self.header_type())?;
+    /// Parse the plugin data read from `reader`, as `parse_reader` does, but
+    /// also call `progress` with the number of bytes read so far after each
+    /// top-level group is read, and once more when parsing has finished.
+    /// Morrowind plugins have no groups, so their progress is only reported
+    /// once they have been parsed.
+    pub fn parse_reader_reporting<R: std::io::Read + std::io::Seek>(
+        &mut self,
+        reader: R,
+        options: ParseOptions,
+        mut progress: impl FnMut(u64),
+    ) -> Result<(), Error> {
+        self.parse_buffered(buffered_reader(reader, options), options, &mut progress)
+    }
 
-        if self.game_id != GameId::Morrowind && self.game_id != GameId::Starfield {
-            self.resolve_record_ids(&[])?;
-        }
+    /// Read the FormIDs of the records in the plugin's top-level groups with
+    /// the given label, including those in their subgroups, without storing
+    /// anything. Other groups are skipped over without being read. Morrowind
+    /// plugins have no groups, so the result is always empty for them.
+    pub fn parse_group<R: BufRead + Seek>(
+        mut reader: R,
+        game_id: GameId,
+        group_label: [u8; 4],
+    ) -> Result<Vec<u32>, Error> {
+        Record::read(&mut reader, game_id, header_type(game_id))?;
+
+        if game_id == GameId::Morrowind {
+            return Ok(Vec::new());
+        }
+
+        let mut form_ids = Vec::new();
+        let mut header_buffer = [0; MAX_RECORD_HEADER_LENGTH];
+        while !reader.fill_buf()?.is_empty() {
+            Group::read_form_ids_if_labelled(
+                &mut reader,
+                game_id,
+                group_label,
+                &mut form_ids,
+                &mut header_buffer,
+                usize::MAX,
+            )?;
+        }
+
+        Ok(form_ids.into_iter().map(|f| f.form_id).collect())
+    }
+
+    fn parse_buffered<R: BufRead + Seek>(
+        &mut self,
+        reader: R,
+        options: ParseOptions,
+        progress: &mut dyn FnMut(u64),
+    ) -> Result<(), Error> {
+        self.data = read_plugin_data(
+            reader,
+            self.game_id,
+            options,
+            self.header_type(),
+            &mut ParseBuffers::default(),
+            progress,
+        )?;
+
+        self.resolve_parsed_record_ids()?;
 
         Ok(())
     }
 
+    /// Resolve the record IDs of a freshly-parsed plugin if its game doesn't
+    /// need other plugins' metadata to do so, returning the no-longer-needed
+    /// unresolved FormIDs.
+    fn resolve_parsed_record_ids(&mut self) -> Result<Option<Vec<TypedFormId>>, Error> {
+        if self.game_id == GameId::Morrowind || self.game_id == GameId::Starfield {
+            return Ok(None);
+        }
+
+        let RecordIds::FormIds(form_ids) = &self.data.record_ids else {
+            return Ok(None);
+        };
+
+        let (record_ids, resolver) = self.resolve_typed_form_ids(form_ids, &[])?;
+        self.data.form_id_resolver = Some(resolver);
+
+        match std::mem::replace(&mut self.data.record_ids, RecordIds::Resolved(record_ids)) {
+            RecordIds::FormIds(form_ids) => Ok(Some(form_ids)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Parse only the plugin's header record, reading no more from the reader
+    /// than is necessary so that it is left positioned just after the header.
+    /// Returns the number of bytes read.
+    pub fn parse_header_from_reader<R: std::io::Read>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<u64, Error> {
+        let header_record = Record::read(reader, self.game_id, self.header_type())?;
+        check_header_is_not_empty(&header_record, self.game_id, self.header_type())?;
+
+        let bytes_read = u64::from(header_length(self.game_id))
+            + u64::from(header_record.header().size_of_subrecords());
+
+        self.data = PluginData {
+            header_record,
+            record_ids: RecordIds::None,
+            records: None,
+            form_id_resolver: None,
+            morrowind_ids: None,
+            record_types: None,
+            file_order_form_ids: None,
+            #[cfg(feature = "xxhash-rust")]
+            content_hash: None,
+            encoding: StringEncoding::default(),
+        };
+
+        Ok(bytes_read)
+    }
+
     pub fn parse_file(&mut self, options: ParseOptions) -> Result<(), Error> {
         let file = File::open(&self.path)?;
 
         self.parse_reader(file, options)
     }
 
+    /// Parse the plugin file, reporting progress as `parse_reader_reporting`
+    /// does. This can be used to show the progress of parsing large plugins.
+    pub fn parse_file_reporting(
+        &mut self,
+        options: ParseOptions,
+        progress: impl FnMut(u64),
+    ) -> Result<(), Error> {
+        let file = File::open(&self.path)?;
+
+        self.parse_reader_reporting(file, options, progress)
+    }
+
+    /// Parse the plugin file again as a plugin for the given game, e.g. if it
+    /// was first parsed for the wrong game. If parsing fails, the plugin is
+    /// left unchanged.
+    pub fn reparse_as(&mut self, game_id: GameId, options: ParseOptions) -> Result<(), Error> {
+        let mut reparsed = Plugin {
+            game_id,
+            path: self.path.clone(),
+            rules: self.rules,
+            data: PluginData::default(),
+        };
+        reparsed.parse_file(options)?;
+
+        self.game_id = reparsed.game_id;
+        self.data = reparsed.data;
+
+        Ok(())
+    }
+
+    /// Parse the plugin file and then resolve its record IDs using the given
+    /// plugins' metadata, which must include metadata for all the plugin's
+    /// masters if it is a Starfield plugin.
+    pub fn parse_file_resolved(
+        &mut self,
+        options: ParseOptions,
+        plugins_metadata: &[PluginMetadata],
+    ) -> Result<(), Error> {
+        self.parse_file(options)?;
+
+        self.resolve_record_ids(plugins_metadata)
+    }
+
+    fn resolve_typed_form_ids(
+        &self,
+        form_ids: &[TypedFormId],
+        plugins_metadata: &[PluginMetadata],
+    ) -> Result<(Vec<ResolvedRecordId>, FormIdResolver), Error> {
+        let filename = self
+            .filename()
+            .ok_or_else(|| Error::NoFilename(self.path.clone()))?;
+        let parent_metadata = PluginMetadata {
+            filename,
+            scale: self.scale(),
+            record_ids: Box::new([]),
+        };
+        let masters = self.masters()?;
+
+        let resolver =
+            FormIdResolver::new(self.game_id, &parent_metadata, &masters, plugins_metadata)?;
+
+        Ok((resolver.resolve_all(form_ids), resolver))
+    }
+
     /// plugins_metadata can be empty for all games other than Starfield, and for Starfield plugins with no masters.
     pub fn resolve_record_ids(&mut self, plugins_metadata: &[PluginMetadata]) -> Result<(), Error> {
         match &self.data.record_ids {
             RecordIds::FormIds(form_ids) => {
-                let filename = self
-                    .filename()
-                    .ok_or_else(|| Error::NoFilename(self.path.clone()))?;
-                let parent_metadata = PluginMetadata {
-                    filename,
-                    scale: self.scale(),
-                    record_ids: Box::new([]),
-                };
-                let masters = self.masters()?;
-
-                let form_ids = resolve_form_ids(
-                    self.game_id,
-                    form_ids,
-                    &parent_metadata,
-                    &masters,
-                    plugins_metadata,
-                )?;
+                let (record_ids, resolver) =
+                    self.resolve_typed_form_ids(form_ids, plugins_metadata)?;
 
-                self.data.record_ids = RecordIds::Resolved(form_ids);
+                self.data.record_ids = RecordIds::Resolved(record_ids);
+                self.data.form_id_resolver = Some(resolver);
             }
             RecordIds::NamespacedIds(namespaced_ids) => {
                 let masters = self.masters()?;
@@ -181,12 +1050,67 @@ impl Plugin {
         Ok(())
     }
 
-    pub fn game_id(&self) -> GameId {
-        self.game_id
+    /// Like `resolve_record_ids()`, but if resolving the plugin's record IDs
+    /// needs metadata for masters that aren't in `plugins_metadata`, the
+    /// record IDs are left unresolved and the missing masters are returned
+    /// instead of an error. Record IDs can't be resolved for only some
+    /// masters, as a Starfield master's mod index depends on the scales of
+    /// the masters before it.
+    pub fn resolve_record_ids_partial(
+        &mut self,
+        plugins_metadata: &[PluginMetadata],
+    ) -> Result<ResolutionStatus, Error> {
+        let needs_masters_metadata = match &self.data.record_ids {
+            RecordIds::FormIds(_) => self.game_id == GameId::Starfield,
+            RecordIds::NamespacedIds(_) => true,
+            RecordIds::None | RecordIds::Resolved(_) => false,
+        };
+
+        if needs_masters_metadata {
+            let missing_masters: Vec<String> = self
+                .masters()?
+                .into_iter()
+                .filter(|master| {
+                    !plugins_metadata
+                        .iter()
+                        .any(|m| unicase::eq(m.filename.as_str(), master.as_str()))
+                })
+                .collect();
+
+            if !missing_masters.is_empty() {
+                return Ok(ResolutionStatus::MissingMasters(missing_masters));
+            }
+        }
+
+        self.resolve_record_ids(plugins_metadata)?;
+
+        Ok(ResolutionStatus::Complete)
     }
 
-    pub fn path(&self) -> &Path {
-        &self.path
+    /// Free the plugin's record IDs and any retained records while keeping its
+    /// header, so that the plugin behaves as if it was parsed with
+    /// `ParseOptions::header_only()`.
+    pub fn drop_record_ids(&mut self) {
+        self.data.record_ids = RecordIds::None;
+        self.data.records = None;
+        self.data.form_id_resolver = None;
+        self.data.morrowind_ids = None;
+        self.data.record_types = None;
+        self.data.file_order_form_ids = None;
+    }
+
+    /// Get a key that can be used to compare or hash this plugin by its
+    /// content, ignoring its path.
+    pub fn content_key(&self) -> ContentKey<'_> {
+        ContentKey(self)
+    }
+
+    pub fn game_id(&self) -> GameId {
+        self.game_id
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
     }
 
     pub fn filename(&self) -> Option<String> {
@@ -196,23 +1120,212 @@ impl Plugin {
             .map(std::string::ToString::to_string)
     }
 
+    /// Get a key that sorts plugins with master files before non-master
+    /// files, and then by filename. The first element is false for master
+    /// files, so that they sort first. The filename is lowercased and has any
+    /// `.ghost` extension removed, so ghosting a plugin doesn't change its
+    /// position.
+    pub fn load_order_key(&self) -> (bool, String) {
+        let filename = self
+            .filename()
+            .map(|f| normalize_filename(&f))
+            .unwrap_or_default();
+
+        (!self.is_master_file(), filename)
+    }
+
     pub fn masters(&self) -> Result<Vec<String>, Error> {
-        masters(&self.data.header_record)
+        masters(&self.data.header_record, self.data.encoding)
+    }
+
+    /// Check if the plugin lists itself as one of its masters. Filenames are
+    /// compared case-insensitively, and any `.ghost` extension on the plugin's
+    /// own filename is ignored.
+    pub fn is_self_referential(&self) -> Result<bool, Error> {
+        let unghosted_path = self.unghosted_path();
+        let Some(filename) = unghosted_path.file_name().and_then(std::ffi::OsStr::to_str) else {
+            return Ok(false);
+        };
+
+        Ok(self
+            .masters()?
+            .iter()
+            .any(|m| unicase::eq(m.as_str(), filename)))
+    }
+
+    /// Check if the plugin has more masters than can be given mod indices, in
+    /// which case some of its masters will be ignored when resolving its
+    /// record IDs. Starfield full, medium and small masters have separate
+    /// limits, so this needs records to be resolved first if run for Starfield.
+    pub fn has_too_many_masters(&self) -> Result<bool, Error> {
+        if self.game_id == GameId::Starfield {
+            self.data
+                .form_id_resolver
+                .as_ref()
+                .map(FormIdResolver::has_too_many_masters)
+                .ok_or_else(|| Error::UnresolvedRecordIds(self.path.clone()))
+        } else {
+            Ok(self.masters()?.len() > MAX_MASTERS)
+        }
+    }
+
+    /// Get the plugin's masters paired with the mod index mask that FormIDs
+    /// referencing each master have, in the order the masters are listed.
+    /// Masters that can't be given a mod index are omitted. Starfield masters
+    /// are indexed separately by scale, so this needs records to be resolved
+    /// first if run for Starfield.
+    pub fn indexed_masters(&self) -> Result<Vec<(u32, String)>, Error> {
+        let masters = self.masters()?;
+
+        let masks: Vec<u32> = if self.game_id == GameId::Starfield {
+            self.data
+                .form_id_resolver
+                .as_ref()
+                .ok_or_else(|| Error::UnresolvedRecordIds(self.path.clone()))?
+                .masters
+                .iter()
+                .map(|m| m.mod_index_mask)
+                .collect()
+        } else {
+            hashed_masters(&masters)
+                .iter()
+                .map(|m| m.mod_index_mask)
+                .collect()
+        };
+
+        Ok(masks.into_iter().zip(masters).collect())
+    }
+
+    /// Get the plugin's masters with master files moved before non-master
+    /// files, keeping the order of the masters otherwise unchanged. The
+    /// masters' headers aren't available, so whether or not a master is a
+    /// master file is guessed from its file extension: .esm files are master
+    /// files, as are .esl files for games that support light plugins, unless
+    /// this plugin's extension rules say otherwise. Master files with other
+    /// extensions will therefore be sorted as non-masters.
+    pub fn sorted_masters(&self) -> Result<Vec<String>, Error> {
+        let mut masters = self.masters()?;
+
+        masters.sort_by_key(|m| {
+            let extension = Path::new(m)
+                .extension()
+                .map_or(FileExtension::Unrecognised, FileExtension::from);
+
+            let is_master = match extension {
+                FileExtension::Esm => self.rules.esm_implies_master,
+                FileExtension::Esl => {
+                    self.game_id.supports_light_plugins() && self.rules.esl_implies_light
+                }
+                _ => false,
+            };
+
+            !is_master
+        });
+
+        Ok(masters)
+    }
+
+    /// Get the mod index bits that the game would give this plugin's new
+    /// records at runtime, given the metadata of the active plugins in load
+    /// order. Plugins of each scale are given indexes separately. Returns
+    /// `None` if this plugin isn't in the load order, or if it's past the
+    /// number of plugins of its scale that the game can load.
+    pub fn runtime_mod_index(&self, load_order: &[PluginMetadata]) -> Option<u32> {
+        let filename = self.filename()?;
+        let scale = self.scale();
+
+        let position = load_order
+            .iter()
+            .position(|m| unicase::eq(m.filename.as_str(), filename.as_str()))?;
+        let index = load_order
+            .get(..position)?
+            .iter()
+            .filter(|m| m.scale == scale)
+            .count();
+        let index = u32::try_from(index).ok()?;
+
+        let max_plugins = match scale {
+            PluginScale::Full => Some(self.game_id.max_full_plugins()),
+            PluginScale::Medium => self.game_id.max_medium_plugins(),
+            PluginScale::Small => self.game_id.max_light_plugins(),
+        };
+        if index >= max_plugins? {
+            return None;
+        }
+
+        let mod_index = match scale {
+            PluginScale::Full => index << 24u8,
+            PluginScale::Medium => 0xFD00_0000 | (index << 16u8),
+            PluginScale::Small => 0xFE00_0000 | (index << 12u8),
+        };
+
+        Some(mod_index)
+    }
+
+    /// Get the plugin's masters in the form that is used when matching them
+    /// against other plugins' filenames, i.e. lowercased and without any
+    /// `.ghost` file extension.
+    pub fn normalized_masters(&self) -> Result<Vec<String>, Error> {
+        Ok(self
+            .masters()?
+            .into_iter()
+            .map(|m| normalize_filename(&m))
+            .collect())
     }
 
     fn file_extension(&self) -> FileExtension {
-        if let Some(p) = self.path.extension() {
-            match FileExtension::from(p) {
-                FileExtension::Ghost => self
-                    .path
-                    .file_stem()
-                    .map(Path::new)
-                    .and_then(Path::extension)
-                    .map_or(FileExtension::Unrecognised, FileExtension::from),
-                e => e,
-            }
+        plugin_file_extension(&self.path)
+    }
+
+    fn has_light_extension(&self) -> bool {
+        self.rules.esl_implies_light && self.file_extension() == FileExtension::Esl
+    }
+
+    /// Check if the plugin's filename ends with a `.ghost` extension, which is
+    /// matched case-insensitively.
+    pub fn is_ghosted(&self) -> bool {
+        self.path
+            .extension()
+            .is_some_and(|e| FileExtension::from(e) == FileExtension::Ghost)
+    }
+
+    /// Get the plugin's path with any `.ghost` extension removed, i.e. the
+    /// path that the plugin would have if it was unghosted. If the plugin
+    /// isn't ghosted, its path is returned unchanged.
+    pub fn unghosted_path(&self) -> PathBuf {
+        if self.is_ghosted() {
+            self.path.with_extension("")
         } else {
-            FileExtension::Unrecognised
+            self.path.clone()
+        }
+    }
+
+    /// A plugin can be ghosted if it isn't already ghosted and it has a file
+    /// extension that the game would load it with. Whether or not other plugins
+    /// depend on it makes no difference, as games ignore ghosted plugins.
+    pub fn is_ghostable(&self) -> bool {
+        if self.is_ghosted() {
+            return false;
+        }
+
+        match self.file_extension() {
+            FileExtension::Esp | FileExtension::Esm => true,
+            FileExtension::Esl => self.game_id.supports_light_plugins(),
+            FileExtension::Ghost | FileExtension::Unrecognised => false,
+        }
+    }
+
+    /// Check if the plugin's strings are stored in separate string tables
+    /// instead of in the plugin itself. Only Skyrim, Skyrim Special Edition,
+    /// Fallout 4, Fallout 76 and Starfield plugins can be localized.
+    pub fn is_localized(&self) -> bool {
+        match self.game_id {
+            GameId::Skyrim
+            | GameId::SkyrimSE
+            | GameId::Fallout4
+            | GameId::Fallout76
+            | GameId::Starfield => self.data.header_record.header().flags() & 0x80 != 0,
+            _ => false,
         }
     }
 
@@ -222,16 +1335,17 @@ impl Plugin {
                 // The .esl extension implies the master flag, but the light and
                 // medium flags do not.
                 self.is_master_flag_set()
-                    || matches!(
-                        self.file_extension(),
-                        FileExtension::Esm | FileExtension::Esl
-                    )
+                    || match self.file_extension() {
+                        FileExtension::Esm => self.rules.esm_implies_master,
+                        FileExtension::Esl => self.rules.esl_implies_light,
+                        _ => false,
+                    }
             }
             _ => self.is_master_flag_set(),
         }
     }
 
-    fn scale(&self) -> PluginScale {
+    pub(crate) fn scale(&self) -> PluginScale {
         if self.is_light_plugin() {
             PluginScale::Small
         } else if self.is_medium_flag_set() {
@@ -247,9 +1361,9 @@ impl Plugin {
                 // If the inject flag is set, it prevents the .esl extension from
                 // causing the light flag to be forcibly set on load.
                 self.is_light_flag_set()
-                    || (!self.is_update_flag_set() && self.file_extension() == FileExtension::Esl)
+                    || (!self.is_update_flag_set() && self.has_light_extension())
             } else {
-                self.is_light_flag_set() || self.file_extension() == FileExtension::Esl
+                self.is_light_flag_set() || self.has_light_extension()
             }
         } else {
             false
@@ -277,6 +1391,44 @@ impl Plugin {
         }
     }
 
+    /// Returns `None` if the plugin is not a Starfield plugin.
+    pub fn starfield_role(&self) -> Option<StarfieldRole> {
+        if self.game_id != GameId::Starfield {
+            return None;
+        }
+
+        let blueprint = self.is_blueprint_plugin();
+
+        let role = if self.is_light_plugin() {
+            StarfieldRole::Small { blueprint }
+        } else if self.is_medium_plugin() {
+            StarfieldRole::Medium { blueprint }
+        } else if self.is_update_plugin() {
+            StarfieldRole::Update { blueprint }
+        } else {
+            StarfieldRole::Full { blueprint }
+        };
+
+        Some(role)
+    }
+
+    /// Returns `None` if the plugin is not a Starfield blueprint plugin.
+    pub fn blueprint_role(&self) -> Option<BlueprintInfo> {
+        let (scale, is_update) = match self.starfield_role()? {
+            StarfieldRole::Full { blueprint: true } => (PluginScale::Full, false),
+            StarfieldRole::Medium { blueprint: true } => (PluginScale::Medium, false),
+            StarfieldRole::Small { blueprint: true } => (PluginScale::Small, false),
+            StarfieldRole::Update { blueprint: true } => (PluginScale::Full, true),
+            _ => return None,
+        };
+
+        Some(BlueprintInfo {
+            scale,
+            is_update,
+            loads_after_other_plugins: self.is_master_file(),
+        })
+    }
+
     pub fn is_valid(game_id: GameId, filepath: &Path, options: ParseOptions) -> bool {
         let mut plugin = Plugin::new(game_id, filepath);
 
@@ -284,34 +1436,149 @@ impl Plugin {
     }
 
     pub fn description(&self) -> Result<Option<String>, Error> {
-        let (target_subrecord_type, description_offset) = match self.game_id {
-            GameId::Morrowind => (b"HEDR", 40),
-            _ => (b"SNAM", 0),
-        };
+        self.description_data()?
+            .map(|data| {
+                self.data
+                    .encoding
+                    .encoding()
+                    .decode_without_bom_handling_and_without_replacement(data)
+                    .map(|s| s.to_string())
+                    .ok_or(Error::DecodeError(data.into()))
+            })
+            .transpose()
+    }
+
+    /// Check if the plugin's description is longer than the game will display,
+    /// so that it will be cut off. This is false if the plugin has no
+    /// description.
+    pub fn description_exceeds_limit(&self) -> Result<bool, Error> {
+        Ok(self
+            .description_data()?
+            .is_some_and(|d| d.len() > self.game_id.max_description_length()))
+    }
+
+    /// Like `description()`, but any bytes that can't be decoded are replaced
+    /// instead of causing an error. Returns `None` if the plugin has no
+    /// description or its description can't be read.
+    pub fn description_lossy(&self) -> Option<String> {
+        self.description_data().ok().flatten().map(|data| {
+            self.data
+                .encoding
+                .encoding()
+                .decode_without_bom_handling(data)
+                .0
+                .into_owned()
+        })
+    }
+
+    fn description_data(&self) -> Result<Option<&[u8]>, Error> {
+        let layout = header_layout(self.game_id);
+        let description_offset = layout.description_offset;
 
         for subrecord in self.data.header_record.subrecords() {
-            if subrecord.subrecord_type() == target_subrecord_type {
-                let data = subrecord
+            if *subrecord.subrecord_type() == layout.description_subrecord_type {
+                return subrecord
                     .data()
                     .get(description_offset..)
-                    .map(until_first_null)
+                    .map(|data| Some(until_first_null(data)))
                     .ok_or_else(|| {
                         Error::ParsingError(
                             subrecord.data().into(),
                             ParsingErrorKind::SubrecordDataTooShort(description_offset),
                         )
-                    })?;
-
-                return WINDOWS_1252
-                    .decode_without_bom_handling_and_without_replacement(data)
-                    .map(|s| Some(s.to_string()))
-                    .ok_or(Error::DecodeError(data.into()));
+                    });
             }
         }
 
         Ok(None)
     }
 
+    pub fn header_counts(&self) -> HeaderCounts {
+        let count = |subrecord_type: &[u8; 4]| {
+            self.data
+                .header_record
+                .subrecords()
+                .iter()
+                .find(|s| s.subrecord_type() == subrecord_type)
+                .and_then(|s| crate::le_slice_to_u32(s.data()).ok())
+        };
+
+        HeaderCounts {
+            tagifiable_string_count: count(b"INTV"),
+            interior_cell_count: count(b"INCC"),
+        }
+    }
+
+    /// Get the data of the first subrecord of the given type in the plugin's
+    /// header record, or `None` if there is no such subrecord.
+    pub fn header_subrecord(&self, subrecord_type: &[u8; 4]) -> Option<&[u8]> {
+        self.data
+            .header_record
+            .subrecords()
+            .iter()
+            .find(|s| s.subrecord_type() == subrecord_type)
+            .map(Subrecord::data)
+    }
+
+    pub fn header_subrecord_count(&self) -> usize {
+        self.data.header_record.subrecords().len()
+    }
+
+    /// Check the plugin's header record for inconsistencies that games and
+    /// this library tolerate, such as missing or duplicated subrecords and
+    /// masters without their DATA subrecords. An empty list means that no
+    /// problems were found.
+    pub fn validate_header(&self) -> Result<Vec<HeaderWarning>, Error> {
+        let min_hedr_length = header_layout(self.game_id).hedr_length;
+        let unique_types: &[&[u8; 4]] = match self.game_id {
+            GameId::Morrowind => &[b"HEDR"],
+            _ => &[b"HEDR", b"CNAM", b"SNAM"],
+        };
+
+        let subrecords = self.data.header_record.subrecords();
+        let mut warnings = Vec::new();
+
+        match self.header_subrecord(b"HEDR") {
+            None => warnings.push(HeaderWarning::MissingHeaderData),
+            Some(data) if data.len() < min_hedr_length => {
+                warnings.push(HeaderWarning::HeaderDataTooShort(data.len()));
+            }
+            Some(_) => {}
+        }
+
+        if self.game_id != GameId::Morrowind && self.header_subrecord(b"SNAM").is_none() {
+            warnings.push(HeaderWarning::MissingDescription);
+        }
+
+        for subrecord_type in unique_types {
+            let count = subrecords
+                .iter()
+                .filter(|s| s.subrecord_type() == *subrecord_type)
+                .count();
+            if count > 1 {
+                warnings.push(HeaderWarning::DuplicateSubrecord(**subrecord_type));
+            }
+        }
+
+        let mut masters = self.masters()?.into_iter();
+        let mut previous_type = None;
+        for (index, subrecord) in subrecords.iter().enumerate() {
+            let subrecord_type = subrecord.subrecord_type();
+            if subrecord_type == b"MAST" {
+                let master = masters.next();
+                let next_type = subrecords.get(index + 1).map(Subrecord::subrecord_type);
+                if next_type != Some(b"DATA") {
+                    warnings.extend(master.map(HeaderWarning::MasterWithoutData));
+                }
+            } else if subrecord_type == b"DATA" && previous_type != Some(b"MAST") {
+                warnings.push(HeaderWarning::DataWithoutMaster);
+            }
+            previous_type = Some(subrecord_type);
+        }
+
+        Ok(warnings)
+    }
+
     pub fn header_version(&self) -> Option<f32> {
         self.data
             .header_record
@@ -321,11 +1588,25 @@ impl Plugin {
             .and_then(|s| crate::le_slice_to_f32(s.data()).ok())
     }
 
+    /// Get the FormID of the plugin's header record, which is usually zero.
+    /// Morrowind records don't have FormIDs, so this is `None` for Morrowind
+    /// plugins.
+    pub fn header_form_id(&self) -> Option<u32> {
+        if self.game_id == GameId::Morrowind {
+            None
+        } else {
+            Some(
+                self.data
+                    .header_record
+                    .header()
+                    .form_id()
+                    .map_or(0, NonZeroU32::get),
+            )
+        }
+    }
+
     pub fn record_and_group_count(&self) -> Option<u32> {
-        let count_offset = match self.game_id {
-            GameId::Morrowind => 296,
-            _ => 4,
-        };
+        let count_offset = header_layout(self.game_id).record_count_offset;
 
         self.data
             .header_record
@@ -336,6 +1617,257 @@ impl Plugin {
             .and_then(|d| crate::le_slice_to_u32(d).ok())
     }
 
+    /// Get the type and human-readable ID of each of a Morrowind plugin's
+    /// records that has an ID, in the order they appear in the plugin. Most
+    /// IDs are strings, but some records are identified by an index or by grid
+    /// coordinates, which are given as e.g. `"-2, 5"`. Plugins for other games
+    /// have no such IDs, so this is always empty for them. This needs Morrowind
+    /// plugins to have been parsed with Morrowind record IDs collected.
+    pub fn morrowind_record_ids(&self) -> Result<Vec<(&[u8; 4], String)>, Error> {
+        if self.game_id != GameId::Morrowind {
+            return Ok(Vec::new());
+        }
+
+        self.data
+            .morrowind_ids
+            .as_ref()
+            .map(|ids| {
+                ids.iter()
+                    .map(|(record_type, id)| (record_type, id.clone()))
+                    .collect()
+            })
+            .ok_or_else(|| Error::RecordsNotRetained(self.path.clone()))
+    }
+
+    /// Get the type of each of the plugin's records, in the order they appear
+    /// in the plugin, including records in subgroups. This needs the plugin to
+    /// have been parsed with record types collected or with its records
+    /// retained.
+    pub fn record_type_sequence(&self) -> Result<Vec<[u8; 4]>, Error> {
+        match (&self.data.record_types, &self.data.records) {
+            (Some(record_types), _) => Ok(record_types.clone()),
+            (None, Some(records)) => {
+                Ok(records.iter().map(|r| *r.header().record_type()).collect())
+            }
+            (None, None) => Err(Error::RecordsNotRetained(self.path.clone())),
+        }
+    }
+
+    /// Get the plugin's raw FormIDs in the order that their records appear in
+    /// the plugin, including any duplicates. Morrowind records have no
+    /// FormIDs, so the result is always empty for Morrowind plugins. Once the
+    /// plugin's record IDs have been resolved, this needs the plugin to have
+    /// been parsed with FormID order preserved or with its records retained.
+    pub fn form_ids_in_file_order(&self) -> Result<Vec<u32>, Error> {
+        if self.game_id == GameId::Morrowind {
+            return Ok(Vec::new());
+        }
+
+        match (
+            &self.data.file_order_form_ids,
+            &self.data.record_ids,
+            &self.data.records,
+        ) {
+            (Some(form_ids), _, _) => Ok(form_ids.clone()),
+            (None, RecordIds::FormIds(_), _) => Ok(raw_form_ids(&self.data.record_ids)),
+            (None, RecordIds::Resolved(_), Some(records)) => Ok(records
+                .iter()
+                .filter_map(|r| r.header().form_id().map(NonZeroU32::get))
+                .collect()),
+            (None, RecordIds::Resolved(_), None) => {
+                Err(Error::RecordsNotRetained(self.path.clone()))
+            }
+            (None, RecordIds::None | RecordIds::NamespacedIds(_), _) => Ok(Vec::new()),
+        }
+    }
+
+    /// Get the plugin's resolved record IDs in sorted order. This needs records
+    /// to be resolved first if run for Morrowind or Starfield, and is empty if
+    /// the plugin's records haven't been parsed.
+    pub fn resolved_record_ids(&self) -> Result<&[ResolvedRecordId], Error> {
+        match &self.data.record_ids {
+            RecordIds::None => Ok(&[]),
+            RecordIds::FormIds(_) | RecordIds::NamespacedIds(_) => {
+                Err(Error::UnresolvedRecordIds(self.path.clone()))
+            }
+            RecordIds::Resolved(record_ids) => Ok(record_ids),
+        }
+    }
+
+    /// Get the plugin's resolved record IDs that match the given predicate, in
+    /// sorted order. This has the same requirements as `resolved_record_ids()`.
+    pub fn resolved_ids_filtered(
+        &self,
+        f: impl Fn(&ResolvedRecordId) -> bool,
+    ) -> Result<Vec<ResolvedRecordId>, Error> {
+        Ok(self
+            .resolved_record_ids()?
+            .iter()
+            .filter(|id| f(id))
+            .copied()
+            .collect())
+    }
+
+    /// Get the hash of the plugin's content that was calculated while parsing
+    /// it, or `None` if the plugin was parsed without computing a hash. The
+    /// hash is stable, so it can be persisted to detect when a plugin changes.
+    /// This needs the `xxhash-rust` feature to be enabled.
+    #[cfg(feature = "xxhash-rust")]
+    pub fn content_hash(&self) -> Option<u64> {
+        self.data.content_hash
+    }
+
+    /// Calculate a hash of the plugin's masters and resolved record IDs that
+    /// ignores the order of its masters, the case of their filenames and the
+    /// plugin's own filename, so that plugins which touch the same records get
+    /// the same fingerprint. Fingerprints are stable, so they can be
+    /// persisted, except for Morrowind plugins, whose record IDs are hashed
+    /// in a way that is only stable for a given build of this library. This
+    /// needs records to be resolved first if run for Morrowind or Starfield,
+    /// and the `xxhash-rust` feature to be enabled.
+    #[cfg(feature = "xxhash-rust")]
+    pub fn content_fingerprint(&self) -> Result<u64, Error> {
+        let master_names: HashMap<u64, String> = self
+            .masters()?
+            .iter()
+            .map(|m| (calculate_filename_hash(m), normalize_filename(m)))
+            .collect();
+
+        let mut masters: Vec<&str> = master_names.values().map(String::as_str).collect();
+        masters.sort_unstable();
+
+        let mut form_ids = Vec::new();
+        let mut namespaced_ids = Vec::new();
+        for record_id in self.resolved_record_ids()? {
+            if let Some(object_index) = record_id.object_index() {
+                let source = record_id
+                    .hashed_source_plugin_name()
+                    .and_then(|h| master_names.get(&h))
+                    .map(String::as_str);
+                form_ids.push((source, object_index));
+            } else {
+                namespaced_ids.extend(record_id.hashed_namespaced_id());
+            }
+        }
+        form_ids.sort_unstable();
+        namespaced_ids.sort_unstable();
+
+        let mut bytes = Vec::new();
+        for master in masters {
+            bytes.extend(master.as_bytes());
+            bytes.push(0);
+        }
+        bytes.push(0);
+
+        for (source, object_index) in form_ids {
+            // Records from the plugin itself are marked with a zero byte
+            // instead of a source name, so that renaming the plugin doesn't
+            // change its fingerprint.
+            match source {
+                Some(name) => {
+                    bytes.push(1);
+                    bytes.extend(name.as_bytes());
+                    bytes.push(0);
+                }
+                None => bytes.push(0),
+            }
+            bytes.extend(object_index.to_le_bytes());
+        }
+
+        for (namespace, hashed_id) in namespaced_ids {
+            bytes.extend(namespace.to_le_bytes());
+            bytes.extend(hashed_id.to_le_bytes());
+        }
+
+        Ok(xxh3_64(&bytes))
+    }
+
+    /// Get the types of the records that this plugin overrides from its
+    /// masters. This needs records to be resolved first if run for Morrowind
+    /// or Starfield.
+    pub fn overridden_record_types(&self) -> Result<HashSet<[u8; 4]>, Error> {
+        Ok(self
+            .resolved_record_ids()?
+            .iter()
+            .filter(|r| r.is_overridden_record())
+            .map(ResolvedRecordId::record_type)
+            .collect())
+    }
+
+    /// Count the plugin's new records in buckets of object indexes, giving a
+    /// map of the first object index in each bucket to the number of new
+    /// records in that bucket. A bucket size of zero is treated as one. This
+    /// needs records to be resolved first if run for Starfield, and Morrowind
+    /// records have no object indexes.
+    pub fn object_index_histogram(&self, bucket_size: u32) -> Result<BTreeMap<u32, usize>, Error> {
+        let bucket_size = bucket_size.max(1);
+        let mut histogram = BTreeMap::new();
+
+        for record_id in self.resolved_record_ids()? {
+            if record_id.is_overridden_record() {
+                continue;
+            }
+
+            if let Some(object_index) = record_id.object_index() {
+                let bucket = object_index
+                    .checked_div(bucket_size)
+                    .unwrap_or(object_index)
+                    .saturating_mul(bucket_size);
+                *histogram.entry(bucket).or_insert(0) += 1;
+            }
+        }
+
+        Ok(histogram)
+    }
+
+    /// Count the plugin's new and override records by record type, giving a
+    /// map of record types to `(new_count, override_count)`. This needs records
+    /// to be resolved first if run for Morrowind or Starfield.
+    pub fn record_type_roles(&self) -> Result<BTreeMap<[u8; 4], (usize, usize)>, Error> {
+        let mut roles = BTreeMap::new();
+
+        for record_id in self.resolved_record_ids()? {
+            let (new_count, override_count) =
+                roles.entry(record_id.record_type()).or_insert((0, 0));
+            if record_id.is_overridden_record() {
+                *override_count += 1;
+            } else {
+                *new_count += 1;
+            }
+        }
+
+        Ok(roles)
+    }
+
+    /// Get the number of record IDs that were read from the plugin, whether or
+    /// not they have been resolved. This is zero if the plugin's records
+    /// haven't been parsed.
+    pub fn raw_record_count(&self) -> usize {
+        match &self.data.record_ids {
+            RecordIds::None => 0,
+            RecordIds::FormIds(ids) => ids.len(),
+            RecordIds::NamespacedIds(ids) => ids.len(),
+            RecordIds::Resolved(ids) => ids.len(),
+        }
+    }
+
+    /// Count the plugin's records, split into new and override records. The
+    /// counts are zero if the plugin's records haven't been parsed. This needs
+    /// records to be resolved first if run for Morrowind or Starfield.
+    pub fn record_stats(&self) -> Result<RecordStats, Error> {
+        let mut stats = RecordStats::default();
+        for record_id in self.resolved_record_ids()? {
+            stats.total += 1;
+            if record_id.is_overridden_record() {
+                stats.overrides += 1;
+            } else {
+                stats.new += 1;
+            }
+        }
+
+        Ok(stats)
+    }
+
     /// This needs records to be resolved first if run for Morrowind or Starfield.
     pub fn count_override_records(&self) -> Result<usize, Error> {
         match &self.data.record_ids {
@@ -361,70 +1893,356 @@ impl Plugin {
         }
     }
 
-    /// Count the number of records that appear in this plugin and one or more
-    /// the others passed. If more than one other contains the same record, it
-    /// is only counted once.
-    pub fn overlap_size(&self, others: &[&Self]) -> Result<usize, Error> {
-        use RecordIds::{FormIds, NamespacedIds, None, Resolved};
+    /// Get the first of the given plugins that overlaps with this plugin, as
+    /// checked by `overlaps_with()`. Plugins after the first overlapping
+    /// plugin are not checked.
+    pub fn first_conflict<'a>(&self, others: &[&'a Self]) -> Result<Option<&'a Self>, Error> {
+        for other in others {
+            if self.overlaps_with(other)? {
+                return Ok(Some(*other));
+            }
+        }
 
-        match &self.data.record_ids {
-            FormIds(_) => Err(Error::UnresolvedRecordIds(self.path.clone())),
-            Resolved(ids) => {
-                let mut count = 0;
-                for id in ids {
-                    for other in others {
-                        match &other.data.record_ids {
-                            FormIds(_) => {
-                                return Err(Error::UnresolvedRecordIds(other.path.clone()))
-                            }
-                            Resolved(master_ids) if master_ids.binary_search(id).is_ok() => {
-                                count += 1;
-                                break;
-                            }
-                            _ => {
-                                // Do nothing.
-                            }
-                        }
-                    }
-                }
+        Ok(None)
+    }
 
-                Ok(count)
+    /// Get the records that both this plugin and the other plugin override,
+    /// i.e. the records that they conflict on. Unlike with `overlaps_with()`,
+    /// a record that one plugin adds and the other overrides is not a
+    /// conflict.
+    pub fn conflicts_with(&self, other: &Self) -> Result<Vec<ResolvedRecordId>, Error> {
+        use RecordIds::{FormIds, NamespacedIds, Resolved};
+        match (&self.data.record_ids, &other.data.record_ids) {
+            (FormIds(_) | NamespacedIds(_), _) => {
+                Err(Error::UnresolvedRecordIds(self.path.clone()))
             }
-            NamespacedIds(ids) => {
-                let count = ids
-                    .iter()
-                    .filter(|id| {
-                        others.iter().any(|other| match &other.data.record_ids {
-                            NamespacedIds(master_ids) => master_ids.binary_search(id).is_ok(),
-                            _ => false,
-                        })
-                    })
-                    .count();
-                Ok(count)
+            (_, FormIds(_) | NamespacedIds(_)) => {
+                Err(Error::UnresolvedRecordIds(other.path.clone()))
             }
-            None => Ok(0),
+            (Resolved(left), Resolved(right)) => Ok(sorted_slices_intersection(left, right)
+                .filter(|(l, r)| l.is_overridden_record() && r.is_overridden_record())
+                .map(|(l, _)| *l)
+                .collect()),
+            _ => Ok(Vec::new()),
         }
     }
 
-    pub fn is_valid_as_light_plugin(&self) -> Result<bool, Error> {
-        if self.game_id.supports_light_plugins() {
-            match &self.data.record_ids {
-                RecordIds::None => Ok(true),
-                RecordIds::FormIds(_) => Err(Error::UnresolvedRecordIds(self.path.clone())),
-                RecordIds::Resolved(form_ids) => {
-                    let valid_range = self.valid_light_form_id_range();
-
-                    let is_valid = form_ids
-                        .iter()
-                        .filter(|f| !f.is_overridden_record())
-                        .all(|f| f.is_object_index_in(&valid_range));
+    /// Check if this plugin contains a record with the given FormID, where the
+    /// FormID's mod index is relative to this plugin's masters. Morrowind
+    /// plugins have no FormIDs, so never contain any. This needs records to be
+    /// resolved first if run for Starfield.
+    pub fn contains_form_id(&self, form_id: u32) -> Result<bool, Error> {
+        match (&self.data.record_ids, &self.data.form_id_resolver) {
+            (RecordIds::FormIds(_) | RecordIds::NamespacedIds(_), _) => {
+                Err(Error::UnresolvedRecordIds(self.path.clone()))
+            }
+            (RecordIds::Resolved(record_ids), Some(resolver)) => {
+                Ok(record_ids.binary_search(&resolver.resolve(form_id)).is_ok())
+            }
+            (RecordIds::None, _) | (RecordIds::Resolved(_), None) => Ok(false),
+        }
+    }
 
-                    Ok(is_valid)
+    /// Check if any of this plugin's FormIDs has a mod index that is greater
+    /// than the number of masters the plugin has, i.e. it references a master
+    /// that the plugin doesn't declare. Morrowind plugins have no FormIDs, so
+    /// never have any invalid references. This needs records to be resolved
+    /// first if run for Starfield.
+    pub fn has_invalid_master_references(&self) -> Result<bool, Error> {
+        match &self.data.record_ids {
+            RecordIds::None => Ok(false),
+            RecordIds::FormIds(_) | RecordIds::NamespacedIds(_) => {
+                Err(Error::UnresolvedRecordIds(self.path.clone()))
+            }
+            RecordIds::Resolved(record_ids) => {
+                let masters_count = self.masters()?.len();
+
+                // Overridden records have already been matched to a master,
+                // so only the plugin's new records can have invalid indices.
+                Ok(record_ids
+                    .iter()
+                    .filter(|r| !r.is_overridden_record())
+                    .filter_map(ResolvedRecordId::raw_form_id)
+                    .any(|f| usize::try_from(f >> 24u32).is_ok_and(|i| i > masters_count)))
+            }
+        }
+    }
+
+    /// Check if any of this plugin's new records has a FormID with a mod index
+    /// that doesn't match the one expected for the plugin's scale, i.e. the
+    /// number of masters for full plugins, or the appropriate light or medium
+    /// mod index for Starfield's small and medium plugins. The game replaces
+    /// such mod indexes at runtime, but they're usually a Creation Kit
+    /// artifact. Morrowind plugins have no FormIDs, so never have any such
+    /// records. This needs records to be resolved first if run for Starfield.
+    pub fn has_out_of_range_new_records(&self) -> Result<bool, Error> {
+        match (&self.data.record_ids, &self.data.form_id_resolver) {
+            (RecordIds::FormIds(_) | RecordIds::NamespacedIds(_), _) => {
+                Err(Error::UnresolvedRecordIds(self.path.clone()))
+            }
+            (RecordIds::Resolved(record_ids), Some(resolver)) => {
+                let mod_index_mask = !resolver.parent.object_index_mask;
+                let parent_mod_index = resolver.parent_mod_index();
+
+                Ok(record_ids
+                    .iter()
+                    .filter(|r| !r.is_overridden_record())
+                    .filter_map(ResolvedRecordId::raw_form_id)
+                    .any(|f| f & mod_index_mask != parent_mod_index))
+            }
+            (RecordIds::None, _) | (RecordIds::Resolved(_), None) => Ok(false),
+        }
+    }
+
+    /// Get the FormIDs of records that have the same FormID as an earlier
+    /// record in this plugin. FormIDs are given as they appear in the plugin,
+    /// and if unresolved are compared as they appear too. Morrowind plugins
+    /// have no FormIDs, so never have any duplicates.
+    pub fn duplicate_form_ids(&self) -> Result<Vec<u32>, Error> {
+        let duplicates = match &self.data.record_ids {
+            RecordIds::None | RecordIds::NamespacedIds(_) => Vec::new(),
+            RecordIds::FormIds(form_ids) => {
+                let mut form_ids: Vec<_> = form_ids.iter().map(|f| f.form_id).collect();
+                form_ids.sort_unstable();
+
+                form_ids
+                    .windows(2)
+                    .filter_map(|w| match w {
+                        [a, b] if a == b => Some(*a),
+                        _ => None,
+                    })
+                    .collect()
+            }
+            // Resolved IDs are sorted stably, so the first record of each
+            // group of duplicates is the earliest in the plugin.
+            RecordIds::Resolved(record_ids) => record_ids
+                .windows(2)
+                .filter_map(|w| match w {
+                    [a, b] if a == b => a.raw_form_id(),
+                    _ => None,
+                })
+                .collect(),
+        };
+
+        Ok(duplicates)
+    }
+
+    /// Get the records in this plugin that override records from the given
+    /// master, which is matched case-insensitively. The result is empty if the
+    /// name isn't one of this plugin's masters, and is always empty for
+    /// Morrowind plugins because their records can't be traced back to a
+    /// specific master. This needs records to be resolved first if run for
+    /// Starfield.
+    pub fn records_from_master(&self, master_name: &str) -> Result<Vec<ResolvedRecordId>, Error> {
+        let record_ids = match &self.data.record_ids {
+            RecordIds::None => return Ok(Vec::new()),
+            RecordIds::FormIds(_) | RecordIds::NamespacedIds(_) => {
+                return Err(Error::UnresolvedRecordIds(self.path.clone()))
+            }
+            RecordIds::Resolved(record_ids) => record_ids,
+        };
+
+        if !self
+            .masters()?
+            .iter()
+            .any(|m| unicase::eq(m.as_str(), master_name))
+        {
+            return Ok(Vec::new());
+        }
+
+        let hashed_name = calculate_filename_hash(master_name);
+
+        Ok(record_ids
+            .iter()
+            .filter(|r| r.is_overridden_record() && r.is_from_plugin(hashed_name))
+            .copied()
+            .collect())
+    }
+
+    /// Get the names of the plugins that this plugin's records come from,
+    /// which are some of its masters and possibly the plugin itself. Masters
+    /// that aren't in the set aren't referenced by any of the plugin's records.
+    /// The set is always empty for Morrowind plugins because their records
+    /// can't be traced back to a specific plugin. This needs records to be
+    /// resolved first if run for Starfield.
+    pub fn referenced_source_plugins(&self) -> Result<HashSet<String>, Error> {
+        let record_ids = match &self.data.record_ids {
+            RecordIds::None => return Ok(HashSet::new()),
+            RecordIds::FormIds(_) | RecordIds::NamespacedIds(_) => {
+                return Err(Error::UnresolvedRecordIds(self.path.clone()))
+            }
+            RecordIds::Resolved(record_ids) => record_ids,
+        };
+
+        let hashed_names: HashSet<u64> = record_ids
+            .iter()
+            .filter_map(ResolvedRecordId::hashed_source_plugin_name)
+            .collect();
+
+        Ok(self
+            .masters()?
+            .into_iter()
+            .chain(self.filename())
+            .filter(|name| hashed_names.contains(&calculate_filename_hash(name)))
+            .collect())
+    }
+
+    /// Count how many of this plugin's records come from each of its masters,
+    /// i.e. how many records it overrides from each master. Every master is
+    /// included, even if none of the plugin's records come from it. Like
+    /// `unused_masters()`, the map is always empty for Morrowind plugins and
+    /// plugins that were parsed without their records. This needs records to
+    /// be resolved first if run for Starfield.
+    pub fn master_reference_counts(&self) -> Result<BTreeMap<String, usize>, Error> {
+        let record_ids = match &self.data.record_ids {
+            RecordIds::None => return Ok(BTreeMap::new()),
+            RecordIds::FormIds(_) | RecordIds::NamespacedIds(_) => {
+                return Err(Error::UnresolvedRecordIds(self.path.clone()))
+            }
+            RecordIds::Resolved(record_ids) => record_ids,
+        };
+
+        if self.game_id == GameId::Morrowind {
+            return Ok(BTreeMap::new());
+        }
+
+        let mut counts_by_hash: HashMap<u64, usize> = HashMap::new();
+        for hashed_name in record_ids
+            .iter()
+            .filter_map(ResolvedRecordId::hashed_source_plugin_name)
+        {
+            *counts_by_hash.entry(hashed_name).or_default() += 1;
+        }
+
+        Ok(self
+            .masters()?
+            .into_iter()
+            .map(|master| {
+                let count = counts_by_hash
+                    .get(&calculate_filename_hash(&master))
+                    .copied()
+                    .unwrap_or_default();
+                (master, count)
+            })
+            .collect())
+    }
+
+    /// Get the masters that none of this plugin's records come from, in the
+    /// order that they're declared. Only record headers are checked, so a
+    /// master that is only referenced from within record data will still be
+    /// listed. The result is always empty for Morrowind plugins and plugins
+    /// that were parsed without their records, as it can't be known which
+    /// masters they use. This needs records to be resolved first if run for
+    /// Starfield.
+    pub fn unused_masters(&self) -> Result<Vec<String>, Error> {
+        if self.game_id == GameId::Morrowind || matches!(self.data.record_ids, RecordIds::None) {
+            return Ok(Vec::new());
+        }
+
+        let referenced = self.referenced_source_plugins()?;
+
+        Ok(self
+            .masters()?
+            .into_iter()
+            .filter(|m| !referenced.contains(m))
+            .collect())
+    }
+
+    /// Get the masters that at least one of this plugin's records comes from,
+    /// in the order that they're declared, i.e. the masters that would be
+    /// kept by removing `unused_masters()`. Keeping the declared order keeps
+    /// the masters in a valid load order, but removing masters changes the
+    /// mod indices of the masters after them, so the plugin's FormIDs would
+    /// need to be remapped to match. As with `unused_masters()`, only record
+    /// headers are checked, and all masters are returned for Morrowind plugins
+    /// and plugins that were parsed without their records. This needs records
+    /// to be resolved first if run for Starfield.
+    pub fn minimal_masters(&self) -> Result<Vec<String>, Error> {
+        let masters = self.masters()?;
+        if self.game_id == GameId::Morrowind || matches!(self.data.record_ids, RecordIds::None) {
+            return Ok(masters);
+        }
+
+        let referenced = self.referenced_source_plugins()?;
+
+        Ok(masters
+            .into_iter()
+            .filter(|m| referenced.contains(m))
+            .collect())
+    }
+
+    /// Count the number of records that appear in this plugin and one or more
+    /// the others passed. If more than one other contains the same record, it
+    /// is only counted once.
+    pub fn overlap_size(&self, others: &[&Self]) -> Result<usize, Error> {
+        use RecordIds::{FormIds, NamespacedIds, None, Resolved};
+
+        match &self.data.record_ids {
+            FormIds(_) => Err(Error::UnresolvedRecordIds(self.path.clone())),
+            Resolved(ids) => {
+                let mut count = 0;
+                for id in ids {
+                    for other in others {
+                        match &other.data.record_ids {
+                            FormIds(_) => {
+                                return Err(Error::UnresolvedRecordIds(other.path.clone()))
+                            }
+                            Resolved(master_ids) if master_ids.binary_search(id).is_ok() => {
+                                count += 1;
+                                break;
+                            }
+                            _ => {
+                                // Do nothing.
+                            }
+                        }
+                    }
                 }
-                RecordIds::NamespacedIds(_) => Ok(false),
+
+                Ok(count)
             }
-        } else {
-            Ok(false)
+            NamespacedIds(ids) => {
+                let count = ids
+                    .iter()
+                    .filter(|id| {
+                        others.iter().any(|other| match &other.data.record_ids {
+                            NamespacedIds(master_ids) => master_ids.binary_search(id).is_ok(),
+                            _ => false,
+                        })
+                    })
+                    .count();
+                Ok(count)
+            }
+            None => Ok(0),
+        }
+    }
+
+    pub fn is_valid_as_light_plugin(&self) -> Result<bool, Error> {
+        self.esl_conversion_blockers()
+            .map(|blockers| blockers.is_empty())
+    }
+
+    /// Get the reasons why this plugin can't be converted to a light plugin,
+    /// which is empty if it can be. This needs records to be resolved first if
+    /// run for Starfield.
+    pub fn esl_conversion_blockers(&self) -> Result<Vec<EslBlocker>, Error> {
+        if !self.game_id.supports_light_plugins() {
+            return Ok(vec![EslBlocker::UnsupportedGame]);
+        }
+
+        match &self.data.record_ids {
+            RecordIds::None => Ok(Vec::new()),
+            RecordIds::FormIds(_) => Err(Error::UnresolvedRecordIds(self.path.clone())),
+            RecordIds::Resolved(form_ids) => {
+                let valid_range = self.valid_light_form_id_range();
+
+                Ok(form_ids
+                    .iter()
+                    .filter(|f| !f.is_overridden_record() && !f.is_object_index_in(&valid_range))
+                    .filter_map(ResolvedRecordId::raw_form_id)
+                    .map(EslBlocker::OutOfRangeFormId)
+                    .collect())
+            }
+            RecordIds::NamespacedIds(_) => Ok(vec![EslBlocker::NamespacedRecordIds]),
         }
     }
 
@@ -451,7 +2269,7 @@ impl Plugin {
     }
 
     pub fn is_valid_as_update_plugin(&self) -> Result<bool, Error> {
-        if self.game_id == GameId::Starfield {
+        if self.game_id.supports_update_plugins() {
             // If an update plugin has a record that does not override an existing record, that
             // record is placed into the mod index of the plugin's first master, which risks
             // overwriting an unrelated record with the same object index, so treat that case as
@@ -469,24 +2287,315 @@ impl Plugin {
         }
     }
 
-    fn header_type(&self) -> &'static [u8] {
-        match self.game_id {
-            GameId::Morrowind => b"TES3",
-            _ => b"TES4",
+    /// Read the plugin file at this plugin's path to summarise its size,
+    /// including how much space is taken up by compressed records.
+    pub fn size_report(&self) -> Result<SizeReport, Error> {
+        let file = File::open(&self.path)?;
+        let file_size = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let header_record = Record::read(&mut reader, self.game_id, self.header_type())?;
+        let header_size = u64::from(header_length(self.game_id))
+            + u64::from(header_record.header().size_of_subrecords());
+
+        // Morrowind records can't be compressed.
+        let mut totals = (0, 0);
+        if self.game_id != GameId::Morrowind {
+            let mut header_buf = [0; MAX_RECORD_HEADER_LENGTH];
+            while !reader.fill_buf()?.is_empty() {
+                Group::read_compressed_sizes(
+                    &mut reader,
+                    self.game_id,
+                    &mut totals,
+                    &mut header_buf,
+                )?;
+            }
+        }
+
+        Ok(SizeReport {
+            file_size,
+            header_size,
+            compressed_data_size: totals.0,
+            decompressed_data_size: totals.1,
+        })
+    }
+
+    /// Read the plugin file at this plugin's path to count the records in each
+    /// of its top-level groups, including the records in their subgroups, so
+    /// that e.g. references are counted as part of the CELL or WRLD group.
+    /// Morrowind plugins have no groups, so this is always empty for them.
+    pub fn top_group_counts(&self) -> Result<BTreeMap<[u8; 4], usize>, Error> {
+        let mut reader = BufReader::new(File::open(&self.path)?);
+
+        Record::read(&mut reader, self.game_id, self.header_type())?;
+
+        let mut counts = BTreeMap::new();
+        if self.game_id != GameId::Morrowind {
+            let mut header_buf = [0; MAX_RECORD_HEADER_LENGTH];
+            while !reader.fill_buf()?.is_empty() {
+                let (label, count) =
+                    Group::count_records(&mut reader, self.game_id, &mut header_buf)?;
+                *counts.entry(label).or_insert(0) += count;
+            }
+        }
+
+        Ok(counts)
+    }
+
+    /// Get the grid coordinates of the exterior cells that this plugin adds or
+    /// overrides. Morrowind plugins are not supported and have no exterior
+    /// cells. This needs the plugin to have been parsed with its records
+    /// retained, and errors if any of its CELL records are compressed and the
+    /// `compressed-fields` feature isn't enabled.
+    pub fn exterior_cells(&self) -> Result<Vec<(i32, i32)>, Error> {
+        let records = self.retained_records()?;
+
+        if self.game_id == GameId::Morrowind {
+            return Ok(Vec::new());
+        }
+
+        let mut cells = Vec::new();
+        for record in records
+            .iter()
+            .filter(|r| r.header().record_type() == b"CELL")
+        {
+            let cell = record
+                .readable_subrecords()?
+                .iter()
+                .find(|s| s.subrecord_type() == b"XCLC")
+                .and_then(|s| {
+                    let x = crate::le_slice_to_i32(s.data()).ok()?;
+                    let y = crate::le_slice_to_i32(s.data().get(4..)?).ok()?;
+                    Some((x, y))
+                });
+
+            cells.extend(cell);
+        }
+
+        Ok(cells)
+    }
+
+    /// Get the FormIDs and display names of the plugin's records that have
+    /// display names, in the order that the records appear in the plugin. The
+    /// names are string IDs if the plugin is localized. Morrowind records have
+    /// no FormIDs, so the result is always empty for Morrowind plugins. This
+    /// needs the plugin to have been parsed with its records retained, and
+    /// errors if any of its records are compressed and the `compressed-fields`
+    /// feature isn't enabled.
+    pub fn display_names(&self) -> Result<Vec<(u32, DisplayName)>, Error> {
+        let records = self.retained_records()?;
+
+        if self.game_id == GameId::Morrowind {
+            return Ok(Vec::new());
+        }
+
+        let is_localized = self.is_localized();
+
+        records
+            .iter()
+            .filter_map(|r| {
+                let form_id = r.header().form_id()?.get();
+                match r.readable_subrecords() {
+                    Ok(subrecords) => subrecords
+                        .iter()
+                        .find(|s| s.subrecord_type() == b"FULL")
+                        .map(|s| Ok((form_id, s.data()))),
+                    Err(e) => Some(Err(e)),
+                }
+            })
+            .map(|result| {
+                let (form_id, data) = result?;
+                let name = if is_localized {
+                    let string_id = crate::le_slice_to_u32(data).map_err(|_e| {
+                        Error::ParsingError(data.into(), ParsingErrorKind::SubrecordDataTooShort(4))
+                    })?;
+                    DisplayName::Localized(string_id)
+                } else {
+                    let data = until_first_null(data);
+                    let name = self
+                        .data
+                        .encoding
+                        .encoding()
+                        .decode_without_bom_handling_and_without_replacement(data)
+                        .ok_or_else(|| Error::DecodeError(data.into()))?;
+                    DisplayName::Inline(name.into_owned())
+                };
+
+                Ok((form_id, name))
+            })
+            .collect()
+    }
+
+    /// Get the FormID, type and data size of the `n` records with the largest
+    /// data, largest first. Records of the same size are given in the order
+    /// that they appear in the plugin. The size of a compressed record's data
+    /// is its compressed size. Morrowind records have no FormIDs, so the
+    /// result is always empty for Morrowind plugins. This needs the plugin to
+    /// have been parsed with its records retained. Only record headers are
+    /// read, so compressed records are supported whether or not the
+    /// `compressed-fields` feature is enabled.
+    pub fn largest_records(&self, n: usize) -> Result<Vec<(u32, [u8; 4], u32)>, Error> {
+        let records = self.retained_records()?;
+
+        let mut sizes: Vec<_> = records
+            .iter()
+            .filter_map(|r| {
+                let header = r.header();
+                Some((
+                    header.form_id()?.get(),
+                    *header.record_type(),
+                    header.size_of_subrecords(),
+                ))
+            })
+            .collect();
+
+        sizes.sort_by_key(|(_, _, size)| std::cmp::Reverse(*size));
+        sizes.truncate(n);
+
+        Ok(sizes)
+    }
+
+    /// Get the FormIDs of the plugin's records that have their deleted flag
+    /// set, in the order that they appear in the plugin. Morrowind records
+    /// have no FormIDs, so the result is always empty for Morrowind plugins.
+    /// This needs the plugin to have been parsed with its records retained.
+    /// Only record headers are read, so compressed records are supported
+    /// whether or not the `compressed-fields` feature is enabled.
+    pub fn deleted_records(&self) -> Result<Vec<u32>, Error> {
+        Ok(self
+            .retained_records()?
+            .iter()
+            .map(Record::header)
+            .filter(|h| h.is_deleted())
+            .filter_map(|h| h.form_id().map(NonZeroU32::get))
+            .collect())
+    }
+
+    /// Check if any of the plugin's records have their deleted flag set. This
+    /// has the same requirements as `deleted_records()`.
+    pub fn has_deleted_records(&self) -> Result<bool, Error> {
+        self.deleted_records().map(|records| !records.is_empty())
+    }
+
+    /// Get the FormIDs of the plugin's records that override a record from one
+    /// of the given masters without changing it, i.e. that have the same type,
+    /// flags and subrecord data byte-for-byte. FormIDs are given as they
+    /// appear in the plugin, in the order that their records appear. Masters
+    /// should be given in load order, as if more than one of them contains a
+    /// record then the last is compared against. Subrecord data isn't
+    /// adjusted for any differences in mod indices. Morrowind records have no
+    /// FormIDs, so the result is always empty for Morrowind plugins. This
+    /// needs this plugin and the masters to have been parsed with their
+    /// records retained, and to have had their records resolved if run for
+    /// Starfield.
+    pub fn identical_to_master_records(&self, masters: &[&Plugin]) -> Result<Vec<u32>, Error> {
+        let records = self.retained_records()?;
+        if self.game_id == GameId::Morrowind {
+            return Ok(Vec::new());
+        }
+
+        let resolver = self.resolver()?;
+
+        let mut master_records = HashMap::new();
+        for master in masters {
+            let master_resolver = master.resolver()?;
+            for record in master.retained_records()? {
+                if let Some(form_id) = record.header().form_id() {
+                    master_records.insert(master_resolver.resolve(form_id.get()), record);
+                }
+            }
         }
+
+        Ok(records
+            .iter()
+            .filter_map(|record| {
+                let form_id = record.header().form_id()?.get();
+                let record_id = resolver.resolve(form_id);
+
+                let master_record = master_records.get(&record_id)?;
+
+                (record_id.is_overridden_record() && record.has_same_content(master_record))
+                    .then_some(form_id)
+            })
+            .collect())
+    }
+
+    /// Get a bitset of the object indices of the records that this plugin
+    /// adds, for fast membership tests. The bitset covers the range of object
+    /// indices that the plugin's scale allows, so is small for light and
+    /// medium plugins, but 2 MiB for full plugins. It's extended to cover any
+    /// out-of-range object indices. Morrowind records have no object indices,
+    /// so the bitset is always empty for Morrowind plugins. This needs records
+    /// to be resolved first if run for Morrowind or Starfield.
+    #[cfg(feature = "fixedbitset")]
+    pub fn object_index_bitset(&self) -> Result<FixedBitSet, Error> {
+        let range_end = match self.scale() {
+            PluginScale::Small => *self.valid_light_form_id_range().end(),
+            PluginScale::Medium => *self.valid_medium_form_id_range().end(),
+            PluginScale::Full => u32::from(ObjectIndexMask::Full),
+        };
+
+        let mut bitset =
+            FixedBitSet::with_capacity(crate::u32_to_usize(range_end).saturating_add(1));
+        bitset.extend(
+            self.new_object_indices()?
+                .into_iter()
+                .map(crate::u32_to_usize),
+        );
+
+        Ok(bitset)
+    }
+
+    /// Get the plugin's FormID resolver, which is only missing if the
+    /// plugin's records haven't been resolved.
+    fn resolver(&self) -> Result<&FormIdResolver, Error> {
+        self.data
+            .form_id_resolver
+            .as_ref()
+            .ok_or_else(|| Error::UnresolvedRecordIds(self.path.clone()))
+    }
+
+    /// Get the sorted object indices of the records that this plugin adds.
+    fn new_object_indices(&self) -> Result<Vec<u32>, Error> {
+        let mut object_indices: Vec<u32> = match &self.data.record_ids {
+            RecordIds::None => Vec::new(),
+            RecordIds::FormIds(_) | RecordIds::NamespacedIds(_) => {
+                return Err(Error::UnresolvedRecordIds(self.path.clone()))
+            }
+            RecordIds::Resolved(record_ids) => record_ids
+                .iter()
+                .filter(|r| !r.is_overridden_record())
+                .filter_map(ResolvedRecordId::object_index)
+                .collect(),
+        };
+
+        object_indices.sort_unstable();
+
+        Ok(object_indices)
+    }
+
+    fn retained_records(&self) -> Result<&[Record], Error> {
+        self.data
+            .records
+            .as_deref()
+            .ok_or_else(|| Error::RecordsNotRetained(self.path.clone()))
+    }
+
+    fn header_type(&self) -> &'static [u8] {
+        header_type(self.game_id)
     }
 
     fn is_master_flag_set(&self) -> bool {
-        match self.game_id {
-            GameId::Morrowind => self
+        match header_layout(self.game_id).flags_offset {
+            Some(flags_offset) => self
                 .data
                 .header_record
                 .subrecords()
                 .iter()
                 .find(|s| s.subrecord_type() == b"HEDR")
-                .and_then(|s| s.data().get(4))
+                .and_then(|s| s.data().get(flags_offset))
                 .is_some_and(|b| b & 0x1 != 0),
-            _ => self.data.header_record.header().flags() & 0x1 != 0,
+            None => self.data.header_record.header().flags() & 0x1 != 0,
         }
     }
 
@@ -516,6 +2625,24 @@ impl Plugin {
         }
     }
 
+    /// Get the minimum version of the game that is needed to load this
+    /// plugin, if its header version is newer than earlier versions of the
+    /// game support. Returns `None` if any version of the game can load it.
+    pub fn minimum_game_version(&self) -> Option<GameVersionRequirement> {
+        let version = self.header_version()?;
+
+        let game_version = match self.game_id {
+            GameId::SkyrimSE if version >= 1.71 => (1, 6, 1130),
+            GameId::Fallout4 if version >= 1.0 => (1, 10, 980),
+            _ => return None,
+        };
+
+        Some(GameVersionRequirement {
+            game_id: self.game_id,
+            game_version,
+        })
+    }
+
     fn valid_light_form_id_range(&self) -> RangeInclusive<u32> {
         match self.game_id {
             GameId::SkyrimSE => match self.header_version() {
@@ -578,77 +2705,383 @@ pub fn plugins_metadata(plugins: &[&Plugin]) -> Result<Vec<PluginMetadata>, Erro
     Ok(vec)
 }
 
-fn sorted_slices_intersect<T: PartialOrd>(left: &[T], right: &[T]) -> bool {
-    let mut left_iter = left.iter();
-    let mut right_iter = right.iter();
-
-    let mut left_element = left_iter.next();
-    let mut right_element = right_iter.next();
+/// Resolve the record IDs of all the given plugins, which should include all
+/// their masters, using metadata that is calculated once for all of them.
+/// The metadata is calculated before any plugin's record IDs are resolved, as
+/// Morrowind plugins' metadata needs their unresolved record IDs.
+pub fn resolve_all(plugins: &mut [Plugin]) -> Result<(), Error> {
+    let plugin_refs: Vec<&Plugin> = plugins.iter().collect();
+    let metadata = plugins_metadata(&plugin_refs)?;
 
-    while let (Some(left_value), Some(right_value)) = (left_element, right_element) {
-        if left_value < right_value {
-            left_element = left_iter.next();
-        } else if left_value > right_value {
-            right_element = right_iter.next();
-        } else {
-            return true;
-        }
+    for plugin in plugins {
+        plugin.resolve_record_ids(&metadata)?;
     }
 
-    false
+    Ok(())
 }
 
-fn resolve_form_ids(
-    game_id: GameId,
-    form_ids: &[u32],
-    plugin_metadata: &PluginMetadata,
-    masters: &[String],
-    other_plugins_metadata: &[PluginMetadata],
-) -> Result<Vec<ResolvedRecordId>, Error> {
-    let hashed_parent = hashed_parent(game_id, plugin_metadata);
-    let hashed_masters = match game_id {
-        GameId::Starfield => hashed_masters_for_starfield(masters, other_plugins_metadata)?,
-        _ => hashed_masters(masters),
-    };
-
-    let mut form_ids: Vec<_> = form_ids
-        .iter()
-        .map(|form_id| ResolvedRecordId::from_form_id(hashed_parent, &hashed_masters, *form_id))
-        .collect();
+impl TryFrom<&Path> for Plugin {
+    type Error = Error;
 
-    form_ids.sort();
+    fn try_from(path: &Path) -> Result<Self, Self::Error> {
+        Plugin::open(path)
+    }
+}
 
-    Ok(form_ids)
+/// Guess the game that a plugin with a 24-byte TES4 header is for from its
+/// header version.
+fn game_id_from_header_version(version: Option<f32>) -> GameId {
+    let is_near = |target: f32| version.is_some_and(|v| (v - target).abs() < 0.001);
+
+    if is_near(0.94) {
+        GameId::Fallout3
+    } else if (1.32..1.345).contains(&version.unwrap_or_default()) {
+        GameId::FalloutNV
+    } else if is_near(0.95) || is_near(1.0) {
+        GameId::Fallout4
+    } else if is_near(0.96) {
+        GameId::Starfield
+    } else {
+        GameId::SkyrimSE
+    }
 }
 
-fn resolve_namespaced_ids(
-    namespaced_ids: &[NamespacedId],
-    masters: &[String],
-    other_plugins_metadata: &[PluginMetadata],
-) -> Result<Vec<ResolvedRecordId>, Error> {
-    let mut record_ids: HashSet<NamespacedId> = HashSet::new();
+/// Check if the file at the given path is a plugin for any supported game, by
+/// trying to read its header as a TES3 header and then as each length of TES4
+/// header.
+pub fn is_valid_plugin_file(path: &Path) -> bool {
+    [GameId::Morrowind, GameId::Oblivion, GameId::SkyrimSE]
+        .into_iter()
+        .any(|game_id| Plugin::is_valid(game_id, path, ParseOptions::header_only()))
+}
 
-    for master in masters {
-        let master_record_ids = other_plugins_metadata
-            .iter()
-            .find(|m| unicase::eq(&m.filename, master))
-            .map(|m| &m.record_ids)
-            .ok_or_else(|| Error::PluginMetadataNotFound(master.clone()))?;
+/// Whether the records that two plugins add could be merged into one plugin.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct MergeReport {
+    /// The object indices that are used by new records in both plugins.
+    pub colliding_object_indices: Vec<u32>,
+    /// The number of new records that the merged plugin would have.
+    pub new_record_count: usize,
+    /// True if there are few enough new records for them to fit in the range
+    /// of object indices that a light plugin can use.
+    pub fits_light_plugin: bool,
+    /// True if there are few enough new records for them to fit in the range
+    /// of object indices that a medium plugin can use.
+    pub fits_medium_plugin: bool,
+}
 
-        record_ids.extend(master_record_ids.iter().cloned());
-    }
+/// Check if the records that the two plugins add could be merged into one
+/// plugin, using the first plugin's game and header version to decide which
+/// object indices are valid. This needs records to be resolved first if run
+/// for Morrowind or Starfield, and Morrowind records have no object indices.
+pub fn can_merge(a: &Plugin, b: &Plugin) -> Result<MergeReport, Error> {
+    let a_indices = a.new_object_indices()?;
+    let b_indices = b.new_object_indices()?;
 
-    let mut resolved_ids: Vec<_> = namespaced_ids
-        .iter()
-        .map(|id| ResolvedRecordId::from_namespaced_id(id, &record_ids))
+    let colliding_object_indices = sorted_slices_intersection(&a_indices, &b_indices)
+        .map(|(i, _)| *i)
         .collect();
-
-    resolved_ids.sort();
-
-    Ok(resolved_ids)
+    let new_record_count = a_indices.len() + b_indices.len();
+
+    let fits_light_plugin = a.game_id.supports_light_plugins()
+        && new_record_count <= range_len(&a.valid_light_form_id_range());
+    let fits_medium_plugin = a.game_id.supports_medium_plugins()
+        && new_record_count <= range_len(&a.valid_medium_form_id_range());
+
+    Ok(MergeReport {
+        colliding_object_indices,
+        new_record_count,
+        fits_light_plugin,
+        fits_medium_plugin,
+    })
 }
 
-fn hashed_parent(game_id: GameId, parent_metadata: &PluginMetadata) -> SourcePlugin {
+fn range_len(range: &RangeInclusive<u32>) -> usize {
+    if range.is_empty() {
+        0
+    } else {
+        crate::u32_to_usize(range.end() - range.start()) + 1
+    }
+}
+
+/// How a plugin's masters changed between two versions of the plugin.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct MasterDiff {
+    /// Masters of the new plugin that the old plugin didn't have.
+    pub added: Vec<String>,
+    /// Masters of the old plugin that the new plugin doesn't have.
+    pub removed: Vec<String>,
+    /// Masters that both plugins have but in a different order relative to
+    /// each other, mapped to their old and new indices.
+    pub reordered: BTreeMap<String, (usize, usize)>,
+}
+
+/// Compare the masters of two versions of a plugin. Master names are compared
+/// case-insensitively, and the names given in the diff are as written in the
+/// new plugin, except for removed masters.
+pub fn master_diff(old: &Plugin, new: &Plugin) -> Result<MasterDiff, Error> {
+    let old_masters = old.masters()?;
+    let new_masters = new.masters()?;
+
+    let position =
+        |masters: &[String], name: &str| masters.iter().position(|m| unicase::eq(m.as_str(), name));
+
+    let added = new_masters
+        .iter()
+        .filter(|m| position(&old_masters, m).is_none())
+        .cloned()
+        .collect();
+
+    let removed: Vec<String> = old_masters
+        .iter()
+        .filter(|m| position(&new_masters, m).is_none())
+        .cloned()
+        .collect();
+
+    // Ignore added and removed masters when comparing order.
+    let old_common: Vec<&String> = old_masters
+        .iter()
+        .filter(|m| !removed.contains(m))
+        .collect();
+
+    let reordered = new_masters
+        .iter()
+        .enumerate()
+        .filter_map(|(new_index, name)| position(&old_masters, name).map(|i| (new_index, i, name)))
+        .enumerate()
+        .filter_map(|(common_index, (new_index, old_index, name))| {
+            let old_common_index = old_common
+                .iter()
+                .position(|m| unicase::eq(m.as_str(), name.as_str()))?;
+
+            (old_common_index != common_index).then(|| (name.clone(), (old_index, new_index)))
+        })
+        .collect();
+
+    Ok(MasterDiff {
+        added,
+        removed,
+        reordered,
+    })
+}
+
+/// How a plugin's records changed between two versions of the plugin.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct RecordDiff {
+    /// Records in the new plugin that the old plugin didn't have.
+    pub added: Vec<ResolvedRecordId>,
+    /// Records in the old plugin that the new plugin doesn't have.
+    pub removed: Vec<ResolvedRecordId>,
+    /// Records that both plugins have, as they are in the new plugin.
+    pub common: Vec<ResolvedRecordId>,
+}
+
+/// Compare the records of two versions of a plugin. Resolved record IDs
+/// identify records by the name of the plugin they come from rather than by
+/// mod index, so they can be compared even if the plugins' masters are
+/// different or in a different order. However, the records that a plugin adds
+/// are identified by its filename, so an error is returned if the two plugins'
+/// filenames aren't case-insensitively equal. This needs both plugins' records
+/// to be resolved first if run for Morrowind or Starfield.
+pub fn record_set_diff(old: &Plugin, new: &Plugin) -> Result<RecordDiff, Error> {
+    let old_filename = old
+        .filename()
+        .ok_or_else(|| Error::NoFilename(old.path.clone()))?;
+    let new_filename = new
+        .filename()
+        .ok_or_else(|| Error::NoFilename(new.path.clone()))?;
+    if !unicase::eq(old_filename.as_str(), new_filename.as_str()) {
+        return Err(Error::PluginFilenameMismatch(
+            old.path.clone(),
+            new.path.clone(),
+        ));
+    }
+
+    let mut old_ids = old.resolved_record_ids()?.iter().peekable();
+    let mut new_ids = new.resolved_record_ids()?.iter().peekable();
+
+    let mut diff = RecordDiff::default();
+    loop {
+        match (old_ids.peek(), new_ids.peek()) {
+            (Some(o), Some(n)) => match o.cmp(n) {
+                std::cmp::Ordering::Less => diff.removed.extend(old_ids.next()),
+                std::cmp::Ordering::Greater => diff.added.extend(new_ids.next()),
+                std::cmp::Ordering::Equal => {
+                    old_ids.next();
+                    diff.common.extend(new_ids.next());
+                }
+            },
+            (Some(_), None) => diff.removed.extend(old_ids.by_ref()),
+            (None, Some(_)) => diff.added.extend(new_ids.by_ref()),
+            (None, None) => break,
+        }
+    }
+
+    Ok(diff)
+}
+
+/// Get the sorted union of the given plugins' resolved record IDs, with each
+/// record appearing once no matter how many of the plugins have it. This
+/// needs all the plugins' records to be resolved first if run for Morrowind
+/// or Starfield.
+pub fn merged_record_ids(plugins: &[&Plugin]) -> Result<Vec<ResolvedRecordId>, Error> {
+    let mut iters = plugins
+        .iter()
+        .map(|p| p.resolved_record_ids().map(<[_]>::iter))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut heap: BinaryHeap<_> = iters
+        .iter_mut()
+        .enumerate()
+        .filter_map(|(i, iter)| iter.next().map(|id| Reverse((*id, i))))
+        .collect();
+
+    let mut merged: Vec<ResolvedRecordId> = Vec::new();
+    while let Some(Reverse((id, i))) = heap.pop() {
+        if merged.last() != Some(&id) {
+            merged.push(id);
+        }
+
+        if let Some(next) = iters.get_mut(i).and_then(Iterator::next) {
+            heap.push(Reverse((*next, i)));
+        }
+    }
+
+    Ok(merged)
+}
+
+/// Map each record in the given load order to the index of the plugin that
+/// wins it, i.e. the last plugin in the load order that contains the record.
+/// This needs all the plugins' records to be resolved first if run for
+/// Morrowind or Starfield.
+pub fn winning_records(load_order: &[&Plugin]) -> Result<HashMap<ResolvedRecordId, usize>, Error> {
+    let mut winners = HashMap::new();
+
+    for (index, plugin) in load_order.iter().enumerate() {
+        for record_id in plugin.resolved_record_ids()? {
+            winners.insert(*record_id, index);
+        }
+    }
+
+    Ok(winners)
+}
+
+/// Read all of a plugin's records in the order that they appear, starting with
+/// its header record. Records inside groups are included, but the groups
+/// themselves are not. All the plugin's record data is held in memory, so this
+/// is best avoided for very large plugins.
+pub fn read_all_records<R: BufRead + Seek>(
+    mut reader: R,
+    game_id: GameId,
+) -> Result<Vec<Record>, Error> {
+    let data = read_plugin(
+        &mut reader,
+        game_id,
+        ParseOptions::builder().retain_records(true).build(),
+        header_type(game_id),
+        &mut ParseBuffers::default(),
+        &mut |_| {},
+    )?;
+
+    Ok(std::iter::once(data.header_record)
+        .chain(data.records.unwrap_or_default())
+        .collect())
+}
+
+/// Get the masters of `removed` that none of the `others` plugins have as a
+/// master, i.e. those that would no longer be needed if `removed` was removed.
+/// Master names are compared case-insensitively, and are given as written in
+/// `removed`.
+pub fn orphaned_masters_if_removed(
+    removed: &Plugin,
+    others: &[&Plugin],
+) -> Result<Vec<String>, Error> {
+    let mut other_masters = HashSet::new();
+    for plugin in others {
+        other_masters.extend(plugin.normalized_masters()?);
+    }
+
+    Ok(removed
+        .masters()?
+        .into_iter()
+        .filter(|m| !other_masters.contains(&normalize_filename(m)))
+        .collect())
+}
+
+fn sorted_slices_intersect<T: PartialOrd>(left: &[T], right: &[T]) -> bool {
+    let mut left_iter = left.iter();
+    let mut right_iter = right.iter();
+
+    let mut left_element = left_iter.next();
+    let mut right_element = right_iter.next();
+
+    while let (Some(left_value), Some(right_value)) = (left_element, right_element) {
+        if left_value < right_value {
+            left_element = left_iter.next();
+        } else if left_value > right_value {
+            right_element = right_iter.next();
+        } else {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Iterate over the pairs of equal elements found in the two given sorted
+/// slices.
+fn sorted_slices_intersection<'a, T: Ord>(
+    left: &'a [T],
+    right: &'a [T],
+) -> impl Iterator<Item = (&'a T, &'a T)> {
+    let mut left_iter = left.iter().peekable();
+    let mut right_iter = right.iter().peekable();
+
+    std::iter::from_fn(move || {
+        while let (Some(left_value), Some(right_value)) = (left_iter.peek(), right_iter.peek()) {
+            match left_value.cmp(right_value) {
+                std::cmp::Ordering::Less => {
+                    left_iter.next();
+                }
+                std::cmp::Ordering::Greater => {
+                    right_iter.next();
+                }
+                std::cmp::Ordering::Equal => return left_iter.next().zip(right_iter.next()),
+            }
+        }
+
+        None
+    })
+}
+
+fn resolve_namespaced_ids(
+    namespaced_ids: &[NamespacedId],
+    masters: &[String],
+    other_plugins_metadata: &[PluginMetadata],
+) -> Result<Vec<ResolvedRecordId>, Error> {
+    let mut record_ids: HashSet<NamespacedId> = HashSet::new();
+
+    for master in masters {
+        let master_record_ids = other_plugins_metadata
+            .iter()
+            .find(|m| unicase::eq(&m.filename, master))
+            .map(|m| &m.record_ids)
+            .ok_or_else(|| Error::PluginMetadataNotFound(master.clone()))?;
+
+        record_ids.extend(master_record_ids.iter().cloned());
+    }
+
+    let mut resolved_ids: Vec<_> = namespaced_ids
+        .iter()
+        .map(|id| ResolvedRecordId::from_namespaced_id(id, &record_ids))
+        .collect();
+
+    resolved_ids.sort();
+
+    Ok(resolved_ids)
+}
+
+fn hashed_parent(game_id: GameId, parent_metadata: &PluginMetadata) -> SourcePlugin {
     match game_id {
         GameId::Starfield => {
             // The Creation Kit can create plugins that contain new records that use mod indexes that don't match the plugin's scale (full/medium/small), e.g. a medium plugin might have new records with FormIDs that don't start with 0xFD. However, at runtime the mod index part is replaced with the mod index of the plugin, according to the plugin's scale, so the plugin's scale is what matters when resolving FormIDs for comparison between plugins.
@@ -706,7 +3139,7 @@ fn hashed_masters_for_starfield(
                     ObjectIndexMask::Full,
                 ));
 
-                full_mask += 0x0100_0000;
+                full_mask = full_mask.saturating_add(0x0100_0000);
             }
             PluginScale::Medium => {
                 hashed_masters.push(SourcePlugin::master(
@@ -715,7 +3148,7 @@ fn hashed_masters_for_starfield(
                     ObjectIndexMask::Medium,
                 ));
 
-                medium_mask += 0x0001_0000;
+                medium_mask = medium_mask.saturating_add(0x0001_0000);
             }
             PluginScale::Small => {
                 hashed_masters.push(SourcePlugin::master(
@@ -724,7 +3157,7 @@ fn hashed_masters_for_starfield(
                     ObjectIndexMask::Small,
                 ));
 
-                small_mask += 0x0000_1000;
+                small_mask = small_mask.saturating_add(0x0000_1000);
             }
         }
     }
@@ -732,14 +3165,30 @@ fn hashed_masters_for_starfield(
     Ok(hashed_masters)
 }
 
-fn masters(header_record: &Record) -> Result<Vec<String>, Error> {
+fn header_type(game_id: GameId) -> &'static [u8] {
+    match game_id {
+        GameId::Morrowind => b"TES3",
+        _ => b"TES4",
+    }
+}
+
+fn normalize_filename(filename: &str) -> String {
+    let filename = filename.to_lowercase();
+    match filename.strip_suffix(".ghost") {
+        Some(unghosted) => unghosted.to_owned(),
+        None => filename,
+    }
+}
+
+fn masters(header_record: &Record, encoding: StringEncoding) -> Result<Vec<String>, Error> {
     header_record
         .subrecords()
         .iter()
         .filter(|s| s.subrecord_type() == b"MAST")
         .map(|s| until_first_null(s.data()))
         .map(|d| {
-            WINDOWS_1252
+            encoding
+                .encoding()
                 .decode_without_bom_handling_and_without_replacement(d)
                 .map(|s| s.to_string())
                 .ok_or(Error::DecodeError(d.into()))
@@ -747,26 +3196,66 @@ fn masters(header_record: &Record) -> Result<Vec<String>, Error> {
         .collect()
 }
 
-fn read_form_ids<R: BufRead + Seek>(reader: &mut R, game_id: GameId) -> Result<Vec<u32>, Error> {
-    let mut form_ids = Vec::new();
-    let mut header_buf = [0; MAX_RECORD_HEADER_LENGTH];
+fn read_form_ids<R: BufRead + Seek>(
+    reader: &mut R,
+    game_id: GameId,
+    buffers: &mut ParseBuffers,
+    max_alloc: usize,
+    strict: bool,
+    counter: &mut RecordCounter,
+    progress: &mut dyn FnMut(u64),
+) -> Result<Vec<TypedFormId>, Error> {
+    let mut form_ids = std::mem::take(&mut buffers.form_ids);
+    form_ids.clear();
 
     while !reader.fill_buf()?.is_empty() {
-        Group::read_form_ids(reader, game_id, &mut form_ids, &mut header_buf)?;
+        Group::read_form_ids(
+            reader,
+            game_id,
+            &mut form_ids,
+            &mut buffers.header,
+            max_alloc,
+            strict,
+            counter,
+        )?;
+        progress(reader.stream_position()?);
     }
 
     Ok(form_ids)
 }
 
-fn read_morrowind_record_ids<R: BufRead + Seek>(reader: &mut R) -> Result<RecordIds, Error> {
+/// Get the raw FormIDs of unresolved record IDs, in the order they were read.
+fn raw_form_ids(record_ids: &RecordIds) -> Vec<u32> {
+    match record_ids {
+        RecordIds::FormIds(form_ids) => form_ids.iter().map(|f| f.form_id).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn read_morrowind_record_ids<R: BufRead + Seek>(
+    reader: &mut R,
+    max_alloc: usize,
+    strict: bool,
+    counter: &mut RecordCounter,
+    morrowind_ids: &mut Option<Vec<([u8; 4], String)>>,
+) -> Result<RecordIds, Error> {
     let mut record_ids = Vec::new();
     let mut header_buf = [0; 16]; // Morrowind record headers are 16 bytes long.
 
     while !reader.fill_buf()?.is_empty() {
-        let (_, record_id) =
-            Record::read_record_id(reader, GameId::Morrowind, &mut header_buf, false)?;
+        read_exact(reader, &mut header_buf, None)?;
+        counter.count(&header_buf)?;
+
+        let record_id = if strict {
+            Record::read_with_header(reader, GameId::Morrowind, &header_buf, max_alloc)?.2
+        } else {
+            Record::read_record_id(reader, GameId::Morrowind, &mut header_buf, true, max_alloc)?.1
+        };
 
-        if let Some(RecordId::NamespacedId(record_id)) = record_id {
+        if let Some(RecordId::NamespacedId(record_id, id)) = record_id {
+            if let Some(morrowind_ids) = morrowind_ids {
+                morrowind_ids.push((record_id.record_type(), id));
+            }
             record_ids.push(record_id);
         }
     }
@@ -776,12 +3265,157 @@ fn read_morrowind_record_ids<R: BufRead + Seek>(reader: &mut R) -> Result<Record
     Ok(record_ids.into())
 }
 
-fn read_record_ids<R: BufRead + Seek>(reader: &mut R, game_id: GameId) -> Result<RecordIds, Error> {
+fn read_record_ids<R: BufRead + Seek>(
+    reader: &mut R,
+    game_id: GameId,
+    buffers: &mut ParseBuffers,
+    options: ParseOptions,
+    counter: &mut RecordCounter,
+    morrowind_ids: &mut Option<Vec<([u8; 4], String)>>,
+    progress: &mut dyn FnMut(u64),
+) -> Result<RecordIds, Error> {
+    let max_alloc = options.max_alloc.unwrap_or(usize::MAX);
+
     if game_id == GameId::Morrowind {
-        read_morrowind_record_ids(reader)
+        read_morrowind_record_ids(reader, max_alloc, options.strict, counter, morrowind_ids)
     } else {
-        read_form_ids(reader, game_id).map(Into::into)
+        read_form_ids(
+            reader,
+            game_id,
+            buffers,
+            max_alloc,
+            options.strict,
+            counter,
+            progress,
+        )
+        .map(Into::into)
+    }
+}
+
+fn read_morrowind_records<R: BufRead + Seek>(
+    reader: &mut R,
+    max_alloc: usize,
+    counter: &mut RecordCounter,
+    morrowind_ids: &mut Option<Vec<([u8; 4], String)>>,
+) -> Result<(RecordIds, Vec<Record>), Error> {
+    let mut record_ids = Vec::new();
+    let mut records = Vec::new();
+    let mut header_buf = [0; 16]; // Morrowind record headers are 16 bytes long.
+
+    while !reader.fill_buf()?.is_empty() {
+        read_exact(reader, &mut header_buf, None)?;
+        counter.count(&header_buf)?;
+        let (_, record, record_id) =
+            Record::read_with_header(reader, GameId::Morrowind, &header_buf, max_alloc)?;
+
+        if let Some(RecordId::NamespacedId(record_id, id)) = record_id {
+            if let Some(morrowind_ids) = morrowind_ids {
+                morrowind_ids.push((record_id.record_type(), id));
+            }
+            record_ids.push(record_id);
+        }
+        records.push(record);
+    }
+
+    record_ids.sort();
+
+    Ok((record_ids.into(), records))
+}
+
+fn read_records<R: BufRead + Seek>(
+    reader: &mut R,
+    game_id: GameId,
+    buffers: &mut ParseBuffers,
+    max_alloc: usize,
+    counter: &mut RecordCounter,
+    morrowind_ids: &mut Option<Vec<([u8; 4], String)>>,
+    progress: &mut dyn FnMut(u64),
+) -> Result<(RecordIds, Vec<Record>), Error> {
+    if game_id == GameId::Morrowind {
+        return read_morrowind_records(reader, max_alloc, counter, morrowind_ids);
+    }
+
+    let mut form_ids = std::mem::take(&mut buffers.form_ids);
+    form_ids.clear();
+    let mut records = Vec::new();
+
+    while !reader.fill_buf()?.is_empty() {
+        Group::read_records(
+            reader,
+            game_id,
+            &mut form_ids,
+            &mut records,
+            &mut buffers.header,
+            max_alloc,
+            counter,
+        )?;
+        progress(reader.stream_position()?);
+    }
+
+    Ok((form_ids.into(), records))
+}
+
+fn buffered_reader<R: std::io::Read>(reader: R, options: ParseOptions) -> BufReader<R> {
+    match options.buffer_capacity {
+        Some(capacity) => BufReader::with_capacity(capacity, reader),
+        None => BufReader::new(reader),
+    }
+}
+
+fn read_plugin_data<R: BufRead + Seek>(
+    mut reader: R,
+    game_id: GameId,
+    options: ParseOptions,
+    expected_header_type: &'static [u8],
+    buffers: &mut ParseBuffers,
+    progress: &mut dyn FnMut(u64),
+) -> Result<PluginData, Error> {
+    #[cfg(feature = "xxhash-rust")]
+    if options.compute_hash {
+        let mut reader = HashingReader::new(reader)?;
+        let mut data = read_plugin(
+            &mut reader,
+            game_id,
+            options,
+            expected_header_type,
+            buffers,
+            progress,
+        )?;
+        progress(reader.stream_position()?);
+        data.content_hash = Some(reader.finish()?);
+
+        return Ok(data);
+    }
+
+    let data = read_plugin(
+        &mut reader,
+        game_id,
+        options,
+        expected_header_type,
+        buffers,
+        progress,
+    )?;
+    progress(reader.stream_position()?);
+
+    Ok(data)
+}
+
+/// Skips any bytes before the first occurrence of the header type in the first
+/// `MAX_HEADER_SCAN_LENGTH` bytes. If the header type isn't found, nothing is
+/// skipped.
+fn skip_to_header<R: BufRead>(reader: &mut R, header_type: &[u8]) -> Result<(), Error> {
+    let buffer = reader.fill_buf()?;
+    let search_length = buffer.len().min(MAX_HEADER_SCAN_LENGTH + header_type.len());
+
+    let offset = buffer
+        .get(..search_length)
+        .and_then(|b| memchr::memmem::find(b, header_type));
+
+    if let Some(offset) = offset {
+        reader.consume(offset);
     }
+
+    Ok(())
 }
 
 fn read_plugin<R: BufRead + Seek>(
@@ -789,24 +3423,109 @@ fn read_plugin<R: BufRead + Seek>(
     game_id: GameId,
     options: ParseOptions,
     expected_header_type: &'static [u8],
+    buffers: &mut ParseBuffers,
+    progress: &mut dyn FnMut(u64),
 ) -> Result<PluginData, Error> {
-    let header_record = Record::read(reader, game_id, expected_header_type)?;
+    if options.scan_for_header {
+        skip_to_header(reader, expected_header_type)?;
+    }
+
+    let max_alloc = options.max_alloc.unwrap_or(usize::MAX);
+    let header_record = if options.masters_only {
+        Record::read_masters(reader, game_id, expected_header_type, max_alloc)?
+    } else {
+        Record::read_with_limit(reader, game_id, expected_header_type, max_alloc)?
+    };
+    check_header_is_not_empty(&header_record, game_id, expected_header_type)?;
 
-    if options.header_only {
+    if options.header_only || options.masters_only {
         return Ok(PluginData {
             header_record,
             record_ids: RecordIds::None,
+            records: None,
+            form_id_resolver: None,
+            morrowind_ids: None,
+            record_types: None,
+            file_order_form_ids: None,
+            #[cfg(feature = "xxhash-rust")]
+            content_hash: None,
+            encoding: options.encoding,
         });
     }
 
-    let record_ids = read_record_ids(reader, game_id)?;
+    let mut morrowind_ids = options.collect_morrowind_record_ids.then(Vec::new);
+    let mut counter = RecordCounter::new(options.max_records_scanned, options.collect_record_types);
+
+    if options.retain_records {
+        let (record_ids, records) = read_records(
+            reader,
+            game_id,
+            buffers,
+            max_alloc,
+            &mut counter,
+            &mut morrowind_ids,
+            progress,
+        )?;
+
+        return Ok(PluginData {
+            header_record,
+            file_order_form_ids: options
+                .preserve_form_id_order
+                .then(|| raw_form_ids(&record_ids)),
+            record_ids,
+            records: Some(records),
+            form_id_resolver: None,
+            morrowind_ids,
+            record_types: counter.into_record_types(),
+            #[cfg(feature = "xxhash-rust")]
+            content_hash: None,
+            encoding: options.encoding,
+        });
+    }
+
+    let record_ids = read_record_ids(
+        reader,
+        game_id,
+        buffers,
+        options,
+        &mut counter,
+        &mut morrowind_ids,
+        progress,
+    )?;
 
     Ok(PluginData {
         header_record,
+        file_order_form_ids: options
+            .preserve_form_id_order
+            .then(|| raw_form_ids(&record_ids)),
         record_ids,
+        records: None,
+        form_id_resolver: None,
+        morrowind_ids,
+        record_types: counter.into_record_types(),
+        #[cfg(feature = "xxhash-rust")]
+        content_hash: None,
+        encoding: options.encoding,
     })
 }
 
+/// A truncated or corrupt plugin can have a header record with no data, which
+/// would otherwise look like a header with no masters or description.
+fn check_header_is_not_empty(
+    header_record: &Record,
+    game_id: GameId,
+    header_type: &[u8],
+) -> Result<(), Error> {
+    if game_id != GameId::Morrowind && header_record.header().size_of_subrecords() == 0 {
+        Err(Error::ParsingError(
+            header_type.into(),
+            ParsingErrorKind::EmptyHeader,
+        ))
+    } else {
+        Ok(())
+    }
+}
+
 /// Return the slice up to and not including the first null byte. If there is no
 /// null byte, return the whole string.
 fn until_first_null(bytes: &[u8]) -> &[u8] {
@@ -824,6 +3543,10 @@ mod tests {
     use tempfile::tempdir;
 
     use super::*;
+    use crate::test_utils::{
+        group_bytes, plugin_bytes, plugin_bytes_with_description, plugin_bytes_with_flags,
+        plugin_bytes_with_header_subrecords, record_bytes, subrecord_bytes,
+    };
 
     mod morrowind {
         use super::*;
@@ -1846,6 +4569,100 @@ mod tests {
             assert!(plugin.is_light_plugin());
         }
 
+        fn parsed_plugin(filename: &str, flags: u32) -> Plugin {
+            let mut plugin = Plugin::new(GameId::Fallout4, Path::new(filename));
+            plugin
+                .parse_reader(
+                    Cursor::new(plugin_bytes_with_flags(flags, &[], &[])),
+                    ParseOptions::header_only(),
+                )
+                .unwrap();
+            plugin
+        }
+
+        #[test]
+        fn extension_rules_should_be_able_to_stop_extensions_implying_flags() {
+            let rules = ExtensionRules {
+                esm_implies_master: false,
+                esl_implies_light: false,
+            };
+            let bytes = plugin_bytes_with_flags(0, &[], &[]);
+
+            let mut plugin =
+                Plugin::new_with_rules(GameId::Fallout4, Path::new("Blank.esm"), rules);
+            plugin
+                .parse_reader(Cursor::new(&bytes), ParseOptions::header_only())
+                .unwrap();
+            assert!(!plugin.is_master_file());
+
+            let mut plugin =
+                Plugin::new_with_rules(GameId::Fallout4, Path::new("Blank.esl"), rules);
+            plugin
+                .parse_reader(Cursor::new(&bytes), ParseOptions::header_only())
+                .unwrap();
+            assert!(!plugin.is_master_file());
+            assert!(!plugin.is_light_plugin());
+
+            assert!(parsed_plugin("Blank.esm", 0).is_master_file());
+            assert!(parsed_plugin("Blank.esl", 0).is_light_plugin());
+        }
+
+        #[test]
+        fn extension_rules_should_not_stop_flags_from_having_an_effect() {
+            let rules = ExtensionRules {
+                esm_implies_master: false,
+                esl_implies_light: false,
+            };
+
+            let mut plugin =
+                Plugin::new_with_rules(GameId::Fallout4, Path::new("Blank.esl"), rules);
+            plugin
+                .parse_reader(
+                    Cursor::new(plugin_bytes_with_flags(0x201, &[], &[])),
+                    ParseOptions::header_only(),
+                )
+                .unwrap();
+            assert!(plugin.is_master_file());
+            assert!(plugin.is_light_plugin());
+        }
+
+        #[test]
+        fn is_light_plugin_should_be_true_for_an_esp_file_with_the_light_flag_set() {
+            let plugin = parsed_plugin("Blank.esp", 0x200);
+
+            assert!(plugin.is_light_plugin());
+            assert!(!plugin.is_master_file());
+            assert!(!plugin.is_update_plugin());
+        }
+
+        #[test]
+        fn is_light_plugin_should_be_true_for_an_esm_file_with_the_light_flag_set() {
+            let plugin = parsed_plugin("Blank.esm", 0x200);
+
+            assert!(plugin.is_light_plugin());
+            assert!(plugin.is_master_file());
+        }
+
+        #[test]
+        fn an_esl_file_should_be_a_light_master_whatever_its_flags() {
+            for flags in [0, 0x1, 0x200, 0x201] {
+                let plugin = parsed_plugin("Blank.esl", flags);
+
+                assert!(plugin.is_light_plugin());
+                assert!(plugin.is_master_file());
+            }
+        }
+
+        #[test]
+        fn the_starfield_medium_and_update_flags_should_be_ignored() {
+            let plugin = parsed_plugin("Blank.esm", 0x401);
+
+            assert!(!plugin.is_light_plugin());
+            assert!(!plugin.is_medium_plugin());
+            assert!(plugin.is_master_file());
+            assert!(!plugin.is_update_plugin());
+        }
+
         #[test]
         fn is_medium_plugin_should_be_false() {
             let plugin = Plugin::new(GameId::Fallout4, Path::new("Blank.esp"));
@@ -1908,10 +4725,60 @@ mod tests {
         }
     }
 
-    mod starfield {
+    mod fallout76 {
         use super::*;
 
-        #[test]
+        fn parsed_plugin(filename: &str, flags: u32) -> Plugin {
+            let mut plugin = Plugin::new(GameId::Fallout76, Path::new(filename));
+            plugin
+                .parse_reader(
+                    Cursor::new(plugin_bytes_with_flags(
+                        flags,
+                        &["SeventySix.esm"],
+                        &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0000_0001, &[])),
+                    )),
+                    ParseOptions::whole_plugin(),
+                )
+                .unwrap();
+            plugin
+        }
+
+        #[test]
+        fn parse_reader_should_read_a_tes4_header_and_resolve_form_ids() {
+            let plugin = parsed_plugin("Blank.esp", 0);
+
+            assert_eq!(vec!["SeventySix.esm"], plugin.masters().unwrap());
+            assert_eq!(1, plugin.resolved_record_ids().unwrap().len());
+            assert_eq!(1, plugin.count_override_records().unwrap());
+        }
+
+        #[test]
+        fn is_master_file_should_only_use_the_master_flag() {
+            assert!(parsed_plugin("Blank.esp", 0x1).is_master_file());
+            assert!(!parsed_plugin("Blank.esm", 0).is_master_file());
+            assert!(!parsed_plugin("Blank.esl", 0).is_master_file());
+        }
+
+        #[test]
+        fn is_light_plugin_should_be_false_whatever_the_extension_and_flags() {
+            for filename in ["Blank.esp", "Blank.esm", "Blank.esl"] {
+                for flags in [0, 0x200, 0x100] {
+                    assert!(!parsed_plugin(filename, flags).is_light_plugin());
+                }
+            }
+        }
+
+        #[test]
+        fn is_localized_should_use_the_localized_flag() {
+            assert!(parsed_plugin("Blank.esm", 0x80).is_localized());
+            assert!(!parsed_plugin("Blank.esm", 0).is_localized());
+        }
+    }
+
+    mod starfield {
+        use super::*;
+
+        #[test]
         fn parse_file_should_succeed() {
             let mut plugin = Plugin::new(
                 GameId::Starfield,
@@ -2724,6 +5591,52 @@ mod tests {
         }
     }
 
+    fn typed_form_ids(form_ids: &[u32]) -> Vec<TypedFormId> {
+        form_ids
+            .iter()
+            .map(|f| TypedFormId {
+                form_id: *f,
+                record_type: *b"GLOB",
+            })
+            .collect()
+    }
+
+    fn resolve_form_ids(
+        game_id: GameId,
+        form_ids: &[u32],
+        plugin_metadata: &PluginMetadata,
+        masters: &[String],
+        other_plugins_metadata: &[PluginMetadata],
+    ) -> Result<Vec<ResolvedRecordId>, Error> {
+        FormIdResolver::new(game_id, plugin_metadata, masters, other_plugins_metadata)
+            .map(|resolver| resolver.resolve_all(&typed_form_ids(form_ids)))
+    }
+
+    fn resolved_plugin(
+        game_id: GameId,
+        filename: &str,
+        masters: &[&str],
+        raw_form_ids: &[u32],
+    ) -> Plugin {
+        let masters: Vec<String> = masters.iter().map(|m| (*m).to_owned()).collect();
+        let form_ids = resolve_form_ids(
+            game_id,
+            raw_form_ids,
+            &PluginMetadata {
+                filename: filename.to_owned(),
+                scale: PluginScale::Full,
+                record_ids: Box::new([]),
+            },
+            &masters,
+            &[],
+        )
+        .unwrap();
+
+        let mut plugin = Plugin::new(game_id, Path::new(filename));
+        plugin.data.record_ids = RecordIds::Resolved(form_ids);
+        plugin
+    }
+
     fn write_invalid_plugin(path: &Path) {
         use std::io::Write;
         let mut file = File::create(path).unwrap();
@@ -2731,6 +5644,29 @@ mod tests {
         file.write_all(&bytes).unwrap();
     }
 
+    fn xclc_bytes(x: i32, y: i32) -> Vec<u8> {
+        let mut data = x.to_le_bytes().to_vec();
+        data.extend(y.to_le_bytes());
+        data.extend([0; 4]);
+        subrecord_bytes(*b"XCLC", &data)
+    }
+
+    fn cells_plugin_bytes() -> Vec<u8> {
+        let mut cells = record_bytes(*b"CELL", 0, 0x800, &xclc_bytes(3, -2));
+        cells.extend(record_bytes(
+            *b"CELL",
+            0,
+            0x801,
+            &subrecord_bytes(*b"EDID", b"Interior\0"),
+        ));
+        let mut world_children = record_bytes(*b"CELL", 0, 0x0100_0802, &xclc_bytes(-7, 11));
+        world_children = group_bytes(*b"\0\0\0\0", &world_children);
+        cells.extend(record_bytes(*b"WRLD", 0, 0x803, &[]));
+        cells.extend(world_children);
+
+        plugin_bytes(&["Skyrim.esm"], &group_bytes(*b"CELL", &cells))
+    }
+
     #[test]
     fn parse_file_should_error_if_plugin_does_not_exist() {
         let mut plugin = Plugin::new(GameId::Skyrim, Path::new("Blank.esm"));
@@ -2829,6 +5765,108 @@ mod tests {
         assert_eq!("Blank.esp.ghost", plugin.filename().unwrap());
     }
 
+    #[test]
+    fn runtime_mod_index_should_count_preceding_plugins_of_the_same_scale() {
+        let full = starfield_plugin("Full.esm", 0x1, &[]);
+        let medium = starfield_plugin("Medium.esm", 0x401, &[]);
+        let small = starfield_plugin("Small.esm", 0x101, &[]);
+        let other_full = starfield_plugin("Other.esp", 0, &[]);
+        let other_small = starfield_plugin("OtherSmall.esp", 0x100, &[]);
+        let load_order =
+            plugins_metadata(&[&full, &small, &medium, &other_small, &other_full]).unwrap();
+
+        assert_eq!(Some(0), full.runtime_mod_index(&load_order));
+        assert_eq!(Some(0x0100_0000), other_full.runtime_mod_index(&load_order));
+        assert_eq!(Some(0xFD00_0000), medium.runtime_mod_index(&load_order));
+        assert_eq!(Some(0xFE00_0000), small.runtime_mod_index(&load_order));
+        assert_eq!(
+            Some(0xFE00_1000),
+            other_small.runtime_mod_index(&load_order)
+        );
+    }
+
+    #[test]
+    fn runtime_mod_index_should_be_none_if_the_plugin_is_not_in_the_load_order() {
+        let plugin = starfield_plugin("Test.esp", 0, &[]);
+        let other = starfield_plugin("Other.esp", 0, &[]);
+        let load_order = plugins_metadata(&[&other]).unwrap();
+
+        assert_eq!(None, plugin.runtime_mod_index(&load_order));
+    }
+
+    #[test]
+    fn normalized_masters_should_lowercase_names_and_strip_ghost_extensions() {
+        let plugin = plugin_with_masters(&["Skyrim.esm", "Update.esm.GHOST", "Blank.ESP"]);
+
+        assert_eq!(
+            vec!["skyrim.esm", "update.esm", "blank.esp"],
+            plugin.normalized_masters().unwrap()
+        );
+    }
+
+    #[test]
+    fn is_ghosted_should_be_true_for_a_plugin_with_a_dot_ghost_extension() {
+        let plugin = Plugin::new(GameId::Skyrim, Path::new("Blank.esp.ghost"));
+        assert!(plugin.is_ghosted());
+
+        let plugin = Plugin::new(GameId::Skyrim, Path::new("Blank.esm.GHOST"));
+        assert!(plugin.is_ghosted());
+    }
+
+    #[test]
+    fn is_ghosted_should_be_false_for_a_plugin_without_a_dot_ghost_extension() {
+        let plugin = Plugin::new(GameId::Skyrim, Path::new("Blank.esp"));
+        assert!(!plugin.is_ghosted());
+
+        let plugin = Plugin::new(GameId::Skyrim, Path::new("Blank.ghost.esp"));
+        assert!(!plugin.is_ghosted());
+    }
+
+    #[test]
+    fn unghosted_path_should_strip_a_dot_ghost_extension() {
+        let plugin = Plugin::new(GameId::Skyrim, Path::new("Data/Blank.esp.GHOST"));
+        assert_eq!(Path::new("Data/Blank.esp"), plugin.unghosted_path());
+
+        let plugin = Plugin::new(GameId::Skyrim, Path::new("Blank.esm.ghost.ghost"));
+        assert_eq!(Path::new("Blank.esm.ghost"), plugin.unghosted_path());
+    }
+
+    #[test]
+    fn unghosted_path_should_return_the_path_unchanged_if_the_plugin_is_not_ghosted() {
+        let plugin = Plugin::new(GameId::Skyrim, Path::new("Data/Blank.ghost.esp"));
+        assert_eq!(Path::new("Data/Blank.ghost.esp"), plugin.unghosted_path());
+    }
+
+    #[test]
+    fn is_ghostable_should_be_false_for_a_plugin_that_is_already_ghosted() {
+        let plugin = Plugin::new(GameId::Skyrim, Path::new("Blank.esm.ghost"));
+        assert!(!plugin.is_ghostable());
+    }
+
+    #[test]
+    fn is_ghostable_should_be_true_for_esp_and_esm_files() {
+        let plugin = Plugin::new(GameId::Skyrim, Path::new("Blank.esp"));
+        assert!(plugin.is_ghostable());
+
+        let plugin = Plugin::new(GameId::Morrowind, Path::new("Blank.esm"));
+        assert!(plugin.is_ghostable());
+    }
+
+    #[test]
+    fn is_ghostable_should_be_true_for_esl_files_only_if_the_game_supports_light_plugins() {
+        let plugin = Plugin::new(GameId::Skyrim, Path::new("Blank.esl"));
+        assert!(!plugin.is_ghostable());
+
+        let plugin = Plugin::new(GameId::SkyrimSE, Path::new("Blank.esl"));
+        assert!(plugin.is_ghostable());
+    }
+
+    #[test]
+    fn is_ghostable_should_be_false_for_a_file_with_an_unrecognised_extension() {
+        let plugin = Plugin::new(GameId::Skyrim, Path::new("Blank.bsa"));
+        assert!(!plugin.is_ghostable());
+    }
+
     #[test]
     fn masters_should_be_empty_for_a_plugin_with_no_masters() {
         let mut plugin = Plugin::new(
@@ -2937,6 +5975,3313 @@ mod tests {
         assert!(plugin.record_and_group_count().is_none());
     }
 
+    #[test]
+    fn conflicts_with_should_only_include_records_that_both_plugins_override() {
+        let plugin1 = resolved_plugin(
+            GameId::SkyrimSE,
+            "A.esp",
+            &["Master.esm"],
+            &[0x01, 0x02, 0x0100_0003],
+        );
+        let plugin2 = resolved_plugin(
+            GameId::SkyrimSE,
+            "B.esp",
+            &["Master.esm", "A.esp"],
+            &[0x01, 0x0100_0003],
+        );
+
+        assert!(plugin1.overlaps_with(&plugin2).unwrap());
+
+        let conflicts = plugin1.conflicts_with(&plugin2).unwrap();
+        assert_eq!(1, conflicts.len());
+        assert_eq!(
+            resolved_plugin(GameId::SkyrimSE, "C.esp", &["Master.esm"], &[0x01])
+                .data
+                .record_ids,
+            RecordIds::Resolved(conflicts)
+        );
+    }
+
+    #[test]
+    fn conflicts_with_should_error_if_record_ids_are_unresolved() {
+        let plugin1 = resolved_plugin(GameId::SkyrimSE, "A.esp", &[], &[0x01]);
+        let mut plugin2 = Plugin::new(GameId::Starfield, Path::new("B.esp"));
+        plugin2.data.record_ids = RecordIds::FormIds(typed_form_ids(&[0x01]));
+
+        match plugin1.conflicts_with(&plugin2).unwrap_err() {
+            Error::UnresolvedRecordIds(path) => assert_eq!(plugin2.path, path),
+            _ => panic!("Expected unresolved record IDs error"),
+        }
+    }
+
+    #[test]
+    fn conflicts_with_should_be_empty_if_either_plugin_has_no_record_ids() {
+        let plugin1 = resolved_plugin(GameId::SkyrimSE, "A.esp", &["Master.esm"], &[0x01]);
+        let plugin2 = Plugin::new(GameId::SkyrimSE, Path::new("B.esp"));
+
+        assert!(plugin1.conflicts_with(&plugin2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_reader_should_read_the_same_record_ids_whether_or_not_records_are_retained() {
+        let bytes = cells_plugin_bytes();
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(&bytes), ParseOptions::whole_plugin())
+            .unwrap();
+
+        let mut retained = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        retained
+            .parse_reader(
+                Cursor::new(&bytes),
+                ParseOptions::whole_plugin_with_records(),
+            )
+            .unwrap();
+
+        assert_eq!(plugin.data.record_ids, retained.data.record_ids);
+        assert!(plugin.data.records.is_none());
+        assert_eq!(4, retained.data.records.unwrap().len());
+    }
+
+    fn named_records_plugin(flags: u32, full1: &[u8], full2: &[u8]) -> Plugin {
+        let mut records = record_bytes(*b"WEAP", 0, 0x0100_0800, &subrecord_bytes(*b"FULL", full1));
+        records.extend(record_bytes(
+            *b"WEAP",
+            0,
+            0x0100_0801,
+            &subrecord_bytes(*b"EDID", b"Unnamed\0"),
+        ));
+        records.extend(record_bytes(
+            *b"WEAP",
+            0,
+            0x0100_0802,
+            &subrecord_bytes(*b"FULL", full2),
+        ));
+        let bytes =
+            plugin_bytes_with_flags(flags, &["Skyrim.esm"], &group_bytes(*b"WEAP", &records));
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(bytes),
+                ParseOptions::whole_plugin_with_records(),
+            )
+            .unwrap();
+        plugin
+    }
+
+    #[test]
+    fn display_names_should_decode_inline_full_subrecords() {
+        let plugin = named_records_plugin(0, b"Iron Sword\0", b"Caf\xE9\0");
+
+        assert!(!plugin.is_localized());
+        assert_eq!(
+            vec![
+                (0x0100_0800, DisplayName::Inline("Iron Sword".into())),
+                (0x0100_0802, DisplayName::Inline("Caf\u{e9}".into())),
+            ],
+            plugin.display_names().unwrap()
+        );
+    }
+
+    #[test]
+    fn display_names_should_read_string_ids_if_the_plugin_is_localized() {
+        let plugin = named_records_plugin(0x80, &5u32.to_le_bytes(), &0x1234u32.to_le_bytes());
+
+        assert!(plugin.is_localized());
+        assert_eq!(
+            vec![
+                (0x0100_0800, DisplayName::Localized(5)),
+                (0x0100_0802, DisplayName::Localized(0x1234)),
+            ],
+            plugin.display_names().unwrap()
+        );
+    }
+
+    #[test]
+    fn display_names_should_error_if_records_were_not_retained() {
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes(&[], &[])),
+                ParseOptions::whole_plugin(),
+            )
+            .unwrap();
+
+        assert!(matches!(
+            plugin.display_names(),
+            Err(Error::RecordsNotRetained(_))
+        ));
+    }
+
+    #[test]
+    fn is_localized_should_be_false_for_games_without_string_tables() {
+        let mut plugin = Plugin::new(GameId::FalloutNV, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes_with_flags(0x80, &[], &[])),
+                ParseOptions::header_only(),
+            )
+            .unwrap();
+
+        assert!(!plugin.is_localized());
+    }
+
+    #[test]
+    fn exterior_cells_should_error_if_records_were_not_retained() {
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(cells_plugin_bytes()),
+                ParseOptions::whole_plugin(),
+            )
+            .unwrap();
+
+        match plugin.exterior_cells().unwrap_err() {
+            Error::RecordsNotRetained(path) => assert_eq!(Path::new("Test.esp"), path),
+            e => panic!("Expected records not retained error, got {e:?}"),
+        }
+    }
+
+    #[test]
+    fn exterior_cells_should_return_the_xclc_coordinates_of_all_cell_records() {
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(cells_plugin_bytes()),
+                ParseOptions::whole_plugin_with_records(),
+            )
+            .unwrap();
+
+        let expected: Vec<(i32, i32)> = vec![(3, -2), (-7, 11)];
+        assert_eq!(expected, plugin.exterior_cells().unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "fixedbitset")]
+    fn object_index_bitset_should_cover_the_range_allowed_by_the_plugins_scale() {
+        let plugin = new_records_plugin(GameId::SkyrimSE, "Test.esl", &[0x800, 0x801]);
+
+        let bitset = plugin.object_index_bitset().unwrap();
+
+        assert_eq!(0x1000, bitset.len());
+        assert_eq!(vec![0x800, 0x801], bitset.ones().collect::<Vec<_>>());
+
+        let plugin = new_records_plugin(GameId::SkyrimSE, "Test.esp", &[0x800]);
+
+        let bitset = plugin.object_index_bitset().unwrap();
+
+        assert_eq!(0x0100_0000, bitset.len());
+        assert!(bitset.contains(0x800));
+    }
+
+    #[test]
+    #[cfg(feature = "fixedbitset")]
+    fn object_index_bitset_should_grow_to_fit_out_of_range_object_indices() {
+        let plugin = new_records_plugin(GameId::SkyrimSE, "Test.esl", &[0x800, 0x1001]);
+
+        let bitset = plugin.object_index_bitset().unwrap();
+
+        assert_eq!(0x1002, bitset.len());
+        assert!(bitset.contains(0x1001));
+    }
+
+    #[test]
+    #[cfg(feature = "compressed-fields")]
+    fn exterior_cells_should_read_compressed_cell_records() {
+        use flate2::write::ZlibEncoder;
+        use std::io::Write;
+
+        let subrecords = xclc_bytes(5, 6);
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&subrecords).unwrap();
+
+        let mut data = u32::try_from(subrecords.len())
+            .unwrap()
+            .to_le_bytes()
+            .to_vec();
+        data.extend(encoder.finish().unwrap());
+
+        let cell = record_bytes(*b"CELL", 0x0004_0000, 0x800, &data);
+        let bytes = plugin_bytes(&[], &group_bytes(*b"CELL", &cell));
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(bytes),
+                ParseOptions::whole_plugin_with_records(),
+            )
+            .unwrap();
+
+        let expected: Vec<(i32, i32)> = vec![(5, 6)];
+        assert_eq!(expected, plugin.exterior_cells().unwrap());
+    }
+
+    #[test]
+    #[cfg(not(feature = "compressed-fields"))]
+    fn exterior_cells_and_display_names_should_error_if_a_record_cannot_be_decompressed() {
+        let mut data = 10u32.to_le_bytes().to_vec();
+        data.extend([0xAB; 12]);
+
+        let cell = record_bytes(*b"CELL", 0x0004_0000, 0x800, &data);
+        let bytes = plugin_bytes(&[], &group_bytes(*b"CELL", &cell));
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(bytes),
+                ParseOptions::whole_plugin_with_records(),
+            )
+            .unwrap();
+
+        assert!(matches!(
+            plugin.exterior_cells(),
+            Err(Error::ParsingError(
+                _,
+                ParsingErrorKind::UnsupportedCompression
+            ))
+        ));
+        assert!(matches!(
+            plugin.display_names(),
+            Err(Error::ParsingError(
+                _,
+                ParsingErrorKind::UnsupportedCompression
+            ))
+        ));
+    }
+
+    #[test]
+    fn parse_header_from_reader_should_leave_the_reader_positioned_after_the_header() {
+        let first = plugin_bytes(&["Skyrim.esm"], &[]);
+        let second = plugin_bytes(&[], &[]);
+
+        let mut bytes = first.clone();
+        bytes.extend(&second);
+        bytes.extend(group_bytes(*b"CELL", &[]));
+        let mut reader = Cursor::new(bytes);
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("First.esp"));
+        let bytes_read = plugin.parse_header_from_reader(&mut reader).unwrap();
+
+        assert_eq!(u64::try_from(first.len()).unwrap(), bytes_read);
+        assert_eq!(bytes_read, reader.position());
+        assert_eq!(vec!["Skyrim.esm"], plugin.masters().unwrap());
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Second.esp"));
+        let bytes_read = plugin.parse_header_from_reader(&mut reader).unwrap();
+
+        assert_eq!(u64::try_from(second.len()).unwrap(), bytes_read);
+        assert_eq!(
+            u64::try_from(first.len() + second.len()).unwrap(),
+            reader.position()
+        );
+        assert!(plugin.masters().unwrap().is_empty());
+        assert_eq!(RecordIds::None, plugin.data.record_ids);
+    }
+
+    #[test]
+    fn size_report_should_sum_the_sizes_of_compressed_records() {
+        let mut compressed_data = 100u32.to_le_bytes().to_vec();
+        compressed_data.extend([0; 20]);
+
+        let mut records = record_bytes(*b"NAVM", 0x0004_0000, 0x800, &compressed_data);
+        records.extend(record_bytes(*b"NAVM", 0, 0x801, &[0; 10]));
+        records.extend(record_bytes(*b"NAVM", 0x0004_0000, 0x802, &compressed_data));
+        let bytes = plugin_bytes(&[], &group_bytes(*b"NAVM", &records));
+        let header_size = plugin_bytes(&[], &[]).len();
+
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("Test.esp");
+        std::fs::write(&path, &bytes).unwrap();
+
+        let plugin = Plugin::new(GameId::SkyrimSE, &path);
+        let report = plugin.size_report().unwrap();
+
+        assert_eq!(
+            SizeReport {
+                file_size: u64::try_from(bytes.len()).unwrap(),
+                header_size: u64::try_from(header_size).unwrap(),
+                compressed_data_size: 48,
+                decompressed_data_size: 200,
+            },
+            report
+        );
+    }
+
+    #[test]
+    fn size_report_should_error_if_a_compressed_record_is_too_small() {
+        let records = record_bytes(*b"NAVM", 0x0004_0000, 0x800, &[0; 3]);
+        let bytes = plugin_bytes(&[], &group_bytes(*b"NAVM", &records));
+
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("Test.esp");
+        std::fs::write(&path, bytes).unwrap();
+
+        let plugin = Plugin::new(GameId::SkyrimSE, &path);
+        assert!(plugin.size_report().is_err());
+    }
+
+    #[test]
+    fn top_group_counts_should_include_records_in_subgroups() {
+        let mut refrs = record_bytes(*b"REFR", 0, 0x801, &[]);
+        refrs.extend(record_bytes(*b"REFR", 0, 0x802, &[]));
+        let mut cells = record_bytes(*b"CELL", 0, 0x800, &[]);
+        cells.extend(group_bytes([0, 8, 0, 0], &refrs));
+        let mut groups = group_bytes(*b"CELL", &cells);
+        groups.extend(group_bytes(
+            *b"GLOB",
+            &record_bytes(*b"GLOB", 0, 0x803, &[]),
+        ));
+
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("Test.esp");
+        std::fs::write(&path, plugin_bytes(&[], &groups)).unwrap();
+
+        let plugin = Plugin::new(GameId::SkyrimSE, &path);
+
+        let expected: BTreeMap<[u8; 4], usize> = [(*b"CELL", 3), (*b"GLOB", 1)].into();
+        assert_eq!(expected, plugin.top_group_counts().unwrap());
+    }
+
+    fn plugin_bytes_with_header_version(version: f32) -> Vec<u8> {
+        let mut hedr = version.to_le_bytes().to_vec();
+        hedr.extend([0; 8]);
+        record_bytes(*b"TES4", 0, 0, &subrecord_bytes(*b"HEDR", &hedr))
+    }
+
+    #[test]
+    #[cfg(feature = "testing")]
+    fn with_header_record_should_create_a_plugin_with_the_given_header() {
+        let plugin = Plugin::with_header_record(
+            GameId::SkyrimSE,
+            Path::new("Test.esp"),
+            &plugin_bytes(&["Skyrim.esm"], &[]),
+        )
+        .unwrap();
+
+        assert_eq!(vec!["Skyrim.esm"], plugin.masters().unwrap());
+        assert_eq!(RecordIds::None, plugin.data.record_ids);
+    }
+
+    #[test]
+    fn masters_and_description_should_be_decoded_using_the_given_encoding() {
+        let mut subrecords = subrecord_bytes(*b"HEDR", &[0; 12]);
+        subrecords.extend(subrecord_bytes(*b"SNAM", b"Caf\xc3\xa9\0"));
+        subrecords.extend(subrecord_bytes(*b"MAST", b"Caf\xc3\xa9.esm\0"));
+        let bytes = record_bytes(*b"TES4", 0, 0, &subrecords);
+
+        let parse = |encoding| {
+            let options = ParseOptions::builder()
+                .header_only(true)
+                .encoding(encoding)
+                .build();
+            let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+            plugin.parse_reader(Cursor::new(&bytes), options).unwrap();
+            plugin
+        };
+
+        let plugin = parse(StringEncoding::Windows1252);
+        assert_eq!(vec!["Caf\u{c3}\u{a9}.esm"], plugin.masters().unwrap());
+        assert_eq!(
+            Some("Caf\u{c3}\u{a9}".into()),
+            plugin.description().unwrap()
+        );
+
+        let plugin = parse(StringEncoding::Utf8);
+        assert_eq!(vec!["Caf\u{e9}.esm"], plugin.masters().unwrap());
+        assert_eq!(Some("Caf\u{e9}".into()), plugin.description().unwrap());
+    }
+
+    #[test]
+    fn minimum_game_version_should_map_newer_header_versions_to_a_game_version() {
+        let plugin = |game_id: GameId, version: f32| {
+            let mut plugin = Plugin::new(game_id, Path::new("Test.esp"));
+            plugin
+                .parse_reader(
+                    Cursor::new(plugin_bytes_with_header_version(version)),
+                    ParseOptions::header_only(),
+                )
+                .unwrap();
+            plugin
+        };
+
+        assert_eq!(
+            Some(GameVersionRequirement {
+                game_id: GameId::SkyrimSE,
+                game_version: (1, 6, 1130)
+            }),
+            plugin(GameId::SkyrimSE, 1.71).minimum_game_version()
+        );
+        assert_eq!(
+            Some(GameVersionRequirement {
+                game_id: GameId::Fallout4,
+                game_version: (1, 10, 980)
+            }),
+            plugin(GameId::Fallout4, 1.0).minimum_game_version()
+        );
+        assert!(plugin(GameId::SkyrimSE, 1.7)
+            .minimum_game_version()
+            .is_none());
+        assert!(plugin(GameId::Fallout4, 0.95)
+            .minimum_game_version()
+            .is_none());
+        assert!(plugin(GameId::Skyrim, 1.71)
+            .minimum_game_version()
+            .is_none());
+    }
+
+    #[test]
+    fn parse_reader_should_error_if_the_header_record_is_empty() {
+        let bytes = record_bytes(*b"TES4", 0, 0, &[]);
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        match plugin.parse_reader(Cursor::new(bytes), ParseOptions::header_only()) {
+            Err(Error::ParsingError(_, ParsingErrorKind::EmptyHeader)) => {}
+            r => panic!("Expected empty header error, got {r:?}"),
+        }
+    }
+
+    #[test]
+    fn open_should_detect_the_game_from_the_header_version() {
+        let tmp_dir = tempdir().unwrap();
+        let open = |version: f32| {
+            let path = tmp_dir.path().join("Test.esp");
+            std::fs::write(&path, plugin_bytes_with_header_version(version)).unwrap();
+            Plugin::open(&path).unwrap().game_id
+        };
+
+        assert_eq!(GameId::Fallout3, open(0.94));
+        assert_eq!(GameId::FalloutNV, open(1.34));
+        assert_eq!(GameId::Fallout4, open(1.0));
+        assert_eq!(GameId::Starfield, open(0.96));
+        assert_eq!(GameId::SkyrimSE, open(1.7));
+    }
+
+    #[test]
+    fn open_should_error_if_the_file_is_not_a_plugin() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("Test.esp");
+        std::fs::write(&path, b"not a plugin file").unwrap();
+
+        assert!(Plugin::open(&path).is_err());
+        assert!(Plugin::try_from(path.as_path()).is_err());
+    }
+
+    fn plugin_with_masters(masters: &[&str]) -> Plugin {
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes(masters, &[])),
+                ParseOptions::header_only(),
+            )
+            .unwrap();
+        plugin
+    }
+
+    #[test]
+    fn is_self_referential_should_be_true_if_a_master_has_the_plugins_filename() {
+        assert!(!plugin_with_masters(&["Skyrim.esm"])
+            .is_self_referential()
+            .unwrap());
+        assert!(plugin_with_masters(&["Skyrim.esm", "test.ESP"])
+            .is_self_referential()
+            .unwrap());
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp.ghost"));
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes(&["Test.esp"], &[])),
+                ParseOptions::header_only(),
+            )
+            .unwrap();
+        assert!(plugin.is_self_referential().unwrap());
+    }
+
+    #[test]
+    fn master_diff_should_be_empty_if_masters_are_equal_ignoring_case() {
+        let old = plugin_with_masters(&["Skyrim.esm", "Update.esm"]);
+        let new = plugin_with_masters(&["skyrim.esm", "UPDATE.ESM"]);
+
+        assert_eq!(MasterDiff::default(), master_diff(&old, &new).unwrap());
+    }
+
+    #[test]
+    fn orphaned_masters_if_removed_should_list_masters_no_other_plugin_uses() {
+        let removed = plugin_with_masters(&["Skyrim.esm", "Dawnguard.esm", "Extra.esp"]);
+        let other1 = plugin_with_masters(&["skyrim.esm"]);
+        let other2 = plugin_with_masters(&["Skyrim.esm", "DAWNGUARD.ESM"]);
+
+        assert_eq!(
+            vec!["Dawnguard.esm", "Extra.esp"],
+            orphaned_masters_if_removed(&removed, &[&other1]).unwrap()
+        );
+        assert_eq!(
+            vec!["Extra.esp"],
+            orphaned_masters_if_removed(&removed, &[&other1, &other2]).unwrap()
+        );
+    }
+
+    #[test]
+    fn master_diff_should_list_added_and_removed_masters() {
+        let old = plugin_with_masters(&["Skyrim.esm", "Dawnguard.esm"]);
+        let new = plugin_with_masters(&["Skyrim.esm", "Update.esm", "HearthFires.esm"]);
+
+        let diff = master_diff(&old, &new).unwrap();
+
+        assert_eq!(vec!["Update.esm", "HearthFires.esm"], diff.added);
+        assert_eq!(vec!["Dawnguard.esm"], diff.removed);
+        assert!(diff.reordered.is_empty());
+    }
+
+    #[test]
+    fn master_diff_should_map_reordered_masters_to_their_old_and_new_indices() {
+        let old = plugin_with_masters(&["Skyrim.esm", "Update.esm", "Dawnguard.esm"]);
+        let new = plugin_with_masters(&["Dawnguard.esm", "Added.esm", "Skyrim.esm", "Update.esm"]);
+
+        let diff = master_diff(&old, &new).unwrap();
+
+        assert_eq!(vec!["Added.esm"], diff.added);
+        assert!(diff.removed.is_empty());
+        assert_eq!(
+            BTreeMap::from([
+                ("Dawnguard.esm".to_owned(), (2, 0)),
+                ("Skyrim.esm".to_owned(), (0, 2)),
+                ("Update.esm".to_owned(), (1, 3)),
+            ]),
+            diff.reordered
+        );
+    }
+
+    #[test]
+    fn record_set_diff_should_list_added_removed_and_common_records() {
+        let old = new_records_plugin(GameId::SkyrimSE, "Test.esp", &[0x800, 0x801, 0x803]);
+        let new = new_records_plugin(GameId::SkyrimSE, "Test.esp", &[0x801, 0x802, 0x803, 0x804]);
+
+        let diff = record_set_diff(&old, &new).unwrap();
+
+        let object_indices = |ids: &[ResolvedRecordId]| -> Vec<u32> {
+            ids.iter()
+                .filter_map(ResolvedRecordId::object_index)
+                .collect()
+        };
+        assert_eq!(vec![0x802, 0x804], object_indices(&diff.added));
+        assert_eq!(vec![0x800], object_indices(&diff.removed));
+        assert_eq!(vec![0x801, 0x803], object_indices(&diff.common));
+    }
+
+    #[test]
+    fn record_set_diff_should_match_overrides_if_masters_are_reordered() {
+        let old_bytes = plugin_bytes(
+            &["Skyrim.esm", "Update.esm"],
+            &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0100_0001, &[])),
+        );
+        let new_bytes = plugin_bytes(
+            &["Update.esm", "Skyrim.esm"],
+            &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0000_0001, &[])),
+        );
+
+        let mut old = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        old.parse_reader(Cursor::new(old_bytes), ParseOptions::whole_plugin())
+            .unwrap();
+        let mut new = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        new.parse_reader(Cursor::new(new_bytes), ParseOptions::whole_plugin())
+            .unwrap();
+
+        let diff = record_set_diff(&old, &new).unwrap();
+
+        assert!(diff.added.is_empty());
+        assert!(diff.removed.is_empty());
+        assert_eq!(1, diff.common.len());
+    }
+
+    #[test]
+    fn record_set_diff_should_ignore_filename_case() {
+        let old = new_records_plugin(GameId::SkyrimSE, "Test.esp", &[0x800]);
+        let new = new_records_plugin(GameId::SkyrimSE, "test.ESP", &[0x800]);
+
+        let diff = record_set_diff(&old, &new).unwrap();
+
+        assert_eq!(1, diff.common.len());
+    }
+
+    #[test]
+    fn record_set_diff_should_error_if_the_plugins_have_different_filenames() {
+        let old = new_records_plugin(GameId::SkyrimSE, "Test.esp", &[0x800]);
+        let new = new_records_plugin(GameId::SkyrimSE, "Other.esp", &[0x800]);
+
+        match record_set_diff(&old, &new).unwrap_err() {
+            Error::PluginFilenameMismatch(old_path, new_path) => {
+                assert_eq!(Path::new("Test.esp"), old_path);
+                assert_eq!(Path::new("Other.esp"), new_path);
+            }
+            _ => panic!("Expected plugin filename mismatch error"),
+        }
+    }
+
+    #[test]
+    fn is_valid_plugin_file_should_be_true_for_a_tes4_plugin_with_24_byte_headers() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("Test.esp");
+        std::fs::write(&path, plugin_bytes(&["Skyrim.esm"], &[])).unwrap();
+
+        assert!(is_valid_plugin_file(&path));
+    }
+
+    #[test]
+    fn is_valid_plugin_file_should_be_true_for_a_tes4_plugin_with_20_byte_headers() {
+        let hedr = subrecord_bytes(*b"HEDR", &[0; 12]);
+        let mut bytes = b"TES4".to_vec();
+        bytes.extend(u32::try_from(hedr.len()).unwrap().to_le_bytes());
+        bytes.extend([0; 12]);
+        bytes.extend(hedr);
+
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("Test.esp");
+        std::fs::write(&path, bytes).unwrap();
+
+        assert!(is_valid_plugin_file(&path));
+    }
+
+    #[test]
+    fn is_valid_plugin_file_should_be_false_for_a_non_plugin_file() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("Invalid.esp");
+        write_invalid_plugin(&path);
+
+        assert!(!is_valid_plugin_file(&path));
+    }
+
+    #[test]
+    fn is_valid_plugin_file_should_be_false_for_a_missing_file() {
+        assert!(!is_valid_plugin_file(Path::new("missing.esp")));
+    }
+
+    fn multiple_masters_plugin() -> Plugin {
+        let mut records = record_bytes(*b"GLOB", 0, 0x0000_0001, &[]);
+        records.extend(record_bytes(*b"GLOB", 0, 0x0100_0002, &[]));
+        records.extend(record_bytes(*b"GLOB", 0, 0x0100_0003, &[]));
+        records.extend(record_bytes(*b"GLOB", 0, 0x0200_0004, &[]));
+        let bytes = plugin_bytes(
+            &["Skyrim.esm", "Update.esm"],
+            &group_bytes(*b"GLOB", &records),
+        );
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::whole_plugin())
+            .unwrap();
+        plugin
+    }
+
+    #[test]
+    fn has_invalid_master_references_should_be_false_if_all_mod_indices_are_valid() {
+        let plugin = multiple_masters_plugin();
+
+        assert!(!plugin.has_invalid_master_references().unwrap());
+    }
+
+    #[test]
+    fn has_invalid_master_references_should_be_true_if_a_mod_index_is_past_the_plugin_itself() {
+        let mut records = record_bytes(*b"GLOB", 0, 0x0000_0001, &[]);
+        records.extend(record_bytes(*b"GLOB", 0, 0x0300_0002, &[]));
+        let bytes = plugin_bytes(
+            &["Skyrim.esm", "Update.esm"],
+            &group_bytes(*b"GLOB", &records),
+        );
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::whole_plugin())
+            .unwrap();
+
+        assert!(plugin.has_invalid_master_references().unwrap());
+    }
+
+    #[test]
+    fn has_out_of_range_new_records_should_be_false_if_new_records_use_the_master_count() {
+        let plugin = multiple_masters_plugin();
+
+        assert!(!plugin.has_out_of_range_new_records().unwrap());
+    }
+
+    #[test]
+    fn has_out_of_range_new_records_should_check_mod_indexes_against_starfield_plugin_scale() {
+        let bytes = plugin_bytes_with_flags(
+            0x100,
+            &[],
+            &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0000_0800, &[])),
+        );
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::whole_plugin())
+            .unwrap();
+        plugin.resolve_record_ids(&[]).unwrap();
+
+        assert!(plugin.has_out_of_range_new_records().unwrap());
+
+        let bytes = plugin_bytes_with_flags(
+            0x100,
+            &[],
+            &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0xFE00_0800, &[])),
+        );
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::whole_plugin())
+            .unwrap();
+        plugin.resolve_record_ids(&[]).unwrap();
+
+        assert!(!plugin.has_out_of_range_new_records().unwrap());
+    }
+
+    #[test]
+    fn records_from_master_should_only_return_records_from_the_named_master() {
+        let plugin = multiple_masters_plugin();
+
+        let records = plugin.records_from_master("update.ESM").unwrap();
+
+        assert_eq!(2, records.len());
+        assert!(records.iter().all(ResolvedRecordId::is_overridden_record));
+        assert!(records.iter().all(|r| r.is_object_index_in(&(2..=3))));
+
+        assert_eq!(1, plugin.records_from_master("Skyrim.esm").unwrap().len());
+    }
+
+    #[test]
+    fn records_from_master_should_be_empty_if_the_name_is_not_a_master() {
+        let plugin = multiple_masters_plugin();
+
+        assert!(plugin.records_from_master("Test.esp").unwrap().is_empty());
+        assert!(plugin
+            .records_from_master("Dawnguard.esm")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn records_from_master_should_error_if_record_ids_are_unresolved() {
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new("Test.esp"));
+        plugin.data.record_ids = RecordIds::FormIds(typed_form_ids(&[0x0000_0001]));
+
+        assert!(plugin.records_from_master("Starfield.esm").is_err());
+    }
+
+    #[test]
+    fn referenced_source_plugins_should_include_masters_and_the_plugin_itself() {
+        let plugin = multiple_masters_plugin();
+
+        let expected: HashSet<String> = ["Skyrim.esm", "Update.esm", "Test.esp"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(expected, plugin.referenced_source_plugins().unwrap());
+    }
+
+    #[test]
+    fn referenced_source_plugins_should_not_include_unused_masters() {
+        let bytes = plugin_bytes(
+            &["Skyrim.esm", "Update.esm"],
+            &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0100_0002, &[])),
+        );
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::whole_plugin())
+            .unwrap();
+
+        let expected: HashSet<String> = std::iter::once("Update.esm".to_owned()).collect();
+        assert_eq!(expected, plugin.referenced_source_plugins().unwrap());
+    }
+
+    #[test]
+    fn referenced_source_plugins_should_error_if_record_ids_are_unresolved() {
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new("Test.esp"));
+        plugin.data.record_ids = RecordIds::FormIds(typed_form_ids(&[0x0000_0001]));
+
+        assert!(plugin.referenced_source_plugins().is_err());
+    }
+
+    #[test]
+    fn unused_masters_should_return_masters_that_no_records_come_from() {
+        let bytes = plugin_bytes(
+            &["Skyrim.esm", "Update.esm", "Dawnguard.esm"],
+            &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0100_0002, &[])),
+        );
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::whole_plugin())
+            .unwrap();
+
+        assert_eq!(
+            vec!["Skyrim.esm", "Dawnguard.esm"],
+            plugin.unused_masters().unwrap()
+        );
+        assert!(multiple_masters_plugin()
+            .unused_masters()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn minimal_masters_should_keep_only_referenced_masters_in_declared_order() {
+        let bytes = plugin_bytes(
+            &[
+                "Skyrim.esm",
+                "Update.esm",
+                "Dawnguard.esm",
+                "HearthFires.esm",
+            ],
+            &group_bytes(
+                *b"GLOB",
+                &[
+                    record_bytes(*b"GLOB", 0, 0x0300_0002, &[]),
+                    record_bytes(*b"GLOB", 0, 0x0000_0001, &[]),
+                    record_bytes(*b"GLOB", 0, 0x0400_0003, &[]),
+                ]
+                .concat(),
+            ),
+        );
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(&bytes), ParseOptions::whole_plugin())
+            .unwrap();
+
+        assert_eq!(
+            vec!["Skyrim.esm", "HearthFires.esm"],
+            plugin.minimal_masters().unwrap()
+        );
+
+        plugin
+            .parse_reader(Cursor::new(&bytes), ParseOptions::header_only())
+            .unwrap();
+
+        assert_eq!(4, plugin.minimal_masters().unwrap().len());
+    }
+
+    #[test]
+    fn unused_masters_should_be_empty_if_records_were_not_parsed() {
+        let bytes = plugin_bytes(&["Skyrim.esm"], &[]);
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::header_only())
+            .unwrap();
+
+        assert!(plugin.unused_masters().unwrap().is_empty());
+    }
+
+    #[test]
+    fn master_reference_counts_should_count_the_records_from_each_master() {
+        let bytes = plugin_bytes(
+            &["Skyrim.esm", "Update.esm", "Dawnguard.esm"],
+            &group_bytes(
+                *b"GLOB",
+                &[
+                    record_bytes(*b"GLOB", 0, 0x0000_0001, &[]),
+                    record_bytes(*b"GLOB", 0, 0x0100_0002, &[]),
+                    record_bytes(*b"GLOB", 0, 0x0100_0003, &[]),
+                    record_bytes(*b"GLOB", 0, 0x0300_0004, &[]),
+                ]
+                .concat(),
+            ),
+        );
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::whole_plugin())
+            .unwrap();
+
+        let counts = plugin.master_reference_counts().unwrap();
+
+        let expected = BTreeMap::from([
+            ("Dawnguard.esm".to_owned(), 0),
+            ("Skyrim.esm".to_owned(), 1),
+            ("Update.esm".to_owned(), 2),
+        ]);
+        assert_eq!(expected, counts);
+    }
+
+    #[test]
+    fn master_reference_counts_should_be_empty_if_records_were_not_parsed() {
+        let bytes = plugin_bytes(&["Skyrim.esm"], &[]);
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::header_only())
+            .unwrap();
+
+        assert!(plugin.master_reference_counts().unwrap().is_empty());
+    }
+
+    #[test]
+    fn master_reference_counts_should_error_if_record_ids_are_unresolved() {
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new("Test.esp"));
+        plugin.data.record_ids = RecordIds::FormIds(typed_form_ids(&[0x0000_0001]));
+
+        assert!(plugin.master_reference_counts().is_err());
+    }
+
+    #[test]
+    fn first_conflict_should_return_the_first_overlapping_plugin() {
+        fn skyrim_override_plugin(filename: &str, form_id: u32) -> Plugin {
+            let bytes = plugin_bytes(
+                &["Skyrim.esm"],
+                &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, form_id, &[])),
+            );
+            let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new(filename));
+            plugin
+                .parse_reader(Cursor::new(bytes), ParseOptions::whole_plugin())
+                .unwrap();
+            plugin
+        }
+
+        let plugin = multiple_masters_plugin();
+        let other1 = new_records_plugin(GameId::SkyrimSE, "Other1.esp", &[1]);
+        let other2 = skyrim_override_plugin("Other2.esp", 0x0000_0001);
+        let other3 = skyrim_override_plugin("Other3.esp", 0x0000_0001);
+
+        let conflict = plugin
+            .first_conflict(&[&other1, &other2, &other3])
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some("Other2.esp".into()), conflict.filename());
+
+        assert!(plugin.first_conflict(&[&other1]).unwrap().is_none());
+        assert!(plugin.first_conflict(&[]).unwrap().is_none());
+    }
+
+    #[test]
+    fn first_conflict_should_error_if_record_ids_are_unresolved() {
+        let plugin = multiple_masters_plugin();
+        let mut other = Plugin::new(GameId::Starfield, Path::new("Other.esp"));
+        other.data.record_ids = RecordIds::FormIds(typed_form_ids(&[0x0000_0001]));
+
+        assert!(plugin.first_conflict(&[&other]).is_err());
+    }
+
+    #[test]
+    fn header_form_id_should_return_the_header_record_form_id() {
+        let header = record_bytes(
+            *b"TES4",
+            0,
+            0x0000_0ABC,
+            &subrecord_bytes(*b"HEDR", &[0; 12]),
+        );
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(header), ParseOptions::header_only())
+            .unwrap();
+        assert_eq!(Some(0xABC), plugin.header_form_id());
+
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes(&[], &[])),
+                ParseOptions::header_only(),
+            )
+            .unwrap();
+        assert_eq!(Some(0), plugin.header_form_id());
+    }
+
+    #[test]
+    fn header_form_id_should_be_none_for_morrowind() {
+        let plugin = Plugin::new(GameId::Morrowind, Path::new("Test.esp"));
+
+        assert!(plugin.header_form_id().is_none());
+    }
+
+    #[test]
+    fn load_order_key_should_sort_masters_first_then_by_filename() {
+        let mut plugins = [
+            Plugin::new(GameId::SkyrimSE, Path::new("b.esp")),
+            Plugin::new(GameId::SkyrimSE, Path::new("Data/C.esm")),
+            Plugin::new(GameId::SkyrimSE, Path::new("A.esp.ghost")),
+            Plugin::new(GameId::SkyrimSE, Path::new("a.esl")),
+        ];
+
+        plugins.sort_by_key(Plugin::load_order_key);
+
+        let filenames: Vec<_> = plugins.iter().filter_map(Plugin::filename).collect();
+        assert_eq!(vec!["a.esl", "C.esm", "A.esp.ghost", "b.esp"], filenames);
+        assert_eq!(
+            (true, "a.esp".to_owned()),
+            Plugin::new(GameId::SkyrimSE, Path::new("A.esp.ghost")).load_order_key()
+        );
+    }
+
+    #[test]
+    fn sorted_masters_should_move_masters_before_non_masters_by_extension() {
+        let masters = ["A.esp", "Skyrim.esm", "B.esl", "C.ESM", "D.esp"];
+        let bytes = plugin_bytes(&masters, &[]);
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(&bytes), ParseOptions::header_only())
+            .unwrap();
+        assert_eq!(
+            vec!["Skyrim.esm", "B.esl", "C.ESM", "A.esp", "D.esp"],
+            plugin.sorted_masters().unwrap()
+        );
+
+        let mut plugin = Plugin::new(GameId::Skyrim, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(&bytes), ParseOptions::header_only())
+            .unwrap();
+        assert_eq!(
+            vec!["Skyrim.esm", "C.ESM", "A.esp", "B.esl", "D.esp"],
+            plugin.sorted_masters().unwrap()
+        );
+    }
+
+    #[test]
+    fn largest_records_should_return_the_n_records_with_the_most_data() {
+        let mut records = record_bytes(
+            *b"GLOB",
+            0,
+            0x0100_0800,
+            &subrecord_bytes(*b"DATA", &[0; 4]),
+        );
+        records.extend(record_bytes(
+            *b"GLOB",
+            0,
+            0x0100_0801,
+            &subrecord_bytes(*b"DATA", &[0; 20]),
+        ));
+        records.extend(record_bytes(
+            *b"GLOB",
+            0,
+            0x0100_0802,
+            &subrecord_bytes(*b"DATA", &[0; 4]),
+        ));
+        let mut groups = group_bytes(*b"GLOB", &records);
+        groups.extend(group_bytes(
+            *b"WEAP",
+            &record_bytes(
+                *b"WEAP",
+                0,
+                0x0100_0803,
+                &subrecord_bytes(*b"DATA", &[0; 10]),
+            ),
+        ));
+        let bytes = plugin_bytes(&["Skyrim.esm"], &groups);
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(bytes),
+                ParseOptions::whole_plugin_with_records(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                (0x0100_0801, *b"GLOB", 26),
+                (0x0100_0803, *b"WEAP", 16),
+                (0x0100_0800, *b"GLOB", 10),
+            ],
+            plugin.largest_records(3).unwrap()
+        );
+        assert_eq!(4, plugin.largest_records(10).unwrap().len());
+        assert!(plugin.largest_records(0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn deleted_records_should_return_records_with_the_deleted_flag_set() {
+        let records = [
+            record_bytes(*b"REFR", 0x20, 0x0000_0001, &[]),
+            record_bytes(*b"REFR", 0x400, 0x0100_0800, &[]),
+            record_bytes(*b"REFR", 0x420, 0x0100_0801, &[]),
+        ]
+        .concat();
+        let bytes = plugin_bytes(&["Skyrim.esm"], &group_bytes(*b"REFR", &records));
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(bytes),
+                ParseOptions::whole_plugin_with_records(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            vec![0x0000_0001, 0x0100_0801],
+            plugin.deleted_records().unwrap()
+        );
+        assert!(plugin.has_deleted_records().unwrap());
+    }
+
+    #[test]
+    fn identical_to_master_records_should_return_unchanged_overrides() {
+        let subrecords = [subrecord_bytes(*b"EDID", b"Sword\0")].concat();
+        let master_records = [
+            record_bytes(*b"WEAP", 0, 0x0000_0001, &subrecords),
+            record_bytes(*b"WEAP", 0, 0x0000_0002, &subrecords),
+            record_bytes(*b"WEAP", 0, 0x0000_0003, &subrecords),
+            record_bytes(*b"WEAP", 0, 0x0000_0004, &subrecords),
+        ]
+        .concat();
+        let mut master = Plugin::new(GameId::SkyrimSE, Path::new("Skyrim.esm"));
+        master
+            .parse_reader(
+                Cursor::new(plugin_bytes(&[], &group_bytes(*b"WEAP", &master_records))),
+                ParseOptions::whole_plugin_with_records(),
+            )
+            .unwrap();
+
+        let changed = [subrecord_bytes(*b"EDID", b"Axe\0")].concat();
+        let records = [
+            record_bytes(*b"WEAP", 0, 0x0000_0003, &subrecords),
+            record_bytes(*b"WEAP", 0, 0x0000_0002, &changed),
+            record_bytes(*b"WEAP", 0x20, 0x0000_0004, &subrecords),
+            record_bytes(*b"WEAP", 0, 0x0000_0001, &subrecords),
+            record_bytes(*b"WEAP", 0, 0x0100_0005, &subrecords),
+        ]
+        .concat();
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes(
+                    &["Skyrim.esm"],
+                    &group_bytes(*b"WEAP", &records),
+                )),
+                ParseOptions::whole_plugin_with_records(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            vec![0x0000_0003, 0x0000_0001],
+            plugin.identical_to_master_records(&[&master]).unwrap()
+        );
+        assert!(plugin.identical_to_master_records(&[]).unwrap().is_empty());
+    }
+
+    #[cfg(feature = "compressed-fields")]
+    fn compressed_record_data(subrecords: &[u8]) -> Vec<u8> {
+        use flate2::write::ZlibEncoder;
+        use std::io::Write;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(subrecords).unwrap();
+
+        let mut data = u32::try_from(subrecords.len())
+            .unwrap()
+            .to_le_bytes()
+            .to_vec();
+        data.extend(encoder.finish().unwrap());
+        data
+    }
+
+    /// Without decompression support the compressed data is never read, so it
+    /// doesn't need to actually be compressed.
+    #[cfg(not(feature = "compressed-fields"))]
+    fn compressed_record_data(subrecords: &[u8]) -> Vec<u8> {
+        let mut data = u32::try_from(subrecords.len())
+            .unwrap()
+            .to_le_bytes()
+            .to_vec();
+        data.extend(subrecords);
+        data
+    }
+
+    #[test]
+    fn identical_to_master_records_should_compare_the_content_of_compressed_records() {
+        let sword = compressed_record_data(&subrecord_bytes(*b"EDID", b"Sword\0"));
+        let axe = compressed_record_data(&subrecord_bytes(*b"EDID", b"Axe\0"));
+
+        let master_records = [
+            record_bytes(*b"WEAP", 0x0004_0000, 0x0000_0001, &sword),
+            record_bytes(*b"WEAP", 0x0004_0000, 0x0000_0002, &sword),
+        ]
+        .concat();
+        let mut master = Plugin::new(GameId::SkyrimSE, Path::new("Skyrim.esm"));
+        master
+            .parse_reader(
+                Cursor::new(plugin_bytes(&[], &group_bytes(*b"WEAP", &master_records))),
+                ParseOptions::whole_plugin_with_records(),
+            )
+            .unwrap();
+
+        let records = [
+            record_bytes(*b"WEAP", 0x0004_0000, 0x0000_0001, &sword),
+            record_bytes(*b"WEAP", 0x0004_0000, 0x0000_0002, &axe),
+        ]
+        .concat();
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes(
+                    &["Skyrim.esm"],
+                    &group_bytes(*b"WEAP", &records),
+                )),
+                ParseOptions::whole_plugin_with_records(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            vec![0x0000_0001],
+            plugin.identical_to_master_records(&[&master]).unwrap()
+        );
+    }
+
+    #[test]
+    fn identical_to_master_records_should_error_if_records_were_not_retained() {
+        let master = multiple_masters_plugin();
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes(&["Skyrim.esm"], &[])),
+                ParseOptions::whole_plugin_with_records(),
+            )
+            .unwrap();
+
+        assert!(plugin.identical_to_master_records(&[&master]).is_err());
+        assert!(master.identical_to_master_records(&[]).is_err());
+    }
+
+    #[test]
+    fn has_deleted_records_should_error_if_records_were_not_retained() {
+        let plugin = multiple_masters_plugin();
+
+        assert!(plugin.has_deleted_records().is_err());
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes(&[], &[])),
+                ParseOptions::whole_plugin_with_records(),
+            )
+            .unwrap();
+        assert!(!plugin.has_deleted_records().unwrap());
+    }
+
+    #[test]
+    fn reparse_as_should_parse_the_file_again_for_the_given_game() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("Test.esp");
+        std::fs::write(&path, plugin_bytes(&["Skyrim.esm"], &[])).unwrap();
+
+        let mut plugin = Plugin::new(GameId::Morrowind, &path);
+        assert!(plugin.parse_file(ParseOptions::header_only()).is_err());
+
+        plugin
+            .reparse_as(GameId::SkyrimSE, ParseOptions::header_only())
+            .unwrap();
+
+        assert_eq!(GameId::SkyrimSE, plugin.game_id());
+        assert_eq!(vec!["Skyrim.esm"], plugin.masters().unwrap());
+    }
+
+    #[test]
+    fn reparse_as_should_leave_the_plugin_unchanged_if_parsing_fails() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("Test.esp");
+        std::fs::write(&path, plugin_bytes(&["Skyrim.esm"], &[])).unwrap();
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, &path);
+        plugin.parse_file(ParseOptions::header_only()).unwrap();
+
+        assert!(plugin
+            .reparse_as(GameId::Morrowind, ParseOptions::header_only())
+            .is_err());
+
+        assert_eq!(GameId::SkyrimSE, plugin.game_id());
+        assert_eq!(vec!["Skyrim.esm"], plugin.masters().unwrap());
+    }
+
+    #[test]
+    fn reparse_as_should_leave_the_plugin_unchanged_if_resolving_record_ids_fails() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("Test.esp");
+        std::fs::write(&path, plugin_bytes(&["Skyrim.esm"], &[])).unwrap();
+
+        let options = ParseOptions::builder()
+            .encoding(StringEncoding::Utf8)
+            .build();
+        let mut plugin = Plugin::new(GameId::Skyrim, &path);
+        plugin.parse_file(options).unwrap();
+
+        // The master name isn't valid UTF-8, which only causes an error once
+        // the plugin's FormIDs are resolved.
+        let mut master = subrecord_bytes(*b"MAST", b"\xFF.esm\0");
+        master.extend(subrecord_bytes(*b"DATA", &[0; 8]));
+        std::fs::write(
+            &path,
+            plugin_bytes_with_header_subrecords(
+                0,
+                b"\0",
+                &[],
+                &master,
+                &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0100_0800, &[])),
+            ),
+        )
+        .unwrap();
+
+        assert!(plugin.reparse_as(GameId::SkyrimSE, options).is_err());
+
+        assert_eq!(GameId::Skyrim, plugin.game_id());
+        assert_eq!(vec!["Skyrim.esm"], plugin.masters().unwrap());
+    }
+
+    #[test]
+    fn has_too_many_masters_should_be_true_if_there_are_more_than_255_masters() {
+        let masters: Vec<String> = (0..256u32).map(|i| format!("{i}.esm")).collect();
+        let masters: Vec<&str> = masters.iter().map(String::as_str).collect();
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes(&masters[..255], &[])),
+                ParseOptions::header_only(),
+            )
+            .unwrap();
+        assert!(!plugin.has_too_many_masters().unwrap());
+
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes(&masters, &[])),
+                ParseOptions::header_only(),
+            )
+            .unwrap();
+        assert!(plugin.has_too_many_masters().unwrap());
+    }
+
+    #[test]
+    fn indexed_masters_should_pair_masters_with_their_mod_index_masks() {
+        let plugin = multiple_masters_plugin();
+
+        assert_eq!(
+            vec![
+                (0, "Skyrim.esm".to_owned()),
+                (0x0100_0000, "Update.esm".to_owned())
+            ],
+            plugin.indexed_masters().unwrap()
+        );
+    }
+
+    #[test]
+    fn indexed_masters_should_use_scale_specific_masks_for_starfield() {
+        let bytes = plugin_bytes(&["Full.esm", "Medium.esm", "Small.esm", "Full2.esm"], &[]);
+        let metadata = [
+            ("Full.esm", PluginScale::Full),
+            ("Medium.esm", PluginScale::Medium),
+            ("Small.esm", PluginScale::Small),
+            ("Full2.esm", PluginScale::Full),
+        ]
+        .map(|(filename, scale)| PluginMetadata {
+            filename: filename.to_owned(),
+            scale,
+            record_ids: Box::new([]),
+        });
+
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(&bytes), ParseOptions::whole_plugin())
+            .unwrap();
+        assert!(plugin.indexed_masters().is_err());
+
+        plugin.resolve_record_ids(&metadata).unwrap();
+        assert_eq!(
+            vec![
+                (0, "Full.esm".to_owned()),
+                (0xFD00_0000, "Medium.esm".to_owned()),
+                (0xFE00_0000, "Small.esm".to_owned()),
+                (0x0100_0000, "Full2.esm".to_owned())
+            ],
+            plugin.indexed_masters().unwrap()
+        );
+    }
+
+    #[test]
+    fn has_too_many_masters_should_use_separate_limits_for_starfield_master_scales() {
+        let masters: Vec<String> = (0..0xFEu32).map(|i| format!("{i}.esm")).collect();
+        let master_refs: Vec<&str> = masters.iter().map(String::as_str).collect();
+        let bytes = plugin_bytes(&master_refs, &[]);
+
+        let metadata = |scale| -> Vec<PluginMetadata> {
+            masters
+                .iter()
+                .map(|m| PluginMetadata {
+                    filename: m.clone(),
+                    scale,
+                    record_ids: Box::new([]),
+                })
+                .collect()
+        };
+
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(&bytes), ParseOptions::whole_plugin())
+            .unwrap();
+        assert!(plugin.has_too_many_masters().is_err());
+
+        plugin
+            .resolve_record_ids(&metadata(PluginScale::Medium))
+            .unwrap();
+        assert!(!plugin.has_too_many_masters().unwrap());
+
+        plugin
+            .parse_reader(Cursor::new(&bytes), ParseOptions::whole_plugin())
+            .unwrap();
+        plugin
+            .resolve_record_ids(&metadata(PluginScale::Full))
+            .unwrap();
+        assert!(plugin.has_too_many_masters().unwrap());
+    }
+
+    #[test]
+    fn parse_reader_should_skip_compressed_records_using_their_compressed_size() {
+        // The decompressed size is much larger than the compressed data, so
+        // skipping by it would skip past the next record.
+        let mut compressed_data = 1000u32.to_le_bytes().to_vec();
+        compressed_data.extend([0xAB; 12]);
+
+        let mut records = record_bytes(*b"GLOB", 0x0004_0000, 0x0100_0800, &compressed_data);
+        records.extend(record_bytes(*b"GLOB", 0, 0x0100_0801, &[]));
+        let mut groups = group_bytes(*b"GLOB", &records);
+        groups.extend(group_bytes(
+            *b"WEAP",
+            &record_bytes(*b"WEAP", 0, 0x0100_0802, &[]),
+        ));
+        let bytes = plugin_bytes(&["Skyrim.esm"], &groups);
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::whole_plugin())
+            .unwrap();
+
+        let form_ids: Vec<_> = plugin
+            .resolved_record_ids()
+            .unwrap()
+            .iter()
+            .filter_map(ResolvedRecordId::raw_form_id)
+            .collect();
+        assert_eq!(vec![0x0100_0800, 0x0100_0801, 0x0100_0802], form_ids);
+    }
+
+    #[test]
+    fn parse_reader_should_stop_reading_after_the_masters_if_only_reading_masters() {
+        let mut subrecords = subrecord_bytes(*b"HEDR", &[0; 12]);
+        subrecords.extend(subrecord_bytes(*b"SNAM", b"Description\0"));
+        subrecords.extend(subrecord_bytes(*b"MAST", b"Skyrim.esm\0"));
+        subrecords.extend(subrecord_bytes(*b"DATA", &[0; 8]));
+        subrecords.extend(subrecord_bytes(*b"MAST", b"Update.esm\0"));
+        subrecords.extend(subrecord_bytes(*b"DATA", &[0; 8]));
+        let masters_end = subrecords.len() + 24;
+        subrecords.extend(subrecord_bytes(*b"ONAM", &[0; 400]));
+        let mut bytes = record_bytes(*b"TES4", 0, 0, &subrecords);
+
+        // Truncate the ONAM data to check that it isn't read.
+        bytes.truncate(masters_end + 6);
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        assert!(plugin
+            .parse_reader(Cursor::new(&bytes), ParseOptions::header_only())
+            .is_err());
+
+        let mut reader = Cursor::new(&bytes);
+        plugin
+            .parse_buffered_reader(&mut reader, ParseOptions::masters_only())
+            .unwrap();
+
+        assert_eq!(vec!["Skyrim.esm", "Update.esm"], plugin.masters().unwrap());
+        assert_eq!(None, plugin.description().unwrap());
+        assert_eq!(None, plugin.header_version());
+        assert_eq!(u64::try_from(bytes.len()).unwrap(), reader.position());
+    }
+
+    #[test]
+    fn parse_reader_should_read_the_whole_header_if_only_reading_masters_and_there_are_none() {
+        let bytes = plugin_bytes(&[], &group_bytes(*b"GLOB", &[]));
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::masters_only())
+            .unwrap();
+
+        assert!(plugin.masters().unwrap().is_empty());
+    }
+
+    #[test]
+    fn content_key_should_ignore_the_plugin_path() {
+        let bytes = plugin_bytes(&["Skyrim.esm"], &[]);
+        let parse = |path: &str| {
+            let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new(path));
+            plugin
+                .parse_reader(Cursor::new(&bytes), ParseOptions::header_only())
+                .unwrap();
+            plugin
+        };
+        let plugin1 = parse("a/Test.esp");
+        let plugin2 = parse("b/Test.esp");
+
+        assert_ne!(plugin1, plugin2);
+        assert_eq!(plugin1.content_key(), plugin2.content_key());
+
+        let keys: HashSet<_> = [plugin1.content_key(), plugin2.content_key()].into();
+        assert_eq!(1, keys.len());
+
+        let mut other = Plugin::new(GameId::Skyrim, Path::new("a/Test.esp"));
+        other
+            .parse_reader(Cursor::new(&bytes), ParseOptions::header_only())
+            .unwrap();
+        assert_ne!(plugin1.content_key(), other.content_key());
+    }
+
+    #[test]
+    fn description_exceeds_limit_should_compare_the_description_length_to_the_game_limit() {
+        let parse = |description: &[u8]| {
+            let bytes = plugin_bytes_with_description(0, description, &[], &[]);
+            let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+            plugin
+                .parse_reader(Cursor::new(bytes), ParseOptions::header_only())
+                .unwrap();
+            plugin
+        };
+
+        let mut description = vec![b'a'; 512];
+        description.push(0);
+        assert!(!parse(&description).description_exceeds_limit().unwrap());
+
+        description.insert(0, b'a');
+        assert!(parse(&description).description_exceeds_limit().unwrap());
+
+        let plugin = parse(b"");
+        assert!(!plugin.description_exceeds_limit().unwrap());
+    }
+
+    #[test]
+    fn merged_record_ids_should_return_the_sorted_union_without_duplicates() {
+        let plugin1 = new_records_plugin(GameId::SkyrimSE, "A.esp", &[1, 3]);
+        let plugin2 = new_records_plugin(GameId::SkyrimSE, "B.esp", &[2]);
+        let plugin3 = multiple_masters_plugin();
+
+        let merged = merged_record_ids(&[&plugin1, &plugin2, &plugin3, &plugin1]).unwrap();
+
+        let mut expected: Vec<_> = [&plugin1, &plugin2, &plugin3]
+            .iter()
+            .flat_map(|p| p.resolved_record_ids().unwrap().iter().copied())
+            .collect();
+        expected.sort();
+        expected.dedup();
+
+        assert_eq!(7, merged.len());
+        assert_eq!(expected, merged);
+    }
+
+    #[test]
+    fn merged_record_ids_should_error_if_any_plugin_is_unresolved() {
+        let plugin1 = new_records_plugin(GameId::SkyrimSE, "A.esp", &[1]);
+        let mut plugin2 = Plugin::new(GameId::Starfield, Path::new("B.esp"));
+        plugin2.data.record_ids = RecordIds::FormIds(typed_form_ids(&[0x0000_0001]));
+
+        assert!(merged_record_ids(&[&plugin1, &plugin2]).is_err());
+        assert!(merged_record_ids(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn winning_records_should_map_records_to_the_last_plugin_containing_them() {
+        let a = new_records_plugin(GameId::SkyrimSE, "A.esp", &[0x800]);
+
+        let records = [
+            record_bytes(*b"GLOB", 0, 0x0000_0001, &[]),
+            record_bytes(*b"GLOB", 0, 0x0100_0800, &[]),
+        ]
+        .concat();
+        let mut b = Plugin::new(GameId::SkyrimSE, Path::new("B.esp"));
+        b.parse_reader(
+            Cursor::new(plugin_bytes(
+                &["Skyrim.esm", "A.esp"],
+                &group_bytes(*b"GLOB", &records),
+            )),
+            ParseOptions::whole_plugin(),
+        )
+        .unwrap();
+
+        let mut c = Plugin::new(GameId::SkyrimSE, Path::new("C.esp"));
+        c.parse_reader(
+            Cursor::new(plugin_bytes(
+                &["Skyrim.esm"],
+                &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0000_0001, &[])),
+            )),
+            ParseOptions::whole_plugin(),
+        )
+        .unwrap();
+
+        let winners = winning_records(&[&a, &b, &c]).unwrap();
+
+        let a_record = a.resolved_record_ids().unwrap()[0];
+        let c_record = c.resolved_record_ids().unwrap()[0];
+        assert_eq!(2, winners.len());
+        assert_eq!(Some(&1), winners.get(&a_record));
+        assert_eq!(Some(&2), winners.get(&c_record));
+
+        let winners = winning_records(&[&c, &b, &a]).unwrap();
+        assert_eq!(Some(&2), winners.get(&a_record));
+        assert_eq!(Some(&1), winners.get(&c_record));
+    }
+
+    #[test]
+    fn winning_records_should_error_if_any_plugin_is_unresolved() {
+        let plugin1 = new_records_plugin(GameId::SkyrimSE, "A.esp", &[1]);
+        let mut plugin2 = Plugin::new(GameId::Starfield, Path::new("B.esp"));
+        plugin2.data.record_ids = RecordIds::FormIds(typed_form_ids(&[0x0000_0001]));
+
+        assert!(winning_records(&[&plugin1, &plugin2]).is_err());
+        assert!(winning_records(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn has_plugin_file_extension_should_only_check_the_last_path_component() {
+        assert!(has_plugin_file_extension(Path::new(
+            "a/b.esm/c/d/archive.ba2/plugins/Foo.esl"
+        )));
+        assert!(has_plugin_file_extension(Path::new(
+            "archive.ba2/plugins/Foo.esl.ghost"
+        )));
+        assert!(!has_plugin_file_extension(Path::new("plugins.esp/Foo")));
+        assert!(!has_plugin_file_extension(Path::new(
+            "plugins.esp/Foo.ghost"
+        )));
+    }
+
+    #[test]
+    fn has_plugin_file_extension_should_treat_both_slashes_as_separators() {
+        assert!(has_plugin_file_extension(Path::new(
+            "archive.ba2\\plugins/Foo.esm"
+        )));
+        assert!(has_plugin_file_extension(Path::new(
+            "archive.ba2/plugins\\Foo.ESL.ghost"
+        )));
+        assert!(has_plugin_file_extension(Path::new("Data\\Foo.esp\\")));
+        assert!(!has_plugin_file_extension(Path::new("Data.esp\\Foo")));
+        assert!(!has_plugin_file_extension(Path::new("Data\\.esp")));
+    }
+
+    #[test]
+    fn has_plugin_file_extension_should_not_look_through_more_than_one_ghost_extension() {
+        assert!(!has_plugin_file_extension(Path::new("Foo.esp.ghost.ghost")));
+        assert!(!has_plugin_file_extension(Path::new(".esp.ghost")));
+        assert!(!has_plugin_file_extension(Path::new("Foo.ghost")));
+    }
+
+    #[test]
+    fn is_light_plugin_should_recognise_a_ghosted_esl_in_a_nested_mixed_separator_path() {
+        let plugin = Plugin::new(
+            GameId::SkyrimSE,
+            Path::new("Data\\archive.ba2/plugins\\Foo.esl.ghost"),
+        );
+
+        assert!(plugin.is_light_plugin());
+        assert!(plugin.is_master_file());
+    }
+
+    #[test]
+    fn read_all_records_should_return_the_header_and_all_records_in_order() {
+        let bytes = plugin_bytes(
+            &["Skyrim.esm"],
+            &[
+                group_bytes(
+                    *b"GLOB",
+                    &[
+                        record_bytes(*b"GLOB", 0, 0x0100_0002, &subrecord_bytes(*b"EDID", b"A\0")),
+                        record_bytes(*b"GLOB", 0x20, 0x0000_0001, &[]),
+                    ]
+                    .concat(),
+                ),
+                group_bytes(*b"KYWD", &record_bytes(*b"KYWD", 0, 0x0100_0003, &[])),
+            ]
+            .concat(),
+        );
+
+        let records = read_all_records(Cursor::new(bytes), GameId::SkyrimSE).unwrap();
+
+        let summary: Vec<_> = records
+            .iter()
+            .map(|r| (*r.record_type(), r.form_id()))
+            .collect();
+        assert_eq!(
+            vec![
+                (*b"TES4", None),
+                (*b"GLOB", Some(0x0100_0002)),
+                (*b"GLOB", Some(0x0000_0001)),
+                (*b"KYWD", Some(0x0100_0003)),
+            ],
+            summary
+        );
+        assert_eq!(0x20, records[2].flags());
+        assert_eq!(b"EDID", records[1].subrecords()[0].subrecord_type());
+        assert_eq!(b"A\0", records[1].subrecords()[0].data());
+        assert!(!records[1].is_compressed());
+        assert!(records[0]
+            .subrecords()
+            .iter()
+            .any(|s| s.subrecord_type() == b"MAST" && s.data() == b"Skyrim.esm\0"));
+    }
+
+    #[test]
+    fn read_all_records_should_error_if_the_header_record_is_missing() {
+        let bytes = group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 1, &[]));
+
+        assert!(read_all_records(Cursor::new(bytes), GameId::SkyrimSE).is_err());
+    }
+
+    #[test]
+    #[cfg(not(feature = "compressed-fields"))]
+    fn read_all_records_should_keep_the_data_of_compressed_records() {
+        let mut data = 10u32.to_le_bytes().to_vec();
+        data.extend([0xAB; 12]);
+
+        let bytes = plugin_bytes(
+            &[],
+            &group_bytes(
+                *b"GLOB",
+                &record_bytes(*b"GLOB", 0x0004_0000, 0x0100_0001, &data),
+            ),
+        );
+
+        let records = read_all_records(Cursor::new(bytes), GameId::SkyrimSE).unwrap();
+
+        assert!(!records[0].is_compressed());
+        assert!(records[0].compressed_data().is_none());
+        assert!(records[1].is_compressed());
+        assert!(records[1].subrecords().is_empty());
+        assert_eq!(Some(data.as_slice()), records[1].compressed_data());
+    }
+
+    #[test]
+    fn parse_group_should_only_read_form_ids_from_groups_with_the_given_label() {
+        let mut cell_group = record_bytes(*b"CELL", 0, 0x0100_0800, &[]);
+        cell_group.extend(group_bytes(
+            *b"CHLD",
+            &record_bytes(*b"REFR", 0, 0x0100_0801, &[]),
+        ));
+        let groups = [
+            group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0000_0001, &[])),
+            group_bytes(*b"CELL", &cell_group),
+            group_bytes(*b"WEAP", &record_bytes(*b"WEAP", 0, 0x0100_0802, &[])),
+        ]
+        .concat();
+        let bytes = plugin_bytes(&["Skyrim.esm"], &groups);
+
+        let form_ids =
+            Plugin::parse_group(Cursor::new(&bytes), GameId::SkyrimSE, *b"CELL").unwrap();
+        assert_eq!(vec![0x0100_0800, 0x0100_0801], form_ids);
+
+        let form_ids =
+            Plugin::parse_group(Cursor::new(&bytes), GameId::SkyrimSE, *b"WEAP").unwrap();
+        assert_eq!(vec![0x0100_0802], form_ids);
+
+        let form_ids =
+            Plugin::parse_group(Cursor::new(&bytes), GameId::SkyrimSE, *b"NPC_").unwrap();
+        assert!(form_ids.is_empty());
+    }
+
+    #[test]
+    fn parse_group_should_error_if_the_header_record_is_missing() {
+        let bytes = group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 1, &[]));
+
+        assert!(Plugin::parse_group(Cursor::new(bytes), GameId::SkyrimSE, *b"GLOB").is_err());
+    }
+
+    #[test]
+    fn header_layout_should_match_the_header_type_for_each_game() {
+        for game_id in [
+            GameId::Morrowind,
+            GameId::Oblivion,
+            GameId::SkyrimSE,
+            GameId::Starfield,
+        ] {
+            assert_eq!(
+                header_type(game_id),
+                header_layout(game_id).record_type.as_slice()
+            );
+        }
+    }
+
+    #[test]
+    fn header_layout_offsets_should_be_within_hedr() {
+        let layout = header_layout(GameId::Morrowind);
+        assert_eq!(*b"HEDR", layout.description_subrecord_type);
+        assert!(layout.description_offset < layout.hedr_length);
+        assert_eq!(layout.hedr_length, layout.record_count_offset + 4);
+        assert_eq!(Some(4), layout.flags_offset);
+
+        let layout = header_layout(GameId::Skyrim);
+        assert_eq!(*b"SNAM", layout.description_subrecord_type);
+        assert_eq!(layout.hedr_length, layout.record_count_offset + 8);
+        assert_eq!(None, layout.flags_offset);
+    }
+
+    #[test]
+    fn parse_options_default_should_parse_the_whole_plugin() {
+        assert_eq!(ParseOptions::whole_plugin(), ParseOptions::default());
+        assert_eq!(ParseOptions::default(), ParseOptions::builder().build());
+    }
+
+    #[test]
+    fn parse_options_builder_should_set_the_given_options() {
+        assert_eq!(
+            ParseOptions::header_only(),
+            ParseOptions::builder().header_only(true).build()
+        );
+        assert_eq!(
+            ParseOptions::whole_plugin_with_records(),
+            ParseOptions::builder().retain_records(true).build()
+        );
+        assert_eq!(
+            ParseOptions::strict(),
+            ParseOptions::builder().strict(true).build()
+        );
+        assert_eq!(
+            ParseOptions::masters_only(),
+            ParseOptions::builder()
+                .header_only(true)
+                .masters_only(true)
+                .build()
+        );
+    }
+
+    fn oversized_subrecord_plugin_bytes() -> Vec<u8> {
+        // The subrecord declares 10 bytes of data but only has 4.
+        let mut subrecord = b"DATA".to_vec();
+        subrecord.extend(10u16.to_le_bytes());
+        subrecord.extend([0; 4]);
+
+        plugin_bytes(
+            &["Skyrim.esm"],
+            &group_bytes(
+                *b"GLOB",
+                &record_bytes(*b"GLOB", 0, 0x0100_0800, &subrecord),
+            ),
+        )
+    }
+
+    #[test]
+    fn parse_reader_should_only_check_subrecord_sizes_if_strict_or_retaining_records() {
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        assert!(plugin
+            .parse_reader(
+                Cursor::new(oversized_subrecord_plugin_bytes()),
+                ParseOptions::whole_plugin()
+            )
+            .is_ok());
+
+        for options in [
+            ParseOptions::strict(),
+            ParseOptions::whole_plugin_with_records(),
+        ] {
+            match plugin.parse_reader(Cursor::new(oversized_subrecord_plugin_bytes()), options) {
+                Err(Error::ParsingError(_, ParsingErrorKind::RecordDataSizeMismatch(size))) => {
+                    assert_eq!(10, size);
+                }
+                r => panic!("Expected a record data size mismatch error, got {r:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_reader_should_succeed_in_strict_mode_if_subrecords_fill_their_records() {
+        let bytes = plugin_bytes(
+            &["Skyrim.esm"],
+            &group_bytes(
+                *b"GLOB",
+                &record_bytes(
+                    *b"GLOB",
+                    0,
+                    0x0100_0800,
+                    &subrecord_bytes(*b"DATA", &[0; 4]),
+                ),
+            ),
+        );
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::strict())
+            .unwrap();
+
+        assert!(plugin.contains_form_id(0x0100_0800).unwrap());
+    }
+
+    fn starfield_plugin(filename: &str, flags: u32, masters: &[&str]) -> Plugin {
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new(filename));
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes_with_flags(flags, masters, &[])),
+                ParseOptions::header_only(),
+            )
+            .unwrap();
+        plugin
+    }
+
+    #[test]
+    fn masters_should_only_read_mast_subrecords_in_a_starfield_header() {
+        let mut subrecords = subrecord_bytes(*b"HEDR", &[0; 12]);
+        subrecords.extend(subrecord_bytes(*b"MAST", b"Starfield.esm\0"));
+        subrecords.extend(subrecord_bytes(*b"ONAM", &[0; 4]));
+        subrecords.extend(subrecord_bytes(*b"MAST", b"Constellation.esm\0"));
+        subrecords.extend(subrecord_bytes(*b"DATA", &[0; 8]));
+        subrecords.extend(subrecord_bytes(*b"MAST", b"Blank.esm\0"));
+        let bytes = record_bytes(*b"TES4", 0, 0, &subrecords);
+
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::header_only())
+            .unwrap();
+
+        assert_eq!(
+            vec!["Starfield.esm", "Constellation.esm", "Blank.esm"],
+            plugin.masters().unwrap()
+        );
+    }
+
+    #[test]
+    fn plugin_slot_cost_should_be_the_plugin_scale_if_the_game_supports_it() {
+        let full = starfield_plugin("Full.esm", 0, &[]);
+        let medium = starfield_plugin("Medium.esm", 0x400, &[]);
+        let small = starfield_plugin("Small.esm", 0x100, &[]);
+
+        assert_eq!(PluginScale::Full, GameId::Starfield.plugin_slot_cost(&full));
+        assert_eq!(
+            PluginScale::Medium,
+            GameId::Starfield.plugin_slot_cost(&medium)
+        );
+        assert_eq!(
+            PluginScale::Small,
+            GameId::Starfield.plugin_slot_cost(&small)
+        );
+
+        assert_eq!(
+            PluginScale::Full,
+            GameId::SkyrimSE.plugin_slot_cost(&medium)
+        );
+        assert_eq!(
+            PluginScale::Small,
+            GameId::SkyrimSE.plugin_slot_cost(&small)
+        );
+        assert_eq!(PluginScale::Full, GameId::Skyrim.plugin_slot_cost(&small));
+    }
+
+    #[test]
+    fn plugin_slot_cost_should_be_small_for_a_light_skyrim_se_plugin() {
+        let mut flagged = Plugin::new(GameId::SkyrimSE, Path::new("Flagged.esp"));
+        flagged
+            .parse_reader(
+                Cursor::new(plugin_bytes_with_flags(0x200, &["Skyrim.esm"], &[])),
+                ParseOptions::header_only(),
+            )
+            .unwrap();
+        let mut esl = Plugin::new(GameId::SkyrimSE, Path::new("Light.esl"));
+        esl.parse_reader(
+            Cursor::new(plugin_bytes(&["Skyrim.esm"], &[])),
+            ParseOptions::header_only(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            PluginScale::Small,
+            GameId::SkyrimSE.plugin_slot_cost(&flagged)
+        );
+        assert_eq!(PluginScale::Small, GameId::SkyrimSE.plugin_slot_cost(&esl));
+    }
+
+    #[test]
+    fn plugin_slot_cost_should_be_medium_for_a_medium_starfield_plugin() {
+        let plugin = starfield_plugin("Medium.esp", 0x400, &["Starfield.esm"]);
+
+        assert!(plugin.is_medium_plugin());
+        assert_eq!(
+            PluginScale::Medium,
+            GameId::Starfield.plugin_slot_cost(&plugin)
+        );
+    }
+
+    #[test]
+    fn plugin_slot_cost_should_be_full_for_an_unparsed_plugin() {
+        let plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        assert_eq!(
+            PluginScale::Full,
+            GameId::SkyrimSE.plugin_slot_cost(&plugin)
+        );
+
+        let plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esl"));
+        assert_eq!(
+            PluginScale::Small,
+            GameId::SkyrimSE.plugin_slot_cost(&plugin)
+        );
+    }
+
+    #[test]
+    fn starfield_role_should_be_none_for_other_games() {
+        let plugin = plugin_with_masters(&[]);
+
+        assert!(plugin.starfield_role().is_none());
+    }
+
+    #[test]
+    fn starfield_role_should_resolve_the_effective_scale_of_a_plugin() {
+        let masters = &["Starfield.esm"];
+
+        assert_eq!(
+            Some(StarfieldRole::Full { blueprint: false }),
+            starfield_plugin("Test.esm", 0x1, masters).starfield_role()
+        );
+        assert_eq!(
+            Some(StarfieldRole::Small { blueprint: false }),
+            starfield_plugin("Test.esl", 0, masters).starfield_role()
+        );
+        assert_eq!(
+            Some(StarfieldRole::Medium { blueprint: false }),
+            starfield_plugin("Test.esm", 0x400, masters).starfield_role()
+        );
+        // The light flag takes precedence over the medium flag.
+        assert_eq!(
+            Some(StarfieldRole::Small { blueprint: false }),
+            starfield_plugin("Test.esm", 0x500, masters).starfield_role()
+        );
+    }
+
+    #[test]
+    fn starfield_role_should_only_be_update_if_the_update_flag_is_not_overridden() {
+        assert_eq!(
+            Some(StarfieldRole::Update { blueprint: false }),
+            starfield_plugin("Test.esm", 0x200, &["Starfield.esm"]).starfield_role()
+        );
+        assert_eq!(
+            Some(StarfieldRole::Full { blueprint: false }),
+            starfield_plugin("Test.esm", 0x200, &[]).starfield_role()
+        );
+        assert_eq!(
+            Some(StarfieldRole::Medium { blueprint: false }),
+            starfield_plugin("Test.esm", 0x600, &["Starfield.esm"]).starfield_role()
+        );
+        // The update flag stops the .esl extension from making a plugin light.
+        assert_eq!(
+            Some(StarfieldRole::Update { blueprint: false }),
+            starfield_plugin("Test.esl", 0x200, &["Starfield.esm"]).starfield_role()
+        );
+    }
+
+    #[test]
+    fn starfield_role_should_record_if_the_blueprint_flag_is_set() {
+        assert_eq!(
+            Some(StarfieldRole::Full { blueprint: true }),
+            starfield_plugin("Test.esm", 0x800, &[]).starfield_role()
+        );
+        assert_eq!(
+            Some(StarfieldRole::Update { blueprint: true }),
+            starfield_plugin("Test.esm", 0xA00, &["Starfield.esm"]).starfield_role()
+        );
+    }
+
+    #[test]
+    fn blueprint_role_should_be_none_if_the_blueprint_flag_is_not_set() {
+        assert!(plugin_with_masters(&[]).blueprint_role().is_none());
+        assert!(starfield_plugin("Test.esm", 0x1, &[])
+            .blueprint_role()
+            .is_none());
+    }
+
+    #[test]
+    fn blueprint_role_should_combine_the_blueprint_flag_with_the_other_flags() {
+        let masters = &["Starfield.esm"];
+        let info = |scale, is_update, loads_after_other_plugins| {
+            Some(BlueprintInfo {
+                scale,
+                is_update,
+                loads_after_other_plugins,
+            })
+        };
+
+        assert_eq!(
+            info(PluginScale::Full, false, true),
+            starfield_plugin("Test.esm", 0x800, masters).blueprint_role()
+        );
+        assert_eq!(
+            info(PluginScale::Full, false, false),
+            starfield_plugin("Test.esp", 0x800, masters).blueprint_role()
+        );
+        assert_eq!(
+            info(PluginScale::Small, false, true),
+            starfield_plugin("Test.esm", 0x900, masters).blueprint_role()
+        );
+        assert_eq!(
+            info(PluginScale::Small, false, true),
+            starfield_plugin("Test.esl", 0x800, masters).blueprint_role()
+        );
+        assert_eq!(
+            info(PluginScale::Medium, false, false),
+            starfield_plugin("Test.esp", 0xC00, masters).blueprint_role()
+        );
+        assert_eq!(
+            info(PluginScale::Small, false, false),
+            starfield_plugin("Test.esp", 0xD00, masters).blueprint_role()
+        );
+        assert_eq!(
+            info(PluginScale::Full, true, true),
+            starfield_plugin("Test.esm", 0xA00, masters).blueprint_role()
+        );
+        assert_eq!(
+            info(PluginScale::Medium, false, true),
+            starfield_plugin("Test.esm", 0xE00, masters).blueprint_role()
+        );
+    }
+
+    #[test]
+    fn description_lossy_should_decode_the_description_like_description() {
+        let bytes = plugin_bytes_with_description(0, b"caf\xe9\0junk", &[], &[]);
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::header_only())
+            .unwrap();
+
+        assert_eq!("caf\u{e9}", plugin.description().unwrap().unwrap());
+        assert_eq!("caf\u{e9}", plugin.description_lossy().unwrap());
+    }
+
+    #[test]
+    fn description_lossy_should_be_none_if_there_is_no_description() {
+        let plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+
+        assert!(plugin.description_lossy().is_none());
+    }
+
+    fn new_records_plugin(game_id: GameId, filename: &str, object_indices: &[u32]) -> Plugin {
+        let records: Vec<u8> = object_indices
+            .iter()
+            .flat_map(|i| record_bytes(*b"GLOB", 0, 0x0100_0000 | i, &[]))
+            .collect();
+        let bytes = plugin_bytes(&["Skyrim.esm"], &group_bytes(*b"GLOB", &records));
+
+        let mut plugin = Plugin::new(game_id, Path::new(filename));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::whole_plugin())
+            .unwrap();
+        plugin
+    }
+
+    #[test]
+    fn can_merge_should_report_object_indices_used_by_new_records_in_both_plugins() {
+        let a = new_records_plugin(GameId::SkyrimSE, "A.esp", &[0x800, 0x801, 0x802]);
+        let b = new_records_plugin(GameId::SkyrimSE, "B.esp", &[0x802, 0x803, 0x800]);
+
+        let report = can_merge(&a, &b).unwrap();
+
+        assert_eq!(vec![0x800, 0x802], report.colliding_object_indices);
+        assert_eq!(6, report.new_record_count);
+        assert!(report.fits_light_plugin);
+        assert!(!report.fits_medium_plugin);
+    }
+
+    #[test]
+    fn can_merge_should_ignore_override_records() {
+        let a = multiple_masters_plugin();
+        let b = multiple_masters_plugin();
+
+        let report = can_merge(&a, &b).unwrap();
+
+        assert_eq!(vec![4], report.colliding_object_indices);
+        assert_eq!(2, report.new_record_count);
+    }
+
+    #[test]
+    fn can_merge_should_check_if_new_records_fit_in_the_light_plugin_range() {
+        let a_indices: Vec<u32> = (0x800..0xC00).collect();
+        let b_indices: Vec<u32> = (0xC00..=0xFFF).collect();
+        let a = new_records_plugin(GameId::SkyrimSE, "A.esp", &a_indices);
+        let b = new_records_plugin(GameId::SkyrimSE, "B.esp", &b_indices);
+
+        assert!(can_merge(&a, &b).unwrap().fits_light_plugin);
+
+        let b_indices: Vec<u32> = (0xC00..=0x1000).collect();
+        let b = new_records_plugin(GameId::SkyrimSE, "B.esp", &b_indices);
+
+        assert!(!can_merge(&a, &b).unwrap().fits_light_plugin);
+    }
+
+    #[test]
+    fn can_merge_should_error_if_either_plugin_has_unresolved_record_ids() {
+        let a = new_records_plugin(GameId::SkyrimSE, "A.esp", &[0x800]);
+        let mut b = Plugin::new(GameId::SkyrimSE, Path::new("B.esp"));
+        b.data.record_ids = RecordIds::FormIds(typed_form_ids(&[0x800]));
+
+        assert!(can_merge(&a, &b).is_err());
+        assert!(can_merge(&b, &a).is_err());
+    }
+
+    #[test]
+    fn contains_form_id_should_resolve_the_form_id_relative_to_the_plugin() {
+        let plugin = multiple_masters_plugin();
+
+        assert!(plugin.contains_form_id(0x0000_0001).unwrap());
+        assert!(plugin.contains_form_id(0x0100_0003).unwrap());
+        assert!(plugin.contains_form_id(0x0200_0004).unwrap());
+
+        assert!(!plugin.contains_form_id(0x0000_0002).unwrap());
+        assert!(!plugin.contains_form_id(0x0200_0001).unwrap());
+    }
+
+    #[test]
+    fn contains_form_id_should_treat_out_of_range_mod_indices_as_the_plugin_itself() {
+        let plugin = multiple_masters_plugin();
+
+        assert!(plugin.contains_form_id(0x0500_0004).unwrap());
+    }
+
+    #[test]
+    fn contains_form_id_should_error_if_record_ids_are_unresolved() {
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes(
+                    &[],
+                    &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x800, &[])),
+                )),
+                ParseOptions::whole_plugin(),
+            )
+            .unwrap();
+
+        assert!(plugin.contains_form_id(0x800).is_err());
+
+        plugin.resolve_record_ids(&[]).unwrap();
+
+        assert!(plugin.contains_form_id(0x800).unwrap());
+    }
+
+    #[test]
+    fn contains_form_id_should_be_false_if_the_plugin_has_no_record_ids() {
+        let plugin = plugin_with_masters(&["Skyrim.esm"]);
+
+        assert!(!plugin.contains_form_id(0x800).unwrap());
+    }
+
+    #[test]
+    fn parse_reader_should_fail_if_there_is_junk_before_the_header_and_scanning_is_disabled() {
+        let mut bytes = vec![0xFF; 10];
+        bytes.extend(plugin_bytes(&["Skyrim.esm"], &[]));
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        assert!(plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::whole_plugin())
+            .is_err());
+    }
+
+    #[test]
+    fn parse_reader_should_error_if_record_data_is_larger_than_max_alloc() {
+        let record = record_bytes(
+            *b"GLOB",
+            0,
+            0x0100_0800,
+            &subrecord_bytes(*b"DATA", &[0; 100]),
+        );
+        let bytes = plugin_bytes(&["Skyrim.esm"], &group_bytes(*b"GLOB", &record));
+
+        let options = ParseOptions::builder()
+            .retain_records(true)
+            .max_alloc(100)
+            .build();
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        match plugin.parse_reader(Cursor::new(bytes.clone()), options) {
+            Err(Error::ParsingError(_, ParsingErrorKind::OversizedField(size))) => {
+                assert_eq!(106, size);
+            }
+            r => panic!("Expected an oversized field error, got {r:?}"),
+        }
+
+        let options = ParseOptions::builder()
+            .retain_records(true)
+            .max_alloc(106)
+            .build();
+        assert!(plugin.parse_reader(Cursor::new(bytes), options).is_ok());
+    }
+
+    #[test]
+    fn parse_reader_should_error_if_more_records_than_max_records_scanned_are_read() {
+        let records = [
+            record_bytes(*b"GLOB", 0, 0x0100_0800, &[]),
+            record_bytes(*b"GLOB", 0, 0x0100_0801, &[]),
+        ]
+        .concat();
+        let mut groups = group_bytes(*b"GLOB", &records);
+        groups.extend(group_bytes(
+            *b"WEAP",
+            &record_bytes(*b"WEAP", 0, 0x0100_0802, &[]),
+        ));
+        let bytes = plugin_bytes(&["Skyrim.esm"], &groups);
+
+        for retain_records in [false, true] {
+            let options = ParseOptions::builder()
+                .retain_records(retain_records)
+                .max_records_scanned(2)
+                .build();
+            let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+            match plugin.parse_reader(Cursor::new(&bytes), options) {
+                Err(Error::ParsingError(_, ParsingErrorKind::BudgetExceeded(limit))) => {
+                    assert_eq!(2, limit);
+                }
+                r => panic!("Expected a budget exceeded error, got {r:?}"),
+            }
+
+            let options = ParseOptions::builder()
+                .retain_records(retain_records)
+                .max_records_scanned(3)
+                .build();
+            assert!(plugin.parse_reader(Cursor::new(&bytes), options).is_ok());
+        }
+    }
+
+    #[test]
+    fn parse_reader_should_apply_max_records_scanned_to_morrowind_plugins() {
+        let mut bytes = morrowind_record_bytes(*b"TES3", &[(*b"HEDR", &[0; 300])]);
+        bytes.extend(morrowind_record_bytes(*b"GLOB", &[(*b"NAME", b"A\0")]));
+        bytes.extend(morrowind_record_bytes(*b"GLOB", &[(*b"NAME", b"B\0")]));
+
+        let options = ParseOptions::builder().max_records_scanned(1).build();
+        let mut plugin = Plugin::new(GameId::Morrowind, Path::new("Test.esp"));
+        assert!(matches!(
+            plugin.parse_reader(Cursor::new(&bytes), options),
+            Err(Error::ParsingError(_, ParsingErrorKind::BudgetExceeded(1)))
+        ));
+
+        let options = ParseOptions::builder().max_records_scanned(2).build();
+        assert!(plugin.parse_reader(Cursor::new(&bytes), options).is_ok());
+    }
+
+    #[test]
+    fn record_type_sequence_should_give_record_types_in_file_order() {
+        let groups = [
+            group_bytes(
+                *b"WEAP",
+                &[
+                    record_bytes(*b"WEAP", 0, 0x0100_0802, &[]),
+                    record_bytes(*b"WEAP", 0, 0x0100_0801, &[]),
+                ]
+                .concat(),
+            ),
+            group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0100_0800, &[])),
+        ]
+        .concat();
+        let bytes = plugin_bytes(&["Skyrim.esm"], &groups);
+        let expected = vec![*b"WEAP", *b"WEAP", *b"GLOB"];
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(&bytes), ParseOptions::whole_plugin())
+            .unwrap();
+        assert!(plugin.record_type_sequence().is_err());
+
+        let options = ParseOptions::builder().collect_record_types(true).build();
+        plugin.parse_reader(Cursor::new(&bytes), options).unwrap();
+        assert_eq!(expected, plugin.record_type_sequence().unwrap());
+
+        plugin
+            .parse_reader(
+                Cursor::new(&bytes),
+                ParseOptions::whole_plugin_with_records(),
+            )
+            .unwrap();
+        assert_eq!(expected, plugin.record_type_sequence().unwrap());
+    }
+
+    #[test]
+    fn record_type_sequence_should_include_morrowind_records_without_ids() {
+        let mut bytes = morrowind_record_bytes(*b"TES3", &[(*b"HEDR", &[0; 300])]);
+        bytes.extend(morrowind_record_bytes(*b"GLOB", &[(*b"NAME", b"A\0")]));
+        bytes.extend(morrowind_record_bytes(*b"TES3", &[(*b"HEDR", &[0; 300])]));
+
+        let options = ParseOptions::builder().collect_record_types(true).build();
+        let mut plugin = Plugin::new(GameId::Morrowind, Path::new("Test.esp"));
+        plugin.parse_reader(Cursor::new(&bytes), options).unwrap();
+
+        assert_eq!(
+            vec![*b"GLOB", *b"TES3"],
+            plugin.record_type_sequence().unwrap()
+        );
+    }
+
+    #[test]
+    fn form_ids_in_file_order_should_give_raw_form_ids_before_sorting() {
+        let records = [
+            record_bytes(*b"GLOB", 0, 0x0100_0802, &[]),
+            record_bytes(*b"GLOB", 0, 0x0000_0001, &[]),
+            record_bytes(*b"GLOB", 0, 0x0100_0800, &[]),
+            record_bytes(*b"GLOB", 0, 0x0100_0802, &[]),
+        ]
+        .concat();
+        let bytes = plugin_bytes(&["Skyrim.esm"], &group_bytes(*b"GLOB", &records));
+        let expected = vec![0x0100_0802, 0x0000_0001, 0x0100_0800, 0x0100_0802];
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(&bytes), ParseOptions::whole_plugin())
+            .unwrap();
+        assert!(plugin.form_ids_in_file_order().is_err());
+
+        let options = ParseOptions::builder().preserve_form_id_order(true).build();
+        plugin.parse_reader(Cursor::new(&bytes), options).unwrap();
+        assert_eq!(expected, plugin.form_ids_in_file_order().unwrap());
+
+        plugin
+            .parse_reader(
+                Cursor::new(&bytes),
+                ParseOptions::whole_plugin_with_records(),
+            )
+            .unwrap();
+        assert_eq!(expected, plugin.form_ids_in_file_order().unwrap());
+
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(&bytes), ParseOptions::whole_plugin())
+            .unwrap();
+        assert_eq!(expected, plugin.form_ids_in_file_order().unwrap());
+    }
+
+    #[test]
+    fn parse_reader_should_error_with_unexpected_eof_if_the_header_is_truncated() {
+        let bytes = plugin_bytes(&["Skyrim.esm"], &[]);
+        let truncated = &bytes[..30];
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        let result = plugin.parse_reader(Cursor::new(truncated), ParseOptions::header_only());
+
+        let expected = bytes.len() - 24;
+        assert!(matches!(
+            result,
+            Err(Error::ParsingError(
+                _,
+                ParsingErrorKind::UnexpectedEof { expected: e, offset: 6 }
+            )) if e == expected
+        ));
+    }
+
+    #[test]
+    fn parse_reader_should_error_with_unexpected_eof_if_the_last_record_is_truncated() {
+        let mut bytes = morrowind_record_bytes(*b"TES3", &[(*b"HEDR", &[0; 300])]);
+        bytes.extend(morrowind_record_bytes(*b"GLOB", &[(*b"NAME", b"A\0")]));
+        let record = morrowind_record_bytes(*b"GLOB", &[(*b"NAME", b"B\0")]);
+        bytes.extend(&record[..20]);
+
+        let mut plugin = Plugin::new(GameId::Morrowind, Path::new("Test.esp"));
+        for options in [ParseOptions::whole_plugin(), ParseOptions::strict()] {
+            assert!(matches!(
+                plugin.parse_reader(Cursor::new(&bytes), options),
+                Err(Error::ParsingError(
+                    _,
+                    ParsingErrorKind::UnexpectedEof {
+                        expected: 10,
+                        offset: 4
+                    }
+                ))
+            ));
+        }
+
+        bytes.extend(&record[20..]);
+        assert!(plugin
+            .parse_reader(Cursor::new(&bytes), ParseOptions::whole_plugin())
+            .is_ok());
+    }
+
+    #[test]
+    fn plugin_parser_should_be_reusable_for_multiple_plugins() {
+        let mut parser = PluginParser::new();
+
+        let mut a = Plugin::new(GameId::SkyrimSE, Path::new("A.esp"));
+        let bytes = plugin_bytes(
+            &["Skyrim.esm"],
+            &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0100_0800, &[])),
+        );
+        parser
+            .parse_into(&mut a, Cursor::new(bytes), ParseOptions::whole_plugin())
+            .unwrap();
+
+        let mut b = Plugin::new(GameId::SkyrimSE, Path::new("B.esp"));
+        let bytes = plugin_bytes(
+            &[],
+            &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0000_0801, &[])),
+        );
+        parser
+            .parse_into(&mut b, Cursor::new(bytes), ParseOptions::whole_plugin())
+            .unwrap();
+
+        assert!(a.contains_form_id(0x0100_0800).unwrap());
+        assert!(!a.contains_form_id(0x0100_0801).unwrap());
+        assert!(b.contains_form_id(0x0000_0801).unwrap());
+        assert!(!b.contains_form_id(0x0000_0800).unwrap());
+        assert_eq!(1, b.resolved_record_ids().unwrap().len());
+    }
+
+    #[test]
+    fn parse_reader_should_parse_the_whole_plugin_with_a_custom_buffer_capacity() {
+        let bytes = plugin_bytes(
+            &["Skyrim.esm"],
+            &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0100_0800, &[])),
+        );
+
+        let options = ParseOptions::builder().buffer_capacity(16).build();
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin.parse_reader(Cursor::new(bytes), options).unwrap();
+
+        assert_eq!(vec!["Skyrim.esm"], plugin.masters().unwrap());
+        assert!(plugin.contains_form_id(0x0100_0800).unwrap());
+    }
+
+    #[test]
+    fn parse_reader_reporting_should_report_progress_after_each_top_level_group() {
+        let glob_group = group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0100_0800, &[]));
+        let weap_group = group_bytes(*b"WEAP", &record_bytes(*b"WEAP", 0, 0x0100_0801, &[]));
+        let mut groups = glob_group.clone();
+        groups.extend(&weap_group);
+        let bytes = plugin_bytes(&["Skyrim.esm"], &groups);
+        let total = u64::try_from(bytes.len()).unwrap();
+        let first_group_end = total - u64::try_from(weap_group.len()).unwrap();
+
+        for options in [
+            ParseOptions::whole_plugin(),
+            ParseOptions::builder().retain_records(true).build(),
+            #[cfg(feature = "xxhash-rust")]
+            ParseOptions::builder().compute_hash(true).build(),
+        ] {
+            let mut reported = Vec::new();
+            let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+            plugin
+                .parse_reader_reporting(Cursor::new(&bytes), options, |b| reported.push(b))
+                .unwrap();
+
+            assert_eq!(vec![first_group_end, total, total], reported);
+        }
+    }
+
+    #[test]
+    fn parse_reader_reporting_should_report_progress_once_for_a_header_only_parse() {
+        let bytes = plugin_bytes(&["Skyrim.esm"], &[]);
+        let total = u64::try_from(bytes.len()).unwrap();
+
+        let mut reported = Vec::new();
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader_reporting(Cursor::new(&bytes), ParseOptions::header_only(), |b| {
+                reported.push(b);
+            })
+            .unwrap();
+
+        assert_eq!(vec![total], reported);
+    }
+
+    #[test]
+    fn parse_buffered_reader_should_parse_an_already_buffered_reader() {
+        let bytes = plugin_bytes(
+            &["Skyrim.esm"],
+            &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0100_0800, &[])),
+        );
+
+        for options in [
+            ParseOptions::whole_plugin(),
+            ParseOptions::whole_plugin_with_records(),
+            #[cfg(feature = "xxhash-rust")]
+            ParseOptions::builder().compute_hash(true).build(),
+        ] {
+            let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+            plugin
+                .parse_buffered_reader(Cursor::new(&bytes), options)
+                .unwrap();
+
+            assert_eq!(vec!["Skyrim.esm"], plugin.masters().unwrap());
+            assert!(plugin.contains_form_id(0x0100_0800).unwrap());
+        }
+    }
+
+    #[test]
+    fn parse_reader_should_skip_junk_before_the_header_if_scanning_is_enabled() {
+        let mut bytes = vec![0xFF; 10];
+        bytes.extend(plugin_bytes(
+            &["Skyrim.esm"],
+            &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0100_0800, &[])),
+        ));
+
+        let options = ParseOptions::builder().scan_for_header(true).build();
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin.parse_reader(Cursor::new(bytes), options).unwrap();
+
+        assert_eq!(vec!["Skyrim.esm"], plugin.masters().unwrap());
+        assert!(plugin.contains_form_id(0x0100_0800).unwrap());
+    }
+
+    #[test]
+    fn parse_reader_should_not_scan_for_the_header_past_the_scan_limit() {
+        let mut bytes = vec![0xFF; MAX_HEADER_SCAN_LENGTH + 1];
+        bytes.extend(plugin_bytes(&[], &[]));
+
+        let options = ParseOptions::builder().scan_for_header(true).build();
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        assert!(plugin.parse_reader(Cursor::new(bytes), options).is_err());
+
+        let mut bytes = vec![0xFF; MAX_HEADER_SCAN_LENGTH];
+        bytes.extend(plugin_bytes(&[], &[]));
+
+        assert!(plugin.parse_reader(Cursor::new(bytes), options).is_ok());
+    }
+
+    fn duplicates_plugin(game_id: GameId) -> Plugin {
+        let mut records = record_bytes(*b"GLOB", 0, 0x0000_0001, &[]);
+        records.extend(record_bytes(*b"GLOB", 0, 0x0100_0800, &[]));
+        records.extend(record_bytes(*b"GLOB", 0, 0x0000_0001, &[]));
+        records.extend(record_bytes(*b"GLOB", 0, 0x0100_0801, &[]));
+        records.extend(record_bytes(*b"GLOB", 0, 0x0500_0800, &[]));
+        let bytes = plugin_bytes(&["Skyrim.esm"], &group_bytes(*b"GLOB", &records));
+
+        let mut plugin = Plugin::new(game_id, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::whole_plugin())
+            .unwrap();
+        plugin
+    }
+
+    #[test]
+    fn duplicate_form_ids_should_find_records_that_resolve_to_the_same_form_id() {
+        let plugin = duplicates_plugin(GameId::SkyrimSE);
+
+        assert_eq!(
+            vec![0x0000_0001, 0x0100_0800],
+            plugin.duplicate_form_ids().unwrap()
+        );
+    }
+
+    #[test]
+    fn duplicate_form_ids_should_compare_unresolved_form_ids_as_they_appear() {
+        let plugin = duplicates_plugin(GameId::Starfield);
+
+        assert_eq!(vec![0x0000_0001], plugin.duplicate_form_ids().unwrap());
+    }
+
+    #[test]
+    fn duplicate_form_ids_should_be_empty_if_there_are_no_duplicates() {
+        let plugin = multiple_masters_plugin();
+
+        assert!(plugin.duplicate_form_ids().unwrap().is_empty());
+    }
+
+    #[test]
+    fn drop_record_ids_should_keep_the_header_but_forget_all_record_ids() {
+        let mut plugin = multiple_masters_plugin();
+        assert_eq!(3, plugin.count_override_records().unwrap());
+
+        plugin.drop_record_ids();
+
+        assert_eq!(RecordIds::None, plugin.data.record_ids);
+        assert_eq!(vec!["Skyrim.esm", "Update.esm"], plugin.masters().unwrap());
+        assert_eq!(0, plugin.count_override_records().unwrap());
+        assert!(!plugin.contains_form_id(0x0000_0001).unwrap());
+    }
+
+    #[test]
+    fn drop_record_ids_should_forget_retained_records() {
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(cells_plugin_bytes()),
+                ParseOptions::whole_plugin_with_records(),
+            )
+            .unwrap();
+        assert!(!plugin.exterior_cells().unwrap().is_empty());
+
+        plugin.drop_record_ids();
+
+        assert!(matches!(
+            plugin.exterior_cells(),
+            Err(Error::RecordsNotRetained(_))
+        ));
+    }
+
+    #[test]
+    fn header_subrecord_count_should_count_all_subrecords_in_the_header() {
+        // HEDR, SNAM, then a MAST and DATA for each master.
+        let plugin = plugin_with_masters(&["Skyrim.esm", "Update.esm"]);
+
+        assert_eq!(6, plugin.header_subrecord_count());
+    }
+
+    #[test]
+    fn header_subrecord_count_should_be_zero_for_an_unparsed_plugin() {
+        let plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+
+        assert_eq!(0, plugin.header_subrecord_count());
+    }
+
+    #[test]
+    fn validate_header_should_return_no_warnings_for_a_consistent_header() {
+        let plugin = plugin_with_masters(&["Skyrim.esm", "Update.esm"]);
+
+        assert!(plugin.validate_header().unwrap().is_empty());
+    }
+
+    #[test]
+    fn validate_header_should_warn_about_missing_hedr_and_snam_subrecords() {
+        let plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+
+        assert_eq!(
+            vec![
+                HeaderWarning::MissingHeaderData,
+                HeaderWarning::MissingDescription
+            ],
+            plugin.validate_header().unwrap()
+        );
+
+        let plugin = Plugin::new(GameId::Morrowind, Path::new("Test.esp"));
+
+        assert_eq!(
+            vec![HeaderWarning::MissingHeaderData],
+            plugin.validate_header().unwrap()
+        );
+    }
+
+    #[test]
+    fn validate_header_should_warn_about_inconsistent_subrecords() {
+        let mut subrecords = subrecord_bytes(*b"HEDR", &[0; 8]);
+        subrecords.extend(subrecord_bytes(*b"CNAM", b"A\0"));
+        subrecords.extend(subrecord_bytes(*b"CNAM", b"B\0"));
+        subrecords.extend(subrecord_bytes(*b"MAST", b"Skyrim.esm\0"));
+        subrecords.extend(subrecord_bytes(*b"MAST", b"Update.esm\0"));
+        subrecords.extend(subrecord_bytes(*b"DATA", &[0; 8]));
+        subrecords.extend(subrecord_bytes(*b"DATA", &[0; 8]));
+        let bytes = record_bytes(*b"TES4", 0, 0, &subrecords);
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::header_only())
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                HeaderWarning::HeaderDataTooShort(8),
+                HeaderWarning::MissingDescription,
+                HeaderWarning::DuplicateSubrecord(*b"CNAM"),
+                HeaderWarning::MasterWithoutData("Skyrim.esm".to_owned()),
+                HeaderWarning::DataWithoutMaster,
+            ],
+            plugin.validate_header().unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "xxhash-rust")]
+    fn content_hash_should_be_none_if_not_computed() {
+        let plugin = plugin_with_masters(&["Skyrim.esm"]);
+
+        assert!(plugin.content_hash().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "xxhash-rust")]
+    fn content_hash_should_change_if_content_after_the_header_changes() {
+        let options = ParseOptions::builder()
+            .header_only(true)
+            .compute_hash(true)
+            .build();
+        let hash = |groups: &[u8]| {
+            let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+            plugin
+                .parse_reader(Cursor::new(plugin_bytes(&[], groups)), options)
+                .unwrap();
+            plugin.content_hash()
+        };
+
+        let group1 = group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x800, &[]));
+        let group2 = group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x801, &[]));
+
+        assert!(hash(&group1).is_some());
+        assert_eq!(hash(&group1), hash(&group1));
+        assert_ne!(hash(&group1), hash(&group2));
+    }
+
+    #[test]
+    #[cfg(feature = "xxhash-rust")]
+    fn content_hash_should_be_the_hash_of_the_whole_file() {
+        let bytes = cells_plugin_bytes();
+
+        for header_only in [true, false] {
+            for capacity in [16, 8192] {
+                let options = ParseOptions::builder()
+                    .header_only(header_only)
+                    .buffer_capacity(capacity)
+                    .compute_hash(true)
+                    .build();
+
+                let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+                plugin.parse_reader(Cursor::new(&bytes), options).unwrap();
+
+                assert_eq!(Some(xxh3_64(&bytes)), plugin.content_hash());
+            }
+        }
+    }
+
+    #[test]
+    fn header_counts_should_read_intv_and_incc_subrecords() {
+        let mut extra_subrecords = subrecord_bytes(*b"INTV", &5u32.to_le_bytes());
+        extra_subrecords.extend(subrecord_bytes(*b"INCC", &12u32.to_le_bytes()));
+        let bytes = plugin_bytes_with_header_subrecords(0, b"\0", &[], &extra_subrecords, &[]);
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::header_only())
+            .unwrap();
+
+        assert_eq!(
+            HeaderCounts {
+                tagifiable_string_count: Some(5),
+                interior_cell_count: Some(12),
+            },
+            plugin.header_counts()
+        );
+    }
+
+    #[test]
+    fn header_subrecord_should_return_the_data_of_the_first_matching_subrecord() {
+        let mut extra_subrecords = subrecord_bytes(*b"TNAM", &[1, 2, 3]);
+        extra_subrecords.extend(subrecord_bytes(*b"TNAM", &[4]));
+        let bytes = plugin_bytes_with_header_subrecords(0, b"\0", &[], &extra_subrecords, &[]);
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::header_only())
+            .unwrap();
+
+        assert_eq!(Some([1, 2, 3].as_slice()), plugin.header_subrecord(b"TNAM"));
+        assert_eq!(None, plugin.header_subrecord(b"MMSB"));
+    }
+
+    #[test]
+    fn header_counts_should_be_none_for_missing_or_too_small_subrecords() {
+        let extra_subrecords = subrecord_bytes(*b"INTV", &[1, 2]);
+        let bytes = plugin_bytes_with_header_subrecords(0, b"\0", &[], &extra_subrecords, &[]);
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::header_only())
+            .unwrap();
+
+        assert_eq!(HeaderCounts::default(), plugin.header_counts());
+    }
+
+    #[test]
+    fn parse_file_resolved_should_parse_and_resolve_a_starfield_plugin() {
+        let bytes = plugin_bytes(
+            &["Starfield.esm"],
+            &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0000_0001, &[])),
+        );
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("Test.esp");
+        std::fs::write(&path, bytes).unwrap();
+
+        let master = Plugin::new(GameId::Starfield, Path::new("Starfield.esm"));
+        let metadata = plugins_metadata(&[&master]).unwrap();
+
+        let mut plugin = Plugin::new(GameId::Starfield, &path);
+        plugin
+            .parse_file_resolved(ParseOptions::whole_plugin(), &metadata)
+            .unwrap();
+
+        assert_eq!(1, plugin.count_override_records().unwrap());
+    }
+
+    #[test]
+    fn parse_file_resolved_should_error_if_metadata_for_a_master_is_missing() {
+        let bytes = plugin_bytes(
+            &["Starfield.esm"],
+            &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0000_0001, &[])),
+        );
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("Test.esp");
+        std::fs::write(&path, bytes).unwrap();
+
+        let mut plugin = Plugin::new(GameId::Starfield, &path);
+        match plugin.parse_file_resolved(ParseOptions::whole_plugin(), &[]) {
+            Err(Error::PluginMetadataNotFound(name)) => assert_eq!("Starfield.esm", name),
+            r => panic!("Expected plugin metadata not found error, got {r:?}"),
+        }
+    }
+
+    #[test]
+    fn resolve_record_ids_partial_should_list_masters_with_missing_metadata() {
+        let bytes = plugin_bytes(
+            &["Starfield.esm", "Blank.esm"],
+            &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0000_0001, &[])),
+        );
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::whole_plugin())
+            .unwrap();
+
+        let master = starfield_plugin("Starfield.esm", 0, &[]);
+        let metadata = plugins_metadata(&[&master]).unwrap();
+
+        assert_eq!(
+            ResolutionStatus::MissingMasters(vec!["Blank.esm".into()]),
+            plugin.resolve_record_ids_partial(&metadata).unwrap()
+        );
+        assert!(plugin.resolved_record_ids().is_err());
+
+        let other_master = starfield_plugin("Blank.esm", 0, &[]);
+        let metadata = plugins_metadata(&[&master, &other_master]).unwrap();
+
+        assert_eq!(
+            ResolutionStatus::Complete,
+            plugin.resolve_record_ids_partial(&metadata).unwrap()
+        );
+        assert_eq!(1, plugin.resolved_record_ids().unwrap().len());
+    }
+
+    #[test]
+    fn resolve_all_should_resolve_plugins_against_each_other() {
+        let mut master = Plugin::new(GameId::Starfield, Path::new("Master.esm"));
+        master
+            .parse_reader(
+                Cursor::new(plugin_bytes_with_flags(
+                    0x100,
+                    &[],
+                    &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0000_0800, &[])),
+                )),
+                ParseOptions::whole_plugin(),
+            )
+            .unwrap();
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes(
+                    &["Master.esm"],
+                    &group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0xFE00_0800, &[])),
+                )),
+                ParseOptions::whole_plugin(),
+            )
+            .unwrap();
+
+        let mut plugins = vec![master, plugin];
+        resolve_all(&mut plugins).unwrap();
+
+        assert_eq!(0, plugins[0].count_override_records().unwrap());
+        assert_eq!(1, plugins[1].count_override_records().unwrap());
+    }
+
+    #[test]
+    fn object_index_histogram_should_count_new_records_in_each_bucket() {
+        let plugin =
+            new_records_plugin(GameId::SkyrimSE, "Test.esp", &[0x800, 0x80F, 0x810, 0x1000]);
+
+        let expected: BTreeMap<u32, usize> = [(0x800, 2), (0x810, 1), (0x1000, 1)].into();
+        assert_eq!(expected, plugin.object_index_histogram(0x10).unwrap());
+
+        let expected: BTreeMap<u32, usize> =
+            [(0x800, 1), (0x80F, 1), (0x810, 1), (0x1000, 1)].into();
+        assert_eq!(expected, plugin.object_index_histogram(0).unwrap());
+    }
+
+    #[test]
+    fn overridden_record_types_should_only_include_types_with_overrides() {
+        let mut groups = group_bytes(*b"WEAP", &record_bytes(*b"WEAP", 0, 0x0100_0800, &[]));
+        groups.extend(group_bytes(
+            *b"NPC_",
+            &record_bytes(*b"NPC_", 0, 0x0000_0007, &[]),
+        ));
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes(&["Skyrim.esm"], &groups)),
+                ParseOptions::whole_plugin(),
+            )
+            .unwrap();
+
+        let expected: HashSet<[u8; 4]> = [*b"NPC_"].into();
+        assert_eq!(expected, plugin.overridden_record_types().unwrap());
+    }
+
+    #[test]
+    fn record_type_roles_should_count_new_and_override_records_of_each_type() {
+        let mut weapons = record_bytes(*b"WEAP", 0, 0x0100_0800, &[]);
+        weapons.extend(record_bytes(*b"WEAP", 0, 0x0100_0801, &[]));
+        let mut npcs = record_bytes(*b"NPC_", 0, 0x0000_0007, &[]);
+        npcs.extend(record_bytes(*b"NPC_", 0, 0x0000_0008, &[]));
+        npcs.extend(record_bytes(*b"NPC_", 0, 0x0100_0802, &[]));
+        let mut groups = group_bytes(*b"WEAP", &weapons);
+        groups.extend(group_bytes(*b"NPC_", &npcs));
+
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes(&["Skyrim.esm"], &groups)),
+                ParseOptions::whole_plugin(),
+            )
+            .unwrap();
+
+        let roles = plugin.record_type_roles().unwrap();
+
+        let expected: BTreeMap<[u8; 4], (usize, usize)> =
+            [(*b"NPC_", (1, 2)), (*b"WEAP", (2, 0))].into();
+        assert_eq!(expected, roles);
+    }
+
+    #[test]
+    #[cfg(feature = "xxhash-rust")]
+    fn content_fingerprint_should_ignore_master_order_and_case() {
+        let records = group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0000_0001, &[]));
+        let mut a = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        a.parse_reader(
+            Cursor::new(plugin_bytes(&["Skyrim.esm", "Update.esm"], &records)),
+            ParseOptions::whole_plugin(),
+        )
+        .unwrap();
+
+        let records = group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0100_0001, &[]));
+        let mut b = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        b.parse_reader(
+            Cursor::new(plugin_bytes(&["update.esm", "Skyrim.esm"], &records)),
+            ParseOptions::whole_plugin(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            a.content_fingerprint().unwrap(),
+            b.content_fingerprint().unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "xxhash-rust")]
+    fn content_fingerprint_should_ignore_the_plugin_filename() {
+        let records = group_bytes(*b"GLOB", &record_bytes(*b"GLOB", 0, 0x0100_0001, &[]));
+        let bytes = plugin_bytes(&["Skyrim.esm"], &records);
+
+        let mut a = Plugin::new(GameId::SkyrimSE, Path::new("A.esp"));
+        a.parse_reader(Cursor::new(&bytes), ParseOptions::whole_plugin())
+            .unwrap();
+
+        let mut b = Plugin::new(GameId::SkyrimSE, Path::new("B.esp"));
+        b.parse_reader(Cursor::new(&bytes), ParseOptions::whole_plugin())
+            .unwrap();
+
+        assert_eq!(
+            a.content_fingerprint().unwrap(),
+            b.content_fingerprint().unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "xxhash-rust")]
+    fn content_fingerprint_should_be_stable() {
+        let records = [
+            record_bytes(*b"GLOB", 0, 0x0000_0001, &[]),
+            record_bytes(*b"GLOB", 0, 0x0100_0002, &[]),
+        ]
+        .concat();
+        let mut plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(plugin_bytes(
+                    &["Skyrim.esm"],
+                    &group_bytes(*b"GLOB", &records),
+                )),
+                ParseOptions::whole_plugin(),
+            )
+            .unwrap();
+
+        let mut bytes = b"skyrim.esm\0\0".to_vec();
+        bytes.push(0);
+        bytes.extend(2u32.to_le_bytes());
+        bytes.extend(b"\x01skyrim.esm\0");
+        bytes.extend(1u32.to_le_bytes());
+
+        assert_eq!(xxh3_64(&bytes), plugin.content_fingerprint().unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "xxhash-rust")]
+    fn content_fingerprint_should_differ_if_different_records_are_touched() {
+        let a = multiple_masters_plugin();
+        let b = plugin_with_masters(&["Skyrim.esm", "Update.esm"]);
+
+        assert_ne!(
+            a.content_fingerprint().unwrap(),
+            b.content_fingerprint().unwrap()
+        );
+    }
+
+    fn morrowind_record_bytes(record_type: [u8; 4], subrecords: &[([u8; 4], &[u8])]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (subrecord_type, subrecord_data) in subrecords {
+            data.extend(subrecord_type);
+            data.extend(u32::try_from(subrecord_data.len()).unwrap().to_le_bytes());
+            data.extend(*subrecord_data);
+        }
+
+        let mut bytes = record_type.to_vec();
+        bytes.extend(u32::try_from(data.len()).unwrap().to_le_bytes());
+        bytes.extend([0; 8]);
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn morrowind_record_ids_should_give_each_records_type_and_readable_id() {
+        let mut coordinates = (-2i32).to_le_bytes().to_vec();
+        coordinates.extend(5i32.to_le_bytes());
+        let mut cell_data = vec![0; 4];
+        cell_data.extend(&coordinates);
+
+        let mut bytes = morrowind_record_bytes(*b"TES3", &[(*b"HEDR", &[0; 300])]);
+        bytes.extend(morrowind_record_bytes(*b"GMST", &[(*b"NAME", b"sName\0")]));
+        bytes.extend(morrowind_record_bytes(
+            *b"SKIL",
+            &[(*b"INDX", &3u32.to_le_bytes())],
+        ));
+        bytes.extend(morrowind_record_bytes(
+            *b"LAND",
+            &[(*b"INTV", &coordinates)],
+        ));
+        bytes.extend(morrowind_record_bytes(
+            *b"CELL",
+            &[(*b"NAME", b"\0"), (*b"DATA", &cell_data)],
+        ));
+
+        let mut plugin = Plugin::new(GameId::Morrowind, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(&bytes), ParseOptions::whole_plugin())
+            .unwrap();
+        assert!(matches!(
+            plugin.morrowind_record_ids(),
+            Err(Error::RecordsNotRetained(_))
+        ));
+
+        let options = ParseOptions::builder()
+            .collect_morrowind_record_ids(true)
+            .build();
+        plugin.parse_reader(Cursor::new(&bytes), options).unwrap();
+        plugin.resolve_record_ids(&[]).unwrap();
+
+        let expected = vec![
+            (b"GMST", "sName".to_owned()),
+            (b"SKIL", "3".to_owned()),
+            (b"LAND", "-2, 5".to_owned()),
+            (b"CELL", "-2, 5".to_owned()),
+        ];
+        assert_eq!(expected, plugin.morrowind_record_ids().unwrap());
+    }
+
+    #[test]
+    fn morrowind_record_ids_should_be_empty_for_other_games() {
+        let plugin = multiple_masters_plugin();
+
+        assert!(plugin.morrowind_record_ids().unwrap().is_empty());
+    }
+
+    #[test]
+    fn esl_conversion_blockers_should_list_new_records_outside_the_light_range() {
+        let plugin = new_records_plugin(GameId::SkyrimSE, "Test.esp", &[0x800, 0x1000, 0x2000]);
+
+        assert_eq!(
+            vec![
+                EslBlocker::OutOfRangeFormId(0x0100_1000),
+                EslBlocker::OutOfRangeFormId(0x0100_2000)
+            ],
+            plugin.esl_conversion_blockers().unwrap()
+        );
+        assert!(!plugin.is_valid_as_light_plugin().unwrap());
+    }
+
+    #[test]
+    fn esl_conversion_blockers_should_be_unsupported_game_if_light_plugins_are_unsupported() {
+        let plugin = new_records_plugin(GameId::Skyrim, "Test.esp", &[0x800]);
+
+        assert_eq!(
+            vec![EslBlocker::UnsupportedGame],
+            plugin.esl_conversion_blockers().unwrap()
+        );
+    }
+
+    #[test]
+    fn resolved_record_ids_should_be_sorted() {
+        let plugin = multiple_masters_plugin();
+
+        let record_ids = plugin.resolved_record_ids().unwrap();
+
+        assert_eq!(4, record_ids.len());
+        assert!(record_ids.windows(2).all(|w| w[0] <= w[1]));
+        let object_indices: Vec<_> = record_ids
+            .iter()
+            .filter_map(ResolvedRecordId::object_index)
+            .collect();
+        assert_eq!(vec![1, 2, 3, 4], object_indices);
+    }
+
+    #[test]
+    fn resolved_record_ids_should_error_if_record_ids_are_unresolved() {
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new("Test.esp"));
+        plugin.data.record_ids = RecordIds::FormIds(typed_form_ids(&[0x0000_0001]));
+
+        assert!(plugin.resolved_record_ids().is_err());
+    }
+
+    #[test]
+    fn record_stats_should_count_new_and_override_records() {
+        let plugin = multiple_masters_plugin();
+
+        assert_eq!(
+            RecordStats {
+                total: 4,
+                new: 1,
+                overrides: 3,
+            },
+            plugin.record_stats().unwrap()
+        );
+
+        let plugin = Plugin::new(GameId::SkyrimSE, Path::new("Test.esp"));
+        assert_eq!(RecordStats::default(), plugin.record_stats().unwrap());
+    }
+
+    #[test]
+    fn record_stats_should_error_if_record_ids_are_unresolved() {
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new("Test.esp"));
+        plugin.data.record_ids = RecordIds::FormIds(typed_form_ids(&[0x0000_0001]));
+
+        assert!(plugin.record_stats().is_err());
+    }
+
+    #[test]
+    fn raw_record_count_should_not_need_record_ids_to_be_resolved() {
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new("Test.esp"));
+        assert_eq!(0, plugin.raw_record_count());
+
+        plugin.data.record_ids = RecordIds::FormIds(typed_form_ids(&[0x0000_0001, 0x0100_0002]));
+        assert_eq!(2, plugin.raw_record_count());
+
+        assert_eq!(4, multiple_masters_plugin().raw_record_count());
+    }
+
+    #[test]
+    fn resolved_ids_filtered_should_only_return_ids_that_match_the_predicate() {
+        let plugin = multiple_masters_plugin();
+        let object_indices = |ids: Vec<ResolvedRecordId>| -> Vec<u32> {
+            ids.iter()
+                .filter_map(ResolvedRecordId::object_index)
+                .collect()
+        };
+
+        let overrides = plugin
+            .resolved_ids_filtered(ResolvedRecordId::is_overridden_record)
+            .unwrap();
+        assert_eq!(vec![1, 2, 3], object_indices(overrides));
+
+        let new_records = plugin
+            .resolved_ids_filtered(|id| !id.is_overridden_record())
+            .unwrap();
+        assert_eq!(vec![4], object_indices(new_records));
+
+        let in_range = plugin
+            .resolved_ids_filtered(|id| id.object_index().is_some_and(|i| i >= 2))
+            .unwrap();
+        assert_eq!(vec![2, 3, 4], object_indices(in_range));
+    }
+
+    #[test]
+    fn resolved_ids_filtered_should_error_if_record_ids_are_unresolved() {
+        let mut plugin = Plugin::new(GameId::Starfield, Path::new("Test.esp"));
+        plugin.data.record_ids = RecordIds::FormIds(typed_form_ids(&[0x0000_0001]));
+
+        assert!(plugin.resolved_ids_filtered(|_| true).is_err());
+    }
+
     #[test]
     fn resolve_form_ids_should_use_plugin_names_case_insensitively() {
         let raw_form_ids = vec![0x0000_0001, 0x0100_0002];
@@ -17,8 +17,13 @@
  * along with esplugin. If not, see <http://www.gnu.org/licenses/>.
  */
 use std::io;
+#[cfg(feature = "compressed-fields")]
+use std::io::Read;
 use std::num::NonZeroU32;
 
+#[cfg(feature = "compressed-fields")]
+use flate2::read::ZlibDecoder;
+
 use nom::bytes::complete::take;
 use nom::combinator::{cond, map, map_res};
 use nom::number::complete::le_u32;
@@ -34,6 +39,51 @@ pub(crate) const MAX_RECORD_HEADER_LENGTH: usize = 24;
 const RECORD_TYPE_LENGTH: usize = 4;
 pub(crate) type RecordType = [u8; 4];
 
+/// Counts the records that have been read, to limit how many can be, and
+/// optionally collects their types in the order they're read.
+#[derive(Clone, Debug)]
+pub(crate) struct RecordCounter {
+    count: usize,
+    limit: usize,
+    record_types: Option<Vec<RecordType>>,
+}
+
+impl RecordCounter {
+    pub(crate) fn new(limit: Option<usize>, collect_record_types: bool) -> Self {
+        Self {
+            count: 0,
+            limit: limit.unwrap_or(usize::MAX),
+            record_types: collect_record_types.then(Vec::new),
+        }
+    }
+
+    pub(crate) fn into_record_types(self) -> Option<Vec<RecordType>> {
+        self.record_types
+    }
+
+    /// Counts a record, erroring if that takes the count over the limit.
+    /// `header_bytes` are used to give the error context.
+    pub(crate) fn count(&mut self, header_bytes: &[u8]) -> Result<(), Error> {
+        self.count = self.count.saturating_add(1);
+        if self.count > self.limit {
+            Err(Error::ParsingError(
+                header_bytes.into(),
+                ParsingErrorKind::BudgetExceeded(self.limit),
+            ))
+        } else {
+            if let Some(record_types) = &mut self.record_types {
+                if let Some(record_type) = header_bytes
+                    .get(..4)
+                    .and_then(|b| RecordType::try_from(b).ok())
+                {
+                    record_types.push(record_type);
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Hash, Default)]
 pub(crate) struct RecordHeader {
     record_type: RecordType,
@@ -47,38 +97,68 @@ impl RecordHeader {
         (self.flags & 0x0004_0000) != 0
     }
 
+    pub(crate) fn is_deleted(&self) -> bool {
+        (self.flags & 0x20) != 0
+    }
+
     pub(crate) fn flags(&self) -> u32 {
         self.flags
     }
+
+    pub(crate) fn form_id(&self) -> Option<NonZeroU32> {
+        self.form_id
+    }
+
+    pub(crate) fn record_type(&self) -> &RecordType {
+        &self.record_type
+    }
+
+    pub(crate) fn size_of_subrecords(&self) -> u32 {
+        self.size_of_subrecords
+    }
 }
 
+/// A record and its subrecords, as read from a plugin.
 #[derive(Clone, PartialEq, Eq, Debug, Hash, Default)]
-pub(crate) struct Record {
+pub struct Record {
     header: RecordHeader,
     subrecords: Vec<Subrecord>,
+    /// The record's compressed data, kept instead of its subrecords if the
+    /// record is compressed and decompression support isn't enabled.
+    compressed_data: Option<Vec<u8>>,
 }
 
 impl Record {
+    /// Check if this record has the same type, flags and subrecords as the
+    /// other record, ignoring their FormIDs. Compressed records that couldn't
+    /// be decompressed are compared using their compressed data.
+    pub(crate) fn has_same_content(&self, other: &Record) -> bool {
+        self.header.record_type == other.header.record_type
+            && self.header.flags == other.header.flags
+            && self.subrecords == other.subrecords
+            && self.compressed_data == other.compressed_data
+    }
+
     pub(crate) fn read<R: std::io::Read>(
         reader: &mut R,
         game_id: GameId,
         expected_type: &[u8],
     ) -> Result<Record, Error> {
-        let mut header_bytes: Vec<u8> = vec![0; usize::from(header_length(game_id))];
-        reader.read_exact(&mut header_bytes)?;
-
-        if !header_bytes.starts_with(expected_type) {
-            // Take a copy of 16 bytes so the output includes the FormID.
-            return Err(Error::ParsingError(
-                header_bytes.get(..16).unwrap_or(&header_bytes).into(),
-                ParsingErrorKind::UnexpectedRecordType(expected_type.to_vec()),
-            ));
-        }
+        Self::read_with_limit(reader, game_id, expected_type, usize::MAX)
+    }
 
-        let header = all_consuming(record_header(&header_bytes, game_id))?;
+    /// Reads a record, erroring instead of allocating a buffer for more than
+    /// `max_alloc` bytes of record data.
+    pub(crate) fn read_with_limit<R: std::io::Read>(
+        reader: &mut R,
+        game_id: GameId,
+        expected_type: &[u8],
+        max_alloc: usize,
+    ) -> Result<Record, Error> {
+        let (header_bytes, header) = read_header(reader, game_id, expected_type)?;
 
-        let mut subrecord_bytes: Vec<u8> = vec![0; u32_to_usize(header.size_of_subrecords)];
-        reader.read_exact(&mut subrecord_bytes)?;
+        let subrecord_bytes =
+            read_data(reader, header.size_of_subrecords, max_alloc, &header_bytes)?;
 
         let subrecords: Vec<Subrecord> = all_consuming(parse_subrecords(
             &subrecord_bytes,
@@ -86,7 +166,95 @@ impl Record {
             header.are_subrecords_compressed(),
         ))?;
 
-        Ok(Record { header, subrecords })
+        Ok(Record {
+            header,
+            subrecords,
+            compressed_data: None,
+        })
+    }
+
+    /// Reads a record's header and then its subrecords until the end of its
+    /// MAST and DATA subrecords, without reading the rest of the record. Only
+    /// the MAST and DATA subrecords are kept. If the record has no MAST
+    /// subrecords, all of its data is read.
+    pub(crate) fn read_masters<R: std::io::Read>(
+        reader: &mut R,
+        game_id: GameId,
+        expected_type: &[u8],
+        max_alloc: usize,
+    ) -> Result<Record, Error> {
+        let (header_bytes, header) = read_header(reader, game_id, expected_type)?;
+
+        let subrecord_header_length = match game_id {
+            GameId::Morrowind => 8,
+            _ => 6,
+        };
+
+        let mut subrecords = Vec::new();
+        let mut remaining_size = header.size_of_subrecords;
+        let mut large_subrecord_size: u32 = 0;
+        let mut found_master = false;
+
+        while remaining_size > 0 {
+            let mut subrecord_bytes = vec![0; subrecord_header_length];
+            read_exact(reader, &mut subrecord_bytes, Some(&header_bytes))?;
+
+            let subrecord_type = subrecord_bytes.get(..4).unwrap_or_default();
+            let is_master_data = subrecord_type == b"MAST" || subrecord_type == b"DATA";
+            if found_master && !is_master_data {
+                break;
+            }
+
+            let data_size = if game_id == GameId::Morrowind {
+                crate::le_slice_to_u32(subrecord_bytes.get(4..).unwrap_or_default()).ok()
+            } else if large_subrecord_size != 0 {
+                Some(large_subrecord_size)
+            } else {
+                subrecord_bytes
+                    .get(4..6)
+                    .and_then(|b| <[u8; 2]>::try_from(b).ok())
+                    .map(|b| u32::from(u16::from_le_bytes(b)))
+            };
+
+            let size_mismatch = || {
+                Error::ParsingError(
+                    header_bytes.as_slice().into(),
+                    ParsingErrorKind::RecordDataSizeMismatch(header.size_of_subrecords),
+                )
+            };
+
+            remaining_size = data_size
+                .and_then(|s| s.checked_add(u32::try_from(subrecord_header_length).ok()?))
+                .and_then(|s| remaining_size.checked_sub(s))
+                .ok_or_else(size_mismatch)?;
+
+            let data = read_data(
+                reader,
+                data_size.unwrap_or_default(),
+                max_alloc,
+                &subrecord_bytes,
+            )?;
+            subrecord_bytes.extend(data);
+
+            if subrecord_bytes.starts_with(b"XXXX") {
+                large_subrecord_size = parse_subrecord_data_as_u32(&subrecord_bytes)?.1;
+                continue;
+            }
+
+            if is_master_data {
+                found_master = found_master || subrecord_bytes.starts_with(b"MAST");
+                let (_, subrecord) =
+                    Subrecord::new(&subrecord_bytes, game_id, large_subrecord_size, false)?;
+                subrecords.push(subrecord);
+            }
+            large_subrecord_size = 0;
+        }
+
+        Ok(Record {
+            header,
+            subrecords,
+            compressed_data: None,
+        })
     }
 
     pub(crate) fn read_record_id<R: io::BufRead + io::Seek>(
@@ -94,6 +262,7 @@ impl Record {
         game_id: GameId,
         header_buffer: &mut [u8],
         header_already_read: bool,
+        max_alloc: usize,
     ) -> Result<(u32, Option<RecordId>), Error> {
         let (header, header_length_read) = if header_already_read {
             let header = all_consuming(record_header(header_buffer, game_id))?;
@@ -103,7 +272,7 @@ impl Record {
 
             // Get a slice of the right size from the header buffer.
             if let Some(header_bytes) = header_buffer.get_mut(..usize::from(header_length)) {
-                reader.read_exact(header_bytes)?;
+                read_exact(reader, header_bytes, None)?;
 
                 let header = all_consuming(record_header(header_bytes, game_id))?;
                 (header, header_length)
@@ -116,38 +285,259 @@ impl Record {
         };
 
         if game_id == GameId::Morrowind {
-            let mut subrecords_data = vec![0; u32_to_usize(header.size_of_subrecords)];
-            reader.read_exact(&mut subrecords_data)?;
+            let subrecords_data =
+                read_data(reader, header.size_of_subrecords, max_alloc, header_buffer)?;
 
             let bytes_read = u32::from(header_length_read) + header.size_of_subrecords;
 
             let (_, record_id) = parse_morrowind_record_id(&subrecords_data, &header)?;
             Ok((bytes_read, record_id))
         } else {
-            // Seeking discards the current buffer, so only do so if the data
-            // to be skipped doesn't fit in the buffer anyway.
-            let buffer = reader.fill_buf()?;
-            let usize_of_subrecords = u32_to_usize(header.size_of_subrecords);
-            if usize_of_subrecords > buffer.len() {
-                reader.seek(io::SeekFrom::Current(i64::from(header.size_of_subrecords)))?;
-            } else {
-                reader.consume(usize_of_subrecords);
-            }
+            skip_bytes(reader, header.size_of_subrecords)?;
 
             Ok((
                 u32::from(header_length_read) + header.size_of_subrecords,
-                header.form_id.map(RecordId::FormId),
+                header
+                    .form_id
+                    .map(|f| RecordId::FormId(f, header.record_type)),
             ))
         }
     }
 
+    /// Reads the rest of a record whose header has already been read into
+    /// `header_bytes`, returning the number of bytes read along with the
+    /// record and its ID.
+    pub(crate) fn read_with_header<R: io::Read>(
+        reader: &mut R,
+        game_id: GameId,
+        header_bytes: &[u8],
+        max_alloc: usize,
+    ) -> Result<(u32, Record, Option<RecordId>), Error> {
+        let header = all_consuming(record_header(header_bytes, game_id))?;
+
+        let subrecord_bytes =
+            read_data(reader, header.size_of_subrecords, max_alloc, header_bytes)?;
+
+        let record_id = if game_id == GameId::Morrowind {
+            parse_morrowind_record_id(&subrecord_bytes, &header)?.1
+        } else {
+            header
+                .form_id
+                .map(|f| RecordId::FormId(f, header.record_type))
+        };
+
+        let (subrecords, compressed_data) = if header.are_subrecords_compressed() {
+            parse_compressed_subrecords(&subrecord_bytes, game_id, max_alloc)?
+        } else {
+            (
+                parse_sized_subrecords(&subrecord_bytes, game_id, &header, header_bytes)?,
+                None,
+            )
+        };
+
+        Ok((
+            header.size_of_subrecords,
+            Record {
+                header,
+                subrecords,
+                compressed_data,
+            },
+            record_id,
+        ))
+    }
+
+    /// Skips over the rest of a record whose header has already been read into
+    /// `header_bytes`, returning the number of bytes read and, if the record is
+    /// compressed, the compressed and decompressed sizes of its data.
+    pub(crate) fn read_compressed_size<R: io::BufRead + io::Seek>(
+        reader: &mut R,
+        game_id: GameId,
+        header_bytes: &[u8],
+    ) -> Result<(u32, Option<(u32, u32)>), Error> {
+        let header = all_consuming(record_header(header_bytes, game_id))?;
+
+        if !header.are_subrecords_compressed() {
+            skip_bytes(reader, header.size_of_subrecords)?;
+            return Ok((header.size_of_subrecords, None));
+        }
+
+        // Compressed data starts with the size of the decompressed data.
+        let Some(remaining_size) = header.size_of_subrecords.checked_sub(4) else {
+            return Err(Error::ParsingError(
+                header_bytes.into(),
+                ParsingErrorKind::GenericParserError("Record::read_compressed_size".into()),
+            ));
+        };
+
+        let mut decompressed_size = [0; 4];
+        read_exact(reader, &mut decompressed_size, Some(header_bytes))?;
+        skip_bytes(reader, remaining_size)?;
+
+        Ok((
+            header.size_of_subrecords,
+            Some((
+                header.size_of_subrecords,
+                u32::from_le_bytes(decompressed_size),
+            )),
+        ))
+    }
+
+    /// Skips past the data of the record with the given header, returning the
+    /// size of the data that was skipped.
+    pub(crate) fn skip<R: io::BufRead + io::Seek>(
+        reader: &mut R,
+        game_id: GameId,
+        header_bytes: &[u8],
+    ) -> Result<u32, Error> {
+        let header = all_consuming(record_header(header_bytes, game_id))?;
+
+        skip_bytes(reader, header.size_of_subrecords)?;
+
+        Ok(header.size_of_subrecords)
+    }
+
     pub(crate) fn header(&self) -> &RecordHeader {
         &self.header
     }
 
-    pub(crate) fn subrecords(&self) -> &[Subrecord] {
+    /// Get the record's type, e.g. `b"GLOB"`.
+    pub fn record_type(&self) -> &[u8; 4] {
+        self.header.record_type()
+    }
+
+    /// Get the record's flags.
+    pub fn flags(&self) -> u32 {
+        self.header.flags
+    }
+
+    /// Get the record's FormID as it appears in the plugin. Morrowind records
+    /// don't have FormIDs, so this is `None` for them, and it's also `None` for
+    /// records with a FormID of zero, such as plugin header records.
+    pub fn form_id(&self) -> Option<u32> {
+        self.header.form_id.map(NonZeroU32::get)
+    }
+
+    /// Check if the record's data is compressed.
+    pub fn is_compressed(&self) -> bool {
+        self.header.are_subrecords_compressed()
+    }
+
+    /// Get the record's subrecords, in the order that they appear. A
+    /// compressed record has no subrecords unless the `compressed-fields`
+    /// feature is enabled, see `compressed_data()`.
+    pub fn subrecords(&self) -> &[Subrecord] {
         &self.subrecords
     }
+
+    /// Get the record's data as it is stored in the plugin if the record is
+    /// compressed and the `compressed-fields` feature isn't enabled, as its
+    /// subrecords can't be read. The data is the size of the decompressed
+    /// subrecords followed by the zlib-compressed subrecords. This is `None`
+    /// for all other records.
+    pub fn compressed_data(&self) -> Option<&[u8]> {
+        self.compressed_data.as_deref()
+    }
+
+    /// Get the record's subrecords, erroring if the record is compressed and
+    /// they couldn't be read because decompression support isn't enabled.
+    pub(crate) fn readable_subrecords(&self) -> Result<&[Subrecord], Error> {
+        if self.compressed_data.is_some() {
+            Err(Error::ParsingError(
+                self.header.record_type.as_slice().into(),
+                ParsingErrorKind::UnsupportedCompression,
+            ))
+        } else {
+            Ok(&self.subrecords)
+        }
+    }
+}
+
+/// Reads a record header, checking that it is for a record of the expected
+/// type. Returns the header's bytes along with the parsed header.
+fn read_header<R: io::Read>(
+    reader: &mut R,
+    game_id: GameId,
+    expected_type: &[u8],
+) -> Result<(Vec<u8>, RecordHeader), Error> {
+    let mut header_bytes: Vec<u8> = vec![0; usize::from(header_length(game_id))];
+    read_exact(reader, &mut header_bytes, None)?;
+
+    if !header_bytes.starts_with(expected_type) {
+        // Take a copy of 16 bytes so the output includes the FormID.
+        return Err(Error::ParsingError(
+            header_bytes.get(..16).unwrap_or(&header_bytes).into(),
+            ParsingErrorKind::UnexpectedRecordType(expected_type.to_vec()),
+        ));
+    }
+
+    let header = all_consuming(record_header(&header_bytes, game_id))?;
+
+    Ok((header_bytes, header))
+}
+
+/// Reads `size` bytes of data into a new buffer, unless that's more than
+/// `max_alloc` bytes. `header_bytes` are used to give the error context.
+fn read_data<R: io::Read>(
+    reader: &mut R,
+    size: u32,
+    max_alloc: usize,
+    header_bytes: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let size = u32_to_usize(size);
+    if size > max_alloc {
+        return Err(Error::ParsingError(
+            header_bytes.into(),
+            ParsingErrorKind::OversizedField(size),
+        ));
+    }
+
+    let mut data = vec![0; size];
+    read_exact(reader, &mut data, Some(header_bytes))?;
+
+    Ok(data)
+}
+
+/// Fills `buffer` from the reader, erroring if the reader's data ends first.
+/// The error is given `context` if it's provided, and otherwise the bytes
+/// that were read into `buffer`.
+pub(crate) fn read_exact<R: io::Read>(
+    reader: &mut R,
+    buffer: &mut [u8],
+    context: Option<&[u8]>,
+) -> Result<(), Error> {
+    let expected = buffer.len();
+    let mut offset = 0;
+
+    while offset < expected {
+        match reader.read(buffer.get_mut(offset..).unwrap_or_default()) {
+            Ok(0) => {
+                let context = context.or_else(|| buffer.get(..offset)).unwrap_or_default();
+                return Err(Error::ParsingError(
+                    context.into(),
+                    ParsingErrorKind::UnexpectedEof { expected, offset },
+                ));
+            }
+            Ok(bytes_read) => offset = offset.saturating_add(bytes_read),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(())
+}
+
+fn skip_bytes<R: io::BufRead + io::Seek>(reader: &mut R, count: u32) -> io::Result<()> {
+    // Seeking discards the current buffer, so only do so if the data
+    // to be skipped doesn't fit in the buffer anyway.
+    let buffer = reader.fill_buf()?;
+    let usize_count = u32_to_usize(count);
+    if usize_count > buffer.len() {
+        reader.seek(io::SeekFrom::Current(i64::from(count)))?;
+    } else {
+        reader.consume(usize_count);
+    }
+
+    Ok(())
 }
 
 fn parse_morrowind_record_id<'a>(
@@ -161,13 +551,56 @@ fn parse_morrowind_record_id<'a>(
         let (remaining_input, subrecords) = parse_id_subrecords(subrecords_data, types)?;
         let data = record_id_subrecord_mapper(header.record_type, &subrecords);
 
-        let namespaced_id =
-            data.map(|data| RecordId::NamespacedId(NamespacedId::new(header.record_type, data)));
+        let namespaced_id = data.map(|data| {
+            // CELL and PGRD records are identified by their NAME data or by
+            // their grid coordinates, so check which was used.
+            let is_text = match &header.record_type {
+                b"SKIL" | b"MGEF" | b"LAND" => false,
+                b"CELL" | b"PGRD" => subrecords
+                    .iter()
+                    .any(|s| s.subrecord_type() == b"NAME" && std::ptr::eq(s.data(), data)),
+                _ => true,
+            };
+
+            RecordId::NamespacedId(
+                NamespacedId::new(header.record_type, data),
+                morrowind_id_string(data, is_text),
+            )
+        });
 
         Ok((remaining_input, namespaced_id))
     }
 }
 
+/// Get a human-readable form of a Morrowind record's ID data. Most records are
+/// identified by a string, but some are identified by an index or by grid
+/// coordinates.
+fn morrowind_id_string(id_data: &[u8], is_text: bool) -> String {
+    let text = || {
+        let text = memchr::memchr(0, id_data).map_or(id_data, |i| id_data.split_at(i).0);
+        encoding_rs::WINDOWS_1252
+            .decode_without_bom_handling(text)
+            .0
+            .into_owned()
+    };
+
+    if is_text {
+        return text();
+    }
+
+    match id_data.len() {
+        4 => crate::le_slice_to_u32(id_data).map_or_else(|_| text(), |i| i.to_string()),
+        8 => match (
+            crate::le_slice_to_i32(id_data),
+            id_data.get(4..).map(crate::le_slice_to_i32),
+        ) {
+            (Ok(x), Some(Ok(y))) => format!("{x}, {y}"),
+            _ => text(),
+        },
+        _ => text(),
+    }
+}
+
 fn all_consuming<I, T, E>(result: IResult<I, T, E>) -> Result<T, nom::Err<E>>
 where
     I: nom::Input,
@@ -274,7 +707,7 @@ fn record_id_subrecord_types(record_type: RecordType) -> Vec<&'static SubrecordT
     }
 }
 
-fn header_length(game_id: GameId) -> u8 {
+pub(crate) fn header_length(game_id: GameId) -> u8 {
     match game_id {
         GameId::Morrowind => 16,
         GameId::Oblivion => 20,
@@ -335,6 +768,75 @@ fn parse_subrecords(
     Ok((input1, subrecords))
 }
 
+/// Parses uncompressed subrecord data that should be exactly the record data
+/// size given in the record's header.
+fn parse_sized_subrecords(
+    data: &[u8],
+    game_id: GameId,
+    header: &RecordHeader,
+    header_bytes: &[u8],
+) -> Result<Vec<Subrecord>, Error> {
+    match parse_subrecords(data, game_id, false) {
+        Ok((_, subrecords)) => Ok(subrecords),
+        Err(_) => Err(Error::ParsingError(
+            header_bytes.into(),
+            ParsingErrorKind::RecordDataSizeMismatch(header.size_of_subrecords),
+        )),
+    }
+}
+
+/// Compressed record data is the size of the decompressed data followed by the
+/// zlib-compressed subrecords. Returns the decompressed subrecords, and no
+/// compressed data.
+#[cfg(feature = "compressed-fields")]
+fn parse_compressed_subrecords(
+    data: &[u8],
+    game_id: GameId,
+    max_alloc: usize,
+) -> Result<(Vec<Subrecord>, Option<Vec<u8>>), Error> {
+    let Some(compressed_data) = data.get(4..) else {
+        return Err(Error::ParsingError(
+            data.into(),
+            ParsingErrorKind::GenericParserError("parse_compressed_subrecords".into()),
+        ));
+    };
+
+    // Limit how much is decompressed, as the data could decompress to any size.
+    let limit = u64::try_from(max_alloc).map_or(u64::MAX, |l| l.saturating_add(1));
+    let mut decoder = ZlibDecoder::new(compressed_data).take(limit);
+    let mut decompressed_data: Vec<u8> = Vec::new();
+    decoder.read_to_end(&mut decompressed_data)?;
+
+    if decompressed_data.len() > max_alloc {
+        // Compressed data starts with the size of the decompressed data.
+        let declared_size =
+            crate::le_slice_to_u32(data).map_or(decompressed_data.len(), u32_to_usize);
+        return Err(Error::ParsingError(
+            data.get(..4).unwrap_or_default().into(),
+            ParsingErrorKind::OversizedField(declared_size),
+        ));
+    }
+
+    let subrecords = all_consuming(parse_subrecords(&decompressed_data, game_id, false))?;
+
+    Ok((subrecords, None))
+}
+
+/// Without decompression support, a compressed record's subrecords can't be
+/// read, so its compressed data is kept instead.
+#[cfg(not(feature = "compressed-fields"))]
+#[expect(
+    clippy::unnecessary_wraps,
+    reason = "The signature must match the decompressing implementation"
+)]
+fn parse_compressed_subrecords(
+    data: &[u8],
+    _game_id: GameId,
+    _max_alloc: usize,
+) -> Result<(Vec<Subrecord>, Option<Vec<u8>>), Error> {
+    Ok((Vec::new(), Some(data.to_vec())))
+}
+
 /// Parses subrecords until one of each given subrecord type has been read, then
 /// skips to the end of the record and returns.
 fn parse_id_subrecords<'a>(
@@ -411,6 +913,7 @@ mod tests {
                 GameId::Morrowind,
                 &mut header_buf,
                 false,
+                usize::MAX,
             )
             .unwrap()
             .1;
@@ -429,16 +932,17 @@ mod tests {
                 GameId::Morrowind,
                 &mut header_buf,
                 false,
+                usize::MAX,
             )
             .unwrap()
             .1
             .unwrap();
 
             match form_id {
-                RecordId::NamespacedId(_) => {
+                RecordId::NamespacedId(..) => {
                     // Do nothing
                 }
-                RecordId::FormId(_) => panic!("Expected a namespaced ID"),
+                RecordId::FormId(..) => panic!("Expected a namespaced ID"),
             }
         }
 
@@ -680,16 +1184,17 @@ mod tests {
                 GameId::Oblivion,
                 &mut header_buf,
                 false,
+                usize::MAX,
             )
             .unwrap()
             .1
             .unwrap();
 
             match form_id {
-                RecordId::FormId(f) => {
+                RecordId::FormId(f, _) => {
                     assert_eq!(0xCF0, f.get());
                 }
-                RecordId::NamespacedId(_) => panic!("Expected a FormID"),
+                RecordId::NamespacedId(..) => panic!("Expected a FormID"),
             }
         }
     }
@@ -790,6 +1295,62 @@ mod tests {
             );
         }
 
+        const LARGE_SUBRECORD_SIZE: usize = 0x1_0004;
+
+        /// Builds a record with an XXXX subrecord giving the size of the large
+        /// subrecord that follows it, then the given subrecords.
+        fn large_subrecord_record_bytes(record_type: [u8; 4], subrecords: &[u8]) -> Vec<u8> {
+            let mut data = b"XXXX".to_vec();
+            data.extend(4u16.to_le_bytes());
+            data.extend(u32::try_from(LARGE_SUBRECORD_SIZE).unwrap().to_le_bytes());
+            data.extend(b"NVNM");
+            data.extend(0u16.to_le_bytes());
+            data.extend(vec![0xAB; LARGE_SUBRECORD_SIZE]);
+            data.extend(subrecords);
+
+            let mut bytes = record_type.to_vec();
+            bytes.extend(u32::try_from(data.len()).unwrap().to_le_bytes());
+            bytes.extend([0; 16]);
+            bytes.extend(data);
+            bytes
+        }
+
+        #[test]
+        fn read_should_use_xxxx_size_for_the_following_subrecord() {
+            let mut edid = b"EDID".to_vec();
+            edid.extend(2u16.to_le_bytes());
+            edid.extend(b"A\0");
+            let bytes = large_subrecord_record_bytes(*b"NAVM", &edid);
+
+            let record = Record::read(&mut Cursor::new(bytes), GameId::Skyrim, b"NAVM").unwrap();
+
+            assert_eq!(2, record.subrecords.len());
+            assert_eq!(b"NVNM", record.subrecords[0].subrecord_type());
+            assert_eq!(LARGE_SUBRECORD_SIZE, record.subrecords[0].data().len());
+            assert_eq!(b"EDID", record.subrecords[1].subrecord_type());
+            assert_eq!(b"A\0", record.subrecords[1].data());
+        }
+
+        #[test]
+        fn read_masters_should_use_xxxx_size_for_the_following_subrecord() {
+            let mut masters = b"MAST".to_vec();
+            masters.extend(11u16.to_le_bytes());
+            masters.extend(b"Skyrim.esm\0");
+            masters.extend(b"DATA");
+            masters.extend(8u16.to_le_bytes());
+            masters.extend([0; 8]);
+            let bytes = large_subrecord_record_bytes(*b"TES4", &masters);
+
+            let record =
+                Record::read_masters(&mut Cursor::new(bytes), GameId::Skyrim, b"TES4", usize::MAX)
+                    .unwrap();
+
+            assert_eq!(2, record.subrecords.len());
+            assert_eq!(b"MAST", record.subrecords[0].subrecord_type());
+            assert_eq!(b"Skyrim.esm\0", record.subrecords[0].data());
+            assert_eq!(b"DATA", record.subrecords[1].subrecord_type());
+        }
+
         #[test]
         fn parse_record_id_should_return_the_form_id() {
             let data = &include_bytes!("../testing-plugins/Skyrim/Data/Blank.esp")[0x53..0xEF];
@@ -800,16 +1361,17 @@ mod tests {
                 GameId::Skyrim,
                 &mut header_buf,
                 false,
+                usize::MAX,
             )
             .unwrap()
             .1
             .unwrap();
 
             match form_id {
-                RecordId::FormId(f) => {
+                RecordId::FormId(f, _) => {
                     assert_eq!(0xCEC, f.get());
                 }
-                RecordId::NamespacedId(_) => panic!("Expected a FormID"),
+                RecordId::NamespacedId(..) => panic!("Expected a FormID"),
             }
         }
     }
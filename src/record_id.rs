@@ -23,17 +23,29 @@ use std::hash::{Hash, Hasher};
 use std::ops::RangeInclusive;
 
 pub(crate) enum RecordId {
-    FormId(std::num::NonZeroU32),
-    NamespacedId(NamespacedId),
+    FormId(std::num::NonZeroU32, [u8; 4]),
+    /// The String field is a human-readable form of the record's ID.
+    NamespacedId(NamespacedId, String),
+}
+
+/// A FormID as it appears in a plugin, along with the type of the record that
+/// it belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct TypedFormId {
+    pub form_id: u32,
+    pub record_type: [u8; 4],
 }
 
 /// This is a FormID equivalent for Morrowind plugin records.
 /// Record IDs with the same data in the same namespace refer to the same record
 /// but if the data or namespace is different, the IDs refer to different records.
-#[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
+/// The record's type is kept but isn't part of its identity, as record types
+/// can share a namespace.
+#[derive(Clone, Debug)]
 pub(crate) struct NamespacedId {
     namespace: Namespace,
     hashed_id: u64,
+    record_type: [u8; 4],
 }
 
 impl NamespacedId {
@@ -44,8 +56,43 @@ impl NamespacedId {
         Self {
             namespace: record_type.into(),
             hashed_id: hasher.finish(),
+            record_type,
         }
     }
+
+    pub(crate) fn record_type(&self) -> [u8; 4] {
+        self.record_type
+    }
+}
+
+impl Ord for NamespacedId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.namespace.cmp(&other.namespace) {
+            Ordering::Equal => self.hashed_id.cmp(&other.hashed_id),
+            o => o,
+        }
+    }
+}
+
+impl PartialOrd for NamespacedId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for NamespacedId {
+    fn eq(&self, other: &Self) -> bool {
+        self.namespace == other.namespace && self.hashed_id == other.hashed_id
+    }
+}
+
+impl Eq for NamespacedId {}
+
+impl Hash for NamespacedId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.namespace.hash(state);
+        self.hashed_id.hash(state);
+    }
 }
 
 /// Each record's ID belongs to a namespace, depending on the record type.
@@ -161,12 +208,27 @@ pub(crate) enum RecordIdType {
     NamespacedId,
 }
 
-#[derive(Clone, Debug)]
-pub(crate) struct ResolvedRecordId {
+/// A record ID that has been resolved using a plugin's masters, so that it
+/// identifies the same record no matter which plugin it appears in.
+///
+/// Resolved IDs are equal if they identify the same record, regardless of
+/// whether the record is an override. They are ordered first by kind, with
+/// FormIDs before Morrowind's namespaced IDs. FormIDs are then ordered by
+/// object index and then by a hash of the name of the plugin that the record
+/// comes from, while namespaced IDs are ordered by namespace and then by a
+/// hash of their ID data. The hashes are stable for a given build of this
+/// library, so resolved IDs should not be persisted.
+#[derive(Clone, Copy, Debug)]
+pub struct ResolvedRecordId {
     record_id_type: RecordIdType,
     overridden_record: bool,
     hashed_data: u64,
     other_data: u32,
+    /// The FormID as it appears in the plugin, or zero for namespaced IDs.
+    /// Like `overridden_record`, this isn't part of the record's identity.
+    raw_form_id: u32,
+    /// The type of the record, or zeroes if it isn't known.
+    record_type: [u8; 4],
 }
 
 impl ResolvedRecordId {
@@ -186,6 +248,8 @@ impl ResolvedRecordId {
                 overridden_record: true,
                 hashed_data: hashed_master.hashed_name,
                 other_data: object_index,
+                raw_form_id,
+                record_type: [0; 4],
             }
         } else {
             let object_index = raw_form_id & parent_plugin.object_index_mask;
@@ -194,6 +258,8 @@ impl ResolvedRecordId {
                 overridden_record: false,
                 hashed_data: parent_plugin.hashed_name,
                 other_data: object_index,
+                raw_form_id,
+                record_type: [0; 4],
             }
         }
     }
@@ -209,13 +275,74 @@ impl ResolvedRecordId {
             overridden_record,
             hashed_data: namespaced_id.hashed_id,
             other_data: namespaced_id.namespace.into(),
+            raw_form_id: 0,
+            record_type: namespaced_id.record_type,
+        }
+    }
+
+    pub(crate) fn from_typed_form_id(
+        parent_plugin: SourcePlugin,
+        masters: &[SourcePlugin],
+        form_id: TypedFormId,
+    ) -> Self {
+        Self {
+            record_type: form_id.record_type,
+            ..Self::from_form_id(parent_plugin, masters, form_id.form_id)
         }
     }
 
-    pub(crate) fn is_overridden_record(&self) -> bool {
+    /// Get the type of the record, e.g. `*b"WEAP"`.
+    pub fn record_type(&self) -> [u8; 4] {
+        self.record_type
+    }
+
+    /// Check if the record is an override of a record from one of the
+    /// plugin's masters, rather than a new record.
+    pub fn is_overridden_record(&self) -> bool {
         self.overridden_record
     }
 
+    /// Get the record's FormID as it appears in the plugin that it was
+    /// resolved for. This is `None` for Morrowind records.
+    pub fn raw_form_id(&self) -> Option<u32> {
+        match self.record_id_type {
+            RecordIdType::FormId => Some(self.raw_form_id),
+            RecordIdType::NamespacedId => None,
+        }
+    }
+
+    /// Get the record's object index, i.e. the part of its FormID that isn't
+    /// the mod index. This is `None` for Morrowind records.
+    pub fn object_index(&self) -> Option<u32> {
+        match self.record_id_type {
+            RecordIdType::FormId => Some(self.other_data),
+            RecordIdType::NamespacedId => None,
+        }
+    }
+
+    /// Get the hash of the name of the plugin that the record comes from.
+    /// This is `None` for Morrowind records.
+    pub(crate) fn hashed_source_plugin_name(&self) -> Option<u64> {
+        match self.record_id_type {
+            RecordIdType::FormId => Some(self.hashed_data),
+            RecordIdType::NamespacedId => None,
+        }
+    }
+
+    /// Get the namespace and the hash of the ID data of a Morrowind record.
+    /// This is `None` for records that have FormIDs.
+    #[cfg(feature = "xxhash-rust")]
+    pub(crate) fn hashed_namespaced_id(&self) -> Option<(u32, u64)> {
+        match self.record_id_type {
+            RecordIdType::FormId => None,
+            RecordIdType::NamespacedId => Some((self.other_data, self.hashed_data)),
+        }
+    }
+
+    pub(crate) fn is_from_plugin(&self, hashed_plugin_name: u64) -> bool {
+        self.record_id_type == RecordIdType::FormId && self.hashed_data == hashed_plugin_name
+    }
+
     pub(crate) fn is_object_index_in(&self, range: &RangeInclusive<u32>) -> bool {
         match self.record_id_type {
             RecordIdType::FormId => range.contains(&self.other_data),
@@ -317,6 +444,16 @@ mod tests {
         assert_eq!(hashed_data, record_id.hashed_id);
     }
 
+    #[test]
+    fn namespaced_ids_should_be_equal_if_their_record_types_share_a_namespace() {
+        let book = NamespacedId::new(*b"BOOK", &[1]);
+        let weapon = NamespacedId::new(*b"WEAP", &[1]);
+
+        assert_eq!(book, weapon);
+        assert_eq!(*b"BOOK", book.record_type);
+        assert_eq!(*b"WEAP", weapon.record_type);
+    }
+
     mod source_plugin {
         use super::*;
 
@@ -489,6 +626,29 @@ mod tests {
             assert_eq!(form_id1, form_id2);
         }
 
+        #[test]
+        fn object_index_should_be_none_for_namespaced_ids() {
+            let form_id = ResolvedRecordId::from_form_id(PARENT_PLUGIN, MASTERS, 0xFD12_6789);
+            let namespaced_id = ResolvedRecordId::from_namespaced_id(
+                &NamespacedId::new(*b"BOOK", &[1]),
+                &HashSet::new(),
+            );
+
+            assert_eq!(Some(0x6789), form_id.object_index());
+            assert_eq!(None, namespaced_id.object_index());
+        }
+
+        #[test]
+        fn form_ids_should_be_ordered_before_namespaced_ids() {
+            let form_id = ResolvedRecordId::from_form_id(PARENT_PLUGIN, NO_MASTERS, 0xFFFF_FFFF);
+            let namespaced_id = ResolvedRecordId::from_namespaced_id(
+                &NamespacedId::new(*b"BOOK", &[1]),
+                &HashSet::new(),
+            );
+
+            assert!(form_id < namespaced_id);
+        }
+
         #[test]
         fn form_ids_can_be_equal_if_one_is_an_override_record_and_the_other_is_not() {
             let form_id1 = ResolvedRecordId::from_form_id(PARENT_PLUGIN, MASTERS, 0x01);
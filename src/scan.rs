@@ -0,0 +1,121 @@
+/*
+ * This file is part of esplugin
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * esplugin is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * esplugin is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with esplugin. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::game_id::GameId;
+use crate::plugin::{has_plugin_file_extension, ParseOptions, Plugin, PluginParser};
+
+/// The outcome of parsing the plugins in a directory.
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    /// The plugins that were parsed successfully.
+    pub plugins: Vec<Plugin>,
+    /// The paths of the plugins that couldn't be parsed, along with the errors
+    /// that were encountered when parsing them.
+    pub failures: Vec<(PathBuf, Error)>,
+}
+
+impl ScanResult {
+    pub fn parsed_count(&self) -> usize {
+        self.plugins.len()
+    }
+
+    pub fn failed_count(&self) -> usize {
+        self.failures.len()
+    }
+}
+
+/// Parse all the files in the given directory that have plugin file
+/// extensions, including ghosted plugins. Plugins are parsed in order of their
+/// paths. An error is only returned if the directory can't be read: errors
+/// parsing individual plugins are recorded in the result.
+pub fn scan_directory(
+    game_id: GameId,
+    directory: &Path,
+    options: ParseOptions,
+) -> Result<ScanResult, Error> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.is_file() && has_plugin_file_extension(&path) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut parser = PluginParser::new();
+    let mut result = ScanResult::default();
+    for path in paths {
+        let mut plugin = Plugin::new(game_id, &path);
+        let parse_result = File::open(&path)
+            .map_err(Error::from)
+            .and_then(|file| parser.parse_into(&mut plugin, file, options));
+
+        match parse_result {
+            Ok(()) => result.plugins.push(plugin),
+            Err(e) => result.failures.push((path, e)),
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::test_utils::plugin_bytes;
+
+    #[test]
+    fn scan_directory_should_separate_parsed_plugins_from_failures() {
+        let tmp_dir = tempdir().unwrap();
+        std::fs::write(tmp_dir.path().join("A.esp"), plugin_bytes(&[], &[])).unwrap();
+        std::fs::write(tmp_dir.path().join("B.esm.ghost"), plugin_bytes(&[], &[])).unwrap();
+        std::fs::write(tmp_dir.path().join("C.esl"), b"invalid").unwrap();
+        std::fs::write(tmp_dir.path().join("D.txt"), plugin_bytes(&[], &[])).unwrap();
+
+        let result = scan_directory(
+            GameId::SkyrimSE,
+            tmp_dir.path(),
+            ParseOptions::header_only(),
+        )
+        .unwrap();
+
+        assert_eq!(2, result.parsed_count());
+        assert_eq!(1, result.failed_count());
+        assert_eq!(Some("A.esp".to_owned()), result.plugins[0].filename());
+        assert_eq!(Some("B.esm.ghost".to_owned()), result.plugins[1].filename());
+        assert_eq!(tmp_dir.path().join("C.esl"), result.failures[0].0);
+    }
+
+    #[test]
+    fn scan_directory_should_error_if_the_directory_does_not_exist() {
+        let tmp_dir = tempdir().unwrap();
+
+        assert!(scan_directory(
+            GameId::SkyrimSE,
+            &tmp_dir.path().join("missing"),
+            ParseOptions::header_only(),
+        )
+        .is_err());
+    }
+}
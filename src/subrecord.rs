@@ -37,8 +37,9 @@ use crate::game_id::GameId;
 const SUBRECORD_TYPE_LENGTH: usize = 4;
 pub(crate) type SubrecordType = [u8; 4];
 
+/// A subrecord, as read from a plugin.
 #[derive(Clone, PartialEq, Eq, Debug, Hash, Default)]
-pub(crate) struct Subrecord {
+pub struct Subrecord {
     #[expect(clippy::struct_field_names, reason = "type is a keyword")]
     subrecord_type: SubrecordType,
     data: Vec<u8>,
@@ -84,11 +85,13 @@ impl Subrecord {
         Ok(decompressed_data)
     }
 
-    pub(crate) fn subrecord_type(&self) -> &SubrecordType {
+    /// Get the subrecord's type, e.g. `b"EDID"`.
+    pub fn subrecord_type(&self) -> &SubrecordType {
         &self.subrecord_type
     }
 
-    pub(crate) fn data(&self) -> &[u8] {
+    /// Get the subrecord's data.
+    pub fn data(&self) -> &[u8] {
         &self.data
     }
 }
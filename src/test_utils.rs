@@ -0,0 +1,96 @@
+/*
+ * This file is part of esplugin
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * esplugin is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * esplugin is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with esplugin. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+// Builders for the bytes of synthetic plugins, shared by the unit tests.
+
+pub(crate) fn subrecord_bytes(subrecord_type: [u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut bytes = subrecord_type.to_vec();
+    bytes.extend(u16::try_from(data.len()).unwrap().to_le_bytes());
+    bytes.extend(data);
+    bytes
+}
+
+pub(crate) fn record_bytes(
+    record_type: [u8; 4],
+    flags: u32,
+    form_id: u32,
+    subrecords: &[u8],
+) -> Vec<u8> {
+    let mut bytes = record_type.to_vec();
+    bytes.extend(u32::try_from(subrecords.len()).unwrap().to_le_bytes());
+    bytes.extend(flags.to_le_bytes());
+    bytes.extend(form_id.to_le_bytes());
+    bytes.extend([0; 8]);
+    bytes.extend(subrecords);
+    bytes
+}
+
+pub(crate) fn group_bytes(label: [u8; 4], contents: &[u8]) -> Vec<u8> {
+    let mut bytes = b"GRUP".to_vec();
+    bytes.extend(u32::try_from(contents.len() + 24).unwrap().to_le_bytes());
+    bytes.extend(label);
+    bytes.extend([0; 12]);
+    bytes.extend(contents);
+    bytes
+}
+
+/// Build a plugin with a 24-byte record header, as used by Skyrim and later
+/// games.
+pub(crate) fn plugin_bytes(masters: &[&str], groups: &[u8]) -> Vec<u8> {
+    plugin_bytes_with_flags(0, masters, groups)
+}
+
+pub(crate) fn plugin_bytes_with_flags(flags: u32, masters: &[&str], groups: &[u8]) -> Vec<u8> {
+    plugin_bytes_with_description(flags, b"\0", masters, groups)
+}
+
+pub(crate) fn plugin_bytes_with_description(
+    flags: u32,
+    description: &[u8],
+    masters: &[&str],
+    groups: &[u8],
+) -> Vec<u8> {
+    plugin_bytes_with_header_subrecords(flags, description, masters, &[], groups)
+}
+
+pub(crate) fn plugin_bytes_with_header_subrecords(
+    flags: u32,
+    description: &[u8],
+    masters: &[&str],
+    extra_subrecords: &[u8],
+    groups: &[u8],
+) -> Vec<u8> {
+    let mut hedr = 1.7f32.to_le_bytes().to_vec();
+    hedr.extend([0; 4]);
+    hedr.extend(0x800u32.to_le_bytes());
+
+    let mut subrecords = subrecord_bytes(*b"HEDR", &hedr);
+    subrecords.extend(subrecord_bytes(*b"SNAM", description));
+    for master in masters {
+        let mut name = master.as_bytes().to_vec();
+        name.push(0);
+        subrecords.extend(subrecord_bytes(*b"MAST", &name));
+        subrecords.extend(subrecord_bytes(*b"DATA", &[0; 8]));
+    }
+    subrecords.extend(extra_subrecords);
+
+    let mut bytes = record_bytes(*b"TES4", flags, 0, &subrecords);
+    bytes.extend(groups);
+    bytes
+}
@@ -0,0 +1,305 @@
+/*
+ * This file is part of esplugin
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * esplugin is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * esplugin is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with esplugin. If not, see <http://www.gnu.org/licenses/>.
+ */
+use encoding_rs::WINDOWS_1252;
+
+use crate::game_id::GameId;
+
+const MORROWIND_AUTHOR_LENGTH: usize = 32;
+const MORROWIND_DESCRIPTION_LENGTH: usize = 256;
+
+/// Builds the bytes of a minimal plugin, to help write tests without needing
+/// plugin files. The plugin has a header record and an empty record for each
+/// added record, with records grouped into top-level groups by type. Morrowind
+/// has no FormIDs or groups, so each Morrowind record is instead given an ID
+/// that is its "FormID" written in hexadecimal.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PluginWriter {
+    game_id: GameId,
+    flags: u32,
+    description: String,
+    masters: Vec<String>,
+    records: Vec<([u8; 4], u32)>,
+}
+
+impl PluginWriter {
+    pub fn new(game_id: GameId) -> Self {
+        Self {
+            game_id,
+            flags: 0,
+            description: String::new(),
+            masters: Vec::new(),
+            records: Vec::new(),
+        }
+    }
+
+    /// Set the flags of the plugin's header record, e.g. `0x1` for a master
+    /// plugin.
+    #[must_use]
+    pub fn flags(mut self, flags: u32) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    #[must_use]
+    pub fn description(mut self, description: &str) -> Self {
+        description.clone_into(&mut self.description);
+        self
+    }
+
+    #[must_use]
+    pub fn master(mut self, master: &str) -> Self {
+        self.masters.push(master.to_owned());
+        self
+    }
+
+    #[must_use]
+    pub fn add_record(mut self, record_type: [u8; 4], form_id: u32) -> Self {
+        self.records.push((record_type, form_id));
+        self
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        if self.game_id == GameId::Morrowind {
+            self.morrowind_bytes()
+        } else {
+            self.tes4_bytes()
+        }
+    }
+
+    fn morrowind_bytes(&self) -> Vec<u8> {
+        let mut hedr = 1.3f32.to_le_bytes().to_vec();
+        hedr.extend(self.flags.to_le_bytes());
+        hedr.extend([0; MORROWIND_AUTHOR_LENGTH]);
+        hedr.extend(fixed_length_string(
+            &self.description,
+            MORROWIND_DESCRIPTION_LENGTH,
+        ));
+        hedr.extend(length_u32(&self.records).to_le_bytes());
+
+        let mut subrecords = self.subrecord(*b"HEDR", &hedr);
+        for master in &self.masters {
+            subrecords.extend(self.subrecord(*b"MAST", &null_terminated(master)));
+            subrecords.extend(self.subrecord(*b"DATA", &[0; 8]));
+        }
+
+        let mut bytes = self.record(*b"TES3", 0, 0, &subrecords);
+        for (record_type, form_id) in &self.records {
+            let name = null_terminated(&format!("{form_id:08X}"));
+            bytes.extend(self.record(*record_type, 0, 0, &self.subrecord(*b"NAME", &name)));
+        }
+
+        bytes
+    }
+
+    fn tes4_bytes(&self) -> Vec<u8> {
+        let mut groups: Vec<([u8; 4], Vec<u8>)> = Vec::new();
+        for (record_type, form_id) in &self.records {
+            let record = self.record(*record_type, 0, *form_id, &[]);
+            match groups.iter_mut().find(|(label, _)| label == record_type) {
+                Some((_, records)) => records.extend(record),
+                None => groups.push((*record_type, record)),
+            }
+        }
+
+        let record_and_group_count = length_u32(&self.records) + length_u32(&groups);
+
+        let mut hedr = self.header_version().to_le_bytes().to_vec();
+        hedr.extend(record_and_group_count.to_le_bytes());
+        hedr.extend(0x800u32.to_le_bytes());
+
+        let mut subrecords = self.subrecord(*b"HEDR", &hedr);
+        if !self.description.is_empty() {
+            subrecords.extend(self.subrecord(*b"SNAM", &null_terminated(&self.description)));
+        }
+        for master in &self.masters {
+            subrecords.extend(self.subrecord(*b"MAST", &null_terminated(master)));
+            subrecords.extend(self.subrecord(*b"DATA", &[0; 8]));
+        }
+
+        let mut bytes = self.record(*b"TES4", self.flags, 0, &subrecords);
+        for (label, records) in groups {
+            bytes.extend(self.group(label, &records));
+        }
+
+        bytes
+    }
+
+    fn header_version(&self) -> f32 {
+        match self.game_id {
+            GameId::Morrowind => 1.3,
+            GameId::Oblivion | GameId::Fallout4 | GameId::Fallout76 => 1.0,
+            GameId::Fallout3 => 0.94,
+            GameId::FalloutNV => 1.34,
+            GameId::Skyrim | GameId::SkyrimSE => 1.7,
+            GameId::Starfield => 0.96,
+        }
+    }
+
+    fn record(&self, record_type: [u8; 4], flags: u32, form_id: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = record_type.to_vec();
+        bytes.extend(length_u32(data).to_le_bytes());
+
+        if self.game_id == GameId::Morrowind {
+            bytes.extend([0; 4]);
+            bytes.extend(flags.to_le_bytes());
+        } else {
+            bytes.extend(flags.to_le_bytes());
+            bytes.extend(form_id.to_le_bytes());
+            bytes.extend(self.header_padding());
+        }
+
+        bytes.extend(data);
+        bytes
+    }
+
+    fn group(&self, label: [u8; 4], contents: &[u8]) -> Vec<u8> {
+        let header_length = 8 + 4 + 4 + self.header_padding().len();
+
+        let mut bytes = b"GRUP".to_vec();
+        bytes.extend(
+            length_u32(contents)
+                .saturating_add(length_u32_of(header_length))
+                .to_le_bytes(),
+        );
+        bytes.extend(label);
+        bytes.extend([0; 4]);
+        bytes.extend(self.header_padding());
+        bytes.extend(contents);
+        bytes
+    }
+
+    /// The version control info and other data at the end of record and group
+    /// headers.
+    fn header_padding(&self) -> &'static [u8] {
+        match self.game_id {
+            GameId::Oblivion => &[0; 4],
+            _ => &[0; 8],
+        }
+    }
+
+    fn subrecord(&self, subrecord_type: [u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut bytes = subrecord_type.to_vec();
+        if self.game_id == GameId::Morrowind {
+            bytes.extend(length_u32(data).to_le_bytes());
+        } else {
+            let length = u16::try_from(data.len()).unwrap_or(u16::MAX);
+            bytes.extend(length.to_le_bytes());
+        }
+        bytes.extend(data);
+        bytes
+    }
+}
+
+fn null_terminated(string: &str) -> Vec<u8> {
+    let mut bytes = WINDOWS_1252.encode(string).0.into_owned();
+    bytes.push(0);
+    bytes
+}
+
+fn fixed_length_string(string: &str, length: usize) -> Vec<u8> {
+    let mut bytes = WINDOWS_1252.encode(string).0.into_owned();
+    bytes.resize(length, 0);
+    bytes
+}
+
+fn length_u32<T>(slice: &[T]) -> u32 {
+    length_u32_of(slice.len())
+}
+
+fn length_u32_of(length: usize) -> u32 {
+    u32::try_from(length).unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::path::Path;
+
+    use crate::plugin::{ParseOptions, Plugin};
+
+    use super::*;
+
+    fn parse(game_id: GameId, bytes: Vec<u8>) -> Plugin {
+        let mut plugin = Plugin::new(game_id, Path::new("Test.esp"));
+        plugin
+            .parse_reader(Cursor::new(bytes), ParseOptions::whole_plugin())
+            .unwrap();
+        plugin
+    }
+
+    #[test]
+    fn to_bytes_should_write_a_plugin_that_can_be_parsed() {
+        for game_id in [
+            GameId::Oblivion,
+            GameId::Skyrim,
+            GameId::Fallout3,
+            GameId::FalloutNV,
+            GameId::Fallout4,
+            GameId::SkyrimSE,
+            GameId::Fallout76,
+        ] {
+            let bytes = PluginWriter::new(game_id)
+                .flags(0x1)
+                .description("A description")
+                .master("A.esm")
+                .add_record(*b"WEAP", 0x0000_0001)
+                .add_record(*b"NPC_", 0x0100_0800)
+                .add_record(*b"WEAP", 0x0100_0801)
+                .to_bytes();
+
+            let plugin = parse(game_id, bytes);
+
+            assert!(plugin.is_master_file());
+            assert_eq!(vec!["A.esm"], plugin.masters().unwrap());
+            assert_eq!(Some("A description".into()), plugin.description().unwrap());
+            assert_eq!(Some(5), plugin.record_and_group_count());
+            assert_eq!(1, plugin.count_override_records().unwrap());
+            assert_eq!(3, plugin.resolved_record_ids().unwrap().len());
+        }
+    }
+
+    #[test]
+    fn to_bytes_should_write_a_morrowind_plugin_that_can_be_parsed() {
+        let bytes = PluginWriter::new(GameId::Morrowind)
+            .flags(0x1)
+            .description("A description")
+            .master("A.esm")
+            .add_record(*b"WEAP", 1)
+            .to_bytes();
+
+        let mut plugin = Plugin::new(GameId::Morrowind, Path::new("Test.esp"));
+        plugin
+            .parse_reader(
+                Cursor::new(bytes),
+                ParseOptions::builder()
+                    .collect_morrowind_record_ids(true)
+                    .build(),
+            )
+            .unwrap();
+
+        assert!(plugin.is_master_file());
+        assert_eq!(vec!["A.esm"], plugin.masters().unwrap());
+        assert_eq!(Some("A description".into()), plugin.description().unwrap());
+        assert_eq!(Some(1), plugin.record_and_group_count());
+        assert_eq!(
+            vec![(b"WEAP", "00000001".to_owned())],
+            plugin.morrowind_record_ids().unwrap()
+        );
+    }
+}